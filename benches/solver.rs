@@ -0,0 +1,72 @@
+//! Benchmarks of the solver, of sending cards home automatically and of
+//! looking for the moves available, over the same deals on every run.
+//! Criterion compares each run with the last one, kept as the baseline
+//! under target/criterion.
+//!
+//! Run them with `cargo bench --bench solver`.
+//!
+//! Baseline, the median of a release build on a single development
+//! machine, to tell at a glance whether a change is in the same range:
+//!
+//! | Benchmark                        | Seed 1   | Seed 7   | Seed 42 | Seed 2021 |
+//! |----------------------------------|----------|----------|---------|-----------|
+//! | solve                            | 88 ms    | 840 ms   | 5.9 s   | 5.7 s     |
+//! | auto_moves                       | 4.9 µs   | 3.7 µs   | 3.6 µs  | 7.1 µs    |
+//!
+//! | Benchmark                        | Seed 1   |
+//! |----------------------------------|----------|
+//! | moves_available/repeated         | 56 ns    |
+//! | moves_available/after a change   | 2.4 µs   |
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use klondike::card_game::klondike::solver::solve;
+use klondike::card_game::klondike::{AutoPolicy, Klondike};
+
+/// Seeds of the deals benchmarked
+pub const SEEDS: [u64; 4] = [1, 7, 42, 2021];
+
+/// Searches can visit a lot of positions, so fewer samples are taken
+const SOLVER_SAMPLE_SIZE: usize = 10;
+
+fn bench_solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+    group.sample_size(SOLVER_SAMPLE_SIZE);
+    for seed in SEEDS.iter() {
+        let klondike = Klondike::new_with_seed(*seed);
+        group.bench_function(format!("seed {}", seed), |b| {
+            b.iter(|| solve(black_box(&klondike)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_auto_moves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("auto_moves");
+    for seed in SEEDS.iter() {
+        let klondike = Klondike::new_with_seed(*seed);
+        group.bench_function(format!("seed {}", seed), |b| {
+            b.iter_batched(|| klondike.clone(),
+                |mut x| x.auto_moves(AutoPolicy::Aggressive),
+                BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+/// Asking again for the moves of a game that didn't change, which just
+/// copies the ones found before, against looking for them after a change
+fn bench_moves_available(c: &mut Criterion) {
+    let mut group = c.benchmark_group("moves_available");
+    let klondike = Klondike::new_with_seed(SEEDS[0]);
+    klondike.moves_available();
+    group.bench_function("repeated", |b| b.iter(|| black_box(&klondike).moves_available()));
+    group.bench_function("after a change", |b| {
+        b.iter_batched(|| klondike.clone(),
+            |x| x.moves_available(),
+            BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_solve, bench_auto_moves, bench_moves_available);
+criterion_main!(benches);
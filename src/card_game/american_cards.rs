@@ -1,9 +1,11 @@
 use ansi_term::Colour::*;
+use std::convert::TryFrom;
 use std::fmt;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 
-#[derive(Debug, EnumIter, Copy, Clone, PartialEq, Serialize)]
+#[derive(Debug, EnumIter, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CardSuit {
     CLUBS,
     DIAMONDS,
@@ -11,7 +13,7 @@ pub enum CardSuit {
     SPADES,
 }
 
-#[derive(Debug, EnumIter, Copy, Clone, PartialEq, Serialize)]
+#[derive(Debug, EnumIter, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CardRank {
     ACE = 1,
     TWO,
@@ -28,7 +30,76 @@ pub enum CardRank {
     KING,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+/// Color of the suits
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Black,
+}
+
+/// Error returned when a number doesn't match any rank or suit
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InvalidCardValue(pub u8);
+
+impl CardSuit {
+    /// Numeric value of the suit, from 0 (CLUBS) to 3 (SPADES)
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            CardSuit::DIAMONDS | CardSuit::HEARTS => Color::Red,
+            CardSuit::CLUBS | CardSuit::SPADES => Color::Black,
+        }
+    }
+}
+
+impl TryFrom<u8> for CardSuit {
+    type Error = InvalidCardValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(CardSuit::CLUBS),
+            1 => Ok(CardSuit::DIAMONDS),
+            2 => Ok(CardSuit::HEARTS),
+            3 => Ok(CardSuit::SPADES),
+            _ => Err(InvalidCardValue(value)),
+        }
+    }
+}
+
+impl CardRank {
+    /// Numeric value of the rank, from 1 (ACE) to 13 (KING)
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl TryFrom<u8> for CardRank {
+    type Error = InvalidCardValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(CardRank::ACE),
+            2 => Ok(CardRank::TWO),
+            3 => Ok(CardRank::THREE),
+            4 => Ok(CardRank::FOUR),
+            5 => Ok(CardRank::FIVE),
+            6 => Ok(CardRank::SIX),
+            7 => Ok(CardRank::SEVEN),
+            8 => Ok(CardRank::EIGHT),
+            9 => Ok(CardRank::NINE),
+            10 => Ok(CardRank::TEN),
+            11 => Ok(CardRank::JACK),
+            12 => Ok(CardRank::QUEEN),
+            13 => Ok(CardRank::KING),
+            _ => Err(InvalidCardValue(value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Card {
     pub suit: CardSuit,
     pub rank: CardRank,
@@ -36,8 +107,70 @@ pub struct Card {
 
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let str = format!(
-            "{:>2}{}",
+        let str = self.to_plain_string();
+
+        let colored = match self.suit.color() {
+            Color::Red => Red.paint(str),
+            Color::Black => Blue.paint(str),
+        };
+
+        write!(f, "{}", colored)
+    }
+}
+
+/// Each card in a single byte, from 0 (ACE of CLUBS) to 51 (KING of
+/// SPADES): (rank - 1) * 4 + suit
+impl From<Card> for u8 {
+    fn from(card: Card) -> u8 {
+        (card.rank.to_u8() - 1) * 4 + card.suit.to_u8()
+    }
+}
+
+impl TryFrom<u8> for Card {
+    type Error = InvalidCardValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value >= 52 {
+            return Err(InvalidCardValue(value));
+        }
+        Ok(Card {
+            rank: CardRank::try_from(value / 4 + 1)?,
+            suit: CardSuit::try_from(value % 4)?,
+        })
+    }
+}
+
+/// Characters taken by any card on text boards
+pub const CARD_WIDTH: usize = 3;
+
+impl Card {
+
+    /// The 52 cards of a deck, ordered by suit and then by rank
+    pub fn full_deck() -> Vec<Card> {
+        let mut cards = Vec::new();
+        for suit in CardSuit::iter() {
+            for rank in CardRank::iter() {
+                cards.push(Card { suit, rank });
+            }
+        }
+        cards
+    }
+
+    /// Rank and suit of the card, without colors, right aligned on
+    /// CARD_WIDTH characters so that every card takes the same room
+    pub fn to_plain_string(&self) -> String {
+        format!("{}{}", " ".repeat(CARD_WIDTH - self.display_len()), self.label())
+    }
+
+    /// Number of characters of the rank and suit of the card, without the
+    /// padding of to_plain_string: 3 for the tens, 2 for the rest
+    pub fn display_len(&self) -> usize {
+        self.label().chars().count()
+    }
+
+    fn label(&self) -> String {
+        format!(
+            "{}{}",
             match self.rank {
                 CardRank::ACE => "A",
                 CardRank::TWO => "2",
@@ -59,30 +192,30 @@ impl fmt::Display for Card {
                 CardSuit::HEARTS => "♥",
                 CardSuit::SPADES => "♤",
             }
-        );
+        )
+    }
 
-        let colored = match self.suit {
-            CardSuit::DIAMONDS | CardSuit::HEARTS => Red.paint(str),
-            CardSuit::SPADES | CardSuit::CLUBS => Blue.paint(str),
+    /// Single character playing card symbol from the Unicode block
+    /// U+1F0A0 - U+1F0DF, skipping the knight
+    pub fn to_unicode_glyph(&self) -> char {
+        let suit_base: u32 = match self.suit {
+            CardSuit::SPADES => 0x1F0A0,
+            CardSuit::HEARTS => 0x1F0B0,
+            CardSuit::DIAMONDS => 0x1F0C0,
+            CardSuit::CLUBS => 0x1F0D0,
         };
 
-        write!(f, "{}", colored)
-    }
-}
+        let rank_offset = match self.rank {
+            CardRank::QUEEN | CardRank::KING => self.rank as u32 + 1,
+            _ => self.rank as u32,
+        };
 
-impl Card {
+        std::char::from_u32(suit_base + rank_offset).unwrap()
+    }
 
     pub fn check_alternate_colors_and_descending_rank(first: Card, second: Card) -> bool {
         ((second.rank as i32) + 1) == (first.rank as i32)
-            && match second.suit {
-                CardSuit::DIAMONDS | CardSuit::HEARTS => {
-                    first.suit == CardSuit::CLUBS || first.suit == CardSuit::SPADES
-                }
-                CardSuit::CLUBS | CardSuit::SPADES => {
-                    first.suit == CardSuit::DIAMONDS || first.suit == CardSuit::HEARTS
-                }
-            }
-
+            && first.suit.color() != second.suit.color()
     }
 }
 
@@ -101,6 +234,134 @@ mod test {
         card_alternating_check_case(CardSuit::DIAMONDS, CardRank::FIVE, CardSuit::DIAMONDS, CardRank::THREE, false);
     }
 
+    #[test]
+    fn card_display_len() {
+        let ten = Card { suit: CardSuit::HEARTS, rank: CardRank::TEN };
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        assert_eq!(ten.display_len(), 3);
+        assert_eq!(ace.display_len(), 2);
+
+        for card in Card::full_deck() {
+            let expected = if card.rank == CardRank::TEN { 3 } else { 2 };
+            assert_eq!(card.display_len(), expected);
+        }
+    }
+
+    #[test]
+    fn suit_color() {
+        assert_eq!(CardSuit::CLUBS.color(), Color::Black);
+        assert_eq!(CardSuit::DIAMONDS.color(), Color::Red);
+        assert_eq!(CardSuit::HEARTS.color(), Color::Red);
+        assert_eq!(CardSuit::SPADES.color(), Color::Black);
+    }
+
+    #[test]
+    fn alternating_colors_all_suits() {
+        let is_red = |suit| suit == CardSuit::DIAMONDS || suit == CardSuit::HEARTS;
+
+        for first in CardSuit::iter() {
+            for second in CardSuit::iter() {
+                let first_card = Card { suit: first, rank: CardRank::SEVEN };
+                let second_card = Card { suit: second, rank: CardRank::SIX };
+                assert_eq!(
+                    Card::check_alternate_colors_and_descending_rank(first_card, second_card),
+                    is_red(first) != is_red(second),
+                    "{:?} on {:?}", second, first
+                );
+                assert!(!Card::check_alternate_colors_and_descending_rank(second_card, first_card));
+            }
+        }
+    }
+
+    #[test]
+    fn rank_try_from_u8() {
+        assert_eq!(CardRank::try_from(0), Err(InvalidCardValue(0)));
+        assert_eq!(CardRank::try_from(1), Ok(CardRank::ACE));
+        assert_eq!(CardRank::try_from(10), Ok(CardRank::TEN));
+        assert_eq!(CardRank::try_from(13), Ok(CardRank::KING));
+        assert_eq!(CardRank::try_from(14), Err(InvalidCardValue(14)));
+
+        for rank in CardRank::iter() {
+            assert_eq!(CardRank::try_from(rank.to_u8()), Ok(rank));
+        }
+    }
+
+    #[test]
+    fn suit_try_from_u8() {
+        assert_eq!(CardSuit::try_from(0), Ok(CardSuit::CLUBS));
+        assert_eq!(CardSuit::try_from(3), Ok(CardSuit::SPADES));
+        assert_eq!(CardSuit::try_from(4), Err(InvalidCardValue(4)));
+
+        for suit in CardSuit::iter() {
+            assert_eq!(CardSuit::try_from(suit.to_u8()), Ok(suit));
+        }
+    }
+
+    #[test]
+    fn card_u8_round_trip() {
+        let bytes: Vec<u8> = Card::full_deck().into_iter().map(u8::from).collect();
+        for card in Card::full_deck() {
+            assert_eq!(Card::try_from(u8::from(card)), Ok(card));
+        }
+        for byte in 0..52 {
+            assert!(bytes.contains(&byte));
+        }
+        assert_eq!(u8::from(Card { suit: CardSuit::CLUBS, rank: CardRank::ACE }), 0);
+        assert_eq!(u8::from(Card { suit: CardSuit::SPADES, rank: CardRank::KING }), 51);
+
+        for byte in 52..=255 {
+            assert_eq!(Card::try_from(byte), Err(InvalidCardValue(byte)));
+        }
+    }
+
+    #[test]
+    fn card_plain_string() {
+        assert_eq!(Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }.to_plain_string(), " A♥");
+        assert_eq!(Card { suit: CardSuit::SPADES, rank: CardRank::TEN }.to_plain_string(), "10♤");
+    }
+
+    #[test]
+    fn card_display_width() {
+        let ten = Card { suit: CardSuit::SPADES, rank: CardRank::TEN };
+        let ace = Card { suit: CardSuit::HEARTS, rank: CardRank::ACE };
+
+        // The colors only wrap the plain string, which is aligned
+        for card in [ten, ace].iter() {
+            assert!(format!("{}", card).contains(&card.to_plain_string()));
+            assert_eq!(card.to_plain_string().chars().count(), 3);
+        }
+        assert_eq!(
+            format!("{}", ten).chars().count(),
+            format!("{}", ace).chars().count()
+        );
+    }
+
+    #[test]
+    fn full_deck() {
+        let deck = Card::full_deck();
+        assert_eq!(deck.len(), 52);
+
+        for (i, card) in deck.iter().enumerate() {
+            assert!(!deck[i + 1..].contains(card));
+        }
+
+        for suit in CardSuit::iter() {
+            for rank in CardRank::iter() {
+                assert!(deck.contains(&Card { suit, rank }));
+            }
+        }
+    }
+
+    #[test]
+    fn card_unicode_glyph() {
+        assert_eq!(Card { suit: CardSuit::SPADES, rank: CardRank::ACE }.to_unicode_glyph(), '\u{1F0A1}');
+        assert_eq!(Card { suit: CardSuit::HEARTS, rank: CardRank::TEN }.to_unicode_glyph(), '\u{1F0BA}');
+        assert_eq!(Card { suit: CardSuit::DIAMONDS, rank: CardRank::JACK }.to_unicode_glyph(), '\u{1F0CB}');
+        assert_eq!(Card { suit: CardSuit::CLUBS, rank: CardRank::QUEEN }.to_unicode_glyph(), '\u{1F0DD}');
+        assert_eq!(Card { suit: CardSuit::SPADES, rank: CardRank::KING }.to_unicode_glyph(), '\u{1F0AE}');
+        assert_eq!(Card { suit: CardSuit::HEARTS, rank: CardRank::KING }.to_unicode_glyph(), '🂾');
+    }
+
     fn card_alternating_check_case (
         first_suit: CardSuit,
         first_rank: CardRank,
@@ -62,12 +62,148 @@ pub trait CardMover {
     ) {
         origin.undo_peek(&destination.undo_poke(number));
     }
+
+    /// Whether the cards of the last move can be moved straight back to
+    /// where they came from. The game, knowing its last move, checks it.
+    fn allows_reversing(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct SimpleCardMover;
 impl CardMover for SimpleCardMover {}
 
+/// Moves the cards like SimpleCardMover, calling the callback with the
+/// cards of each successful move.
+pub struct LoggingCardMover<F: FnMut(&[Card])> {
+    mover: SimpleCardMover,
+    callback: F,
+}
+
+impl<F: FnMut(&[Card])> LoggingCardMover<F> {
+    pub fn new(callback: F) -> LoggingCardMover<F> {
+        LoggingCardMover { mover: SimpleCardMover {}, callback }
+    }
+}
+
+impl<F: FnMut(&[Card])> CardMover for LoggingCardMover<F> {
+    fn move_cards(
+        &mut self,
+        origin: &mut dyn CardOrigin,
+        destination: &mut dyn CardDestination,
+        number: usize,
+    ) -> bool {
+        let cards = origin.try_peek(number);
+        let moved = self.mover.move_cards(origin, destination, number);
+
+        if let (true, Some(cards)) = (moved, cards) {
+            (self.callback)(&cards);
+        }
+        moved
+    }
+
+    fn allows_reversing(&self) -> bool {
+        self.mover.allows_reversing()
+    }
+}
+
+/// Moves the cards with the given mover, counting how many moves were
+/// attempted, how many succeeded and how many were undone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountingCardMover<T: CardMover> {
+    mover: T,
+    attempts: u32,
+    successes: u32,
+    undos: u32,
+}
+
+impl<T: CardMover> CountingCardMover<T> {
+    pub fn new(mover: T) -> CountingCardMover<T> {
+        CountingCardMover { mover, attempts: 0, successes: 0, undos: 0 }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn successes(&self) -> u32 {
+        self.successes
+    }
+
+    pub fn undos(&self) -> u32 {
+        self.undos
+    }
+}
+
+impl<T: CardMover> CardMover for CountingCardMover<T> {
+    fn move_cards(
+        &mut self,
+        origin: &mut dyn CardOrigin,
+        destination: &mut dyn CardDestination,
+        number: usize,
+    ) -> bool {
+        self.attempts += 1;
+        let moved = self.mover.move_cards(origin, destination, number);
+        if moved {
+            self.successes += 1;
+        }
+        moved
+    }
+
+    fn undo_move_cards(
+        &mut self,
+        origin: &mut dyn CardOrigin,
+        destination: &mut dyn CardDestination,
+        number: usize,
+    ) {
+        self.undos += 1;
+        self.mover.undo_move_cards(origin, destination, number);
+    }
+
+    fn allows_reversing(&self) -> bool {
+        self.mover.allows_reversing()
+    }
+}
+
+/// Moves the cards with the given mover, refusing to move the cards of
+/// the previous move straight back to where they came from, as that only
+/// undoes it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoRepeatMover<T: CardMover> {
+    mover: T,
+}
+
+impl<T: CardMover> NoRepeatMover<T> {
+    pub fn new(mover: T) -> NoRepeatMover<T> {
+        NoRepeatMover { mover }
+    }
+}
+
+impl<T: CardMover> CardMover for NoRepeatMover<T> {
+    fn move_cards(
+        &mut self,
+        origin: &mut dyn CardOrigin,
+        destination: &mut dyn CardDestination,
+        number: usize,
+    ) -> bool {
+        self.mover.move_cards(origin, destination, number)
+    }
+
+    fn undo_move_cards(
+        &mut self,
+        origin: &mut dyn CardOrigin,
+        destination: &mut dyn CardDestination,
+        number: usize,
+    ) {
+        self.mover.undo_move_cards(origin, destination, number);
+    }
+
+    fn allows_reversing(&self) -> bool {
+        false
+    }
+}
+
 pub mod test_common {
     use super::*;
     use rand::seq::SliceRandom;
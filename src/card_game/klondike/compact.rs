@@ -0,0 +1,128 @@
+use crate::card_game::american_cards::*;
+use std::convert::TryFrom;
+
+/// Error returned when the bytes given don't contain a valid game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InvalidBytes;
+
+pub(crate) fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Writes the number of cards (one byte) followed by the cards, each one
+/// in a byte
+pub(crate) fn write_cards(out: &mut Vec<u8>, cards: &[Card]) {
+    out.push(cards.len() as u8);
+    out.extend(cards.iter().map(|card| u8::from(*card)));
+}
+
+/// Writes the number of flags followed by the flags, packed eight per byte
+pub(crate) fn write_flags(out: &mut Vec<u8>, flags: &[bool]) {
+    write_u32(out, flags.len() as u32);
+    for chunk in flags.chunks(8) {
+        let mut byte = 0u8;
+        for (i, flag) in chunk.iter().enumerate() {
+            if *flag {
+                byte |= 1 << i;
+            }
+        }
+        out.push(byte);
+    }
+}
+
+/// Sequential reader over the bytes written by the functions above
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { bytes, position: 0 }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, InvalidBytes> {
+        let byte = *self.bytes.get(self.position).ok_or(InvalidBytes)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, InvalidBytes> {
+        let mut value = [0u8; 4];
+        for byte in value.iter_mut() {
+            *byte = self.read_u8()?;
+        }
+        Ok(u32::from_le_bytes(value))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, InvalidBytes> {
+        let mut value = [0u8; 8];
+        for byte in value.iter_mut() {
+            *byte = self.read_u8()?;
+        }
+        Ok(u64::from_le_bytes(value))
+    }
+
+    pub(crate) fn read_cards(&mut self) -> Result<Vec<Card>, InvalidBytes> {
+        let len = self.read_u8()?;
+        (0..len).map(|_i| Card::try_from(self.read_u8()?).map_err(|_x| InvalidBytes)).collect()
+    }
+
+    pub(crate) fn read_flags(&mut self) -> Result<Vec<bool>, InvalidBytes> {
+        let len = self.read_u32()? as usize;
+        let mut flags = Vec::new();
+        while flags.len() < len {
+            let byte = self.read_u8()?;
+            for i in 0..8 {
+                if flags.len() < len {
+                    flags.push(byte & (1 << i) != 0);
+                }
+            }
+        }
+        Ok(flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_round_trip() {
+        let mut out = Vec::new();
+        write_cards(&mut out, &Card::full_deck());
+        assert_eq!(out.len(), 1 + 52);
+
+        let mut reader = ByteReader::new(&out);
+        assert_eq!(reader.read_cards(), Ok(Card::full_deck()));
+        assert!(reader.is_finished());
+
+        assert_eq!(ByteReader::new(&[1, 52]).read_cards(), Err(InvalidBytes));
+    }
+
+    #[test]
+    fn flags_round_trip() {
+        let flags = vec![true, false, false, true, true, false, true, false, true, true];
+        let mut out = Vec::new();
+        write_flags(&mut out, &flags);
+        assert_eq!(out.len(), 4 + 2);
+
+        let mut reader = ByteReader::new(&out);
+        assert_eq!(reader.read_flags(), Ok(flags));
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn read_past_the_end() {
+        let mut reader = ByteReader::new(&[3, 4, 5]);
+        assert_eq!(reader.read_cards(), Err(InvalidBytes));
+    }
+}
@@ -1,14 +1,31 @@
 use crate::card_game::american_cards::*;
 use crate::card_game::card_containers::*;
+use super::compact::*;
 use serde::Serialize;
+use rand::RngCore;
+use rand::seq::SliceRandom;
+use std::collections::VecDeque;
 
 /// The deck of the game, consisting in two piles: the stock and the waste.
 /// The waste also acts as a CardOrigin.
+/// Each take moves draw_count cards from the stock to the waste.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Deck {
     stock: Vec<Card>,
     waste: Vec<Card>,
-    take_caused_flip: Vec<bool>
+    /// Effect of every take not undone yet, the last one at the back
+    takes: VecDeque<Take>,
+    draw_count: u32,
+}
+
+/// What a take did, so that undoing it restores the deck exactly
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Take {
+    /// Moved the given number of cards from the stock to the waste
+    Draw(u32),
+    /// Turned the waste over into the stock, and then moved the given
+    /// number of cards back to the waste
+    RecycleAndDraw(u32),
 }
 
 /// Value object used by UI for representing the status of a Deck
@@ -17,6 +34,11 @@ pub struct DeckStatus {
     pub cards_on_waste: u32,
     pub cards_on_stock: u32,
     pub top_card_on_waste: Option<Card>,
+    /// Cards left to take before the waste is turned over
+    pub cards_until_recycle: u32,
+    /// Whether a take would change the deck: false once the stock and the
+    /// waste are both empty
+    pub can_take: bool,
 }
 
 impl CardOrigin for Deck {
@@ -51,34 +73,70 @@ impl Deck {
     ///Creates a deck containing the given cards. One of the cards goes to
     ///the waste, the others to the pile.
     pub fn new(cards: &Vec<Card>) -> Deck {
+        Deck::new_with_draw_count(cards, 1)
+    }
+
+    ///Creates a deck containing the given cards, where each take moves
+    ///draw_count cards to the waste. The first take is already done.
+    pub fn new_with_draw_count(cards: &Vec<Card>, draw_count: u32) -> Deck {
         let mut deck = Deck {
             stock: cards.to_vec(),
             waste: Vec::new(),
-            take_caused_flip: Vec::new(),
+            takes: VecDeque::new(),
+            draw_count,
         };
 
         deck.take();
         return deck;
     }
 
-    ///Moves one card from the pile to the waste.
+    ///Creates a deck with the given stock and waste, from the bottom to 
+    ///the top card. No take is done.
+    pub fn from_cards(stock: Vec<Card>, waste: Vec<Card>, draw_count: u32) -> Deck {
+        Deck {
+            stock,
+            waste,
+            takes: VecDeque::new(),
+            draw_count,
+        }
+    }
+
+    pub fn get_draw_count(&self) -> u32 {
+        self.draw_count
+    }
+
+    ///Moves draw_count cards (or the remaining ones, if less) from the 
+    ///pile to the waste.
     ///If the pile is empty, all the waste cards are moved to the pile.
     ///If both the pile and the waste are empty, nothing is done.
     pub fn take(&mut self) {
-        if self.stock.is_empty() && !self.waste.is_empty() {
+        let recycled = self.will_recycle();
+        if recycled {
             self.waste.reverse();
             self.stock.append(&mut self.waste);
-            self.take_caused_flip.push(true);
-        } else {
-            self.take_caused_flip.push(false);
         }
 
-        match self.stock.pop() {
-            Some(card) => {
-                self.waste.push(card);
+        let mut taken = 0;
+        while taken < self.draw_count {
+            match self.stock.pop() {
+                Some(card) => {
+                    self.waste.push(card);
+                    taken += 1;
+                }
+                None => break
             }
-            None => {}
         }
+        self.takes.push_back(if recycled { Take::RecycleAndDraw(taken) } else { Take::Draw(taken) });
+    }
+
+    /// Takes like take, unless there are no cards on the stock nor the
+    /// waste. Returns whether the take was done.
+    pub fn try_take(&mut self) -> bool {
+        if self.stock.is_empty() && self.waste.is_empty() {
+            return false;
+        }
+        self.take();
+        true
     }
 
     pub fn get_status(&self) -> DeckStatus {
@@ -91,27 +149,141 @@ impl Deck {
             cards_on_waste: self.waste.len() as u32,
             cards_on_stock: self.stock.len() as u32,
             top_card_on_waste,
+            cards_until_recycle: self.stock.len() as u32,
+            can_take: !self.stock.is_empty() || self.will_recycle(),
         }
     }
 
-    pub fn undo_take(&mut self) {
+    /// Returns the card the next take will move to the waste, without
+    /// modifying the deck. None if the stock is empty.
+    pub fn peek_stock_top(&self) -> Option<Card> {
+        self.stock.last().copied()
+    }
+
+    /// Returns the cards on the waste, from the bottom to the top one,
+    /// without modifying the deck.
+    pub fn waste_snapshot(&self) -> &[Card] {
+        &self.waste[..]
+    }
+
+    /// Returns the cards on the stock, from the bottom to the top one,
+    /// without modifying the deck.
+    pub fn stock_snapshot(&self) -> &[Card] {
+        &self.stock[..]
+    }
+
+    /// Shuffles the cards on the stock, leaving the waste as it is
+    pub fn shuffle_remaining(&mut self, rng: &mut dyn RngCore) {
+        self.stock.shuffle(rng);
+    }
+
+    /// Puts the stock back in the given order, as long as it has the
+    /// same cards. Returns whether it was done.
+    pub(crate) fn reorder_stock(&mut self, stock: &[Card]) -> bool {
+        let mut current = self.stock.clone();
+        let mut given = stock.to_vec();
+        current.sort_by_key(|x| u8::from(*x));
+        given.sort_by_key(|x| u8::from(*x));
+        if current != given {
+            return false;
+        }
+        self.stock = stock.to_vec();
+        true
+    }
+
+    /// Number of cards on the stock and the waste
+    pub fn total_cards(&self) -> usize {
+        self.stock.len() + self.waste.len()
+    }
+
+    /// Cards in the order successive takes will draw them, without
+    /// changing the deck: the stock from its top card, and then the waste
+    /// as it will be after turning it over.
+    pub fn upcoming(&self) -> impl Iterator<Item = &Card> {
+        self.stock.iter().rev().chain(self.waste.iter())
+    }
 
-        if let Some(card) = self.waste.pop() {
-            self.stock.push(card);
+    /// Returns true if the next take will turn the waste over into
+    /// the stock before drawing
+    pub fn will_recycle(&self) -> bool {
+        self.stock.is_empty() && !self.waste.is_empty()
+    }
+
+    /// Appends the cards on the stock and the waste, leaving out the
+    /// history of takes
+    pub(crate) fn write_position(&self, out: &mut Vec<u8>) {
+        write_cards(out, &self.stock);
+        write_cards(out, &self.waste);
+    }
+
+    /// Appends the compact representation of the deck
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_cards(out, &self.stock);
+        write_cards(out, &self.waste);
+        let recycled = self.takes.iter()
+            .map(|x| matches!(x, Take::RecycleAndDraw(_)))
+            .collect::<Vec<bool>>();
+        write_flags(out, &recycled);
+        write_u32(out, self.draw_count);
+        write_u32(out, self.takes.len() as u32);
+        // A take never moves more cards than a whole deck
+        out.extend(self.takes.iter().map(|x| match x {
+            Take::Draw(taken) | Take::RecycleAndDraw(taken) => *taken as u8,
+        }));
+    }
+
+    /// Reads a deck written by write_bytes
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Result<Deck, InvalidBytes> {
+        let stock = reader.read_cards()?;
+        let waste = reader.read_cards()?;
+        let recycled = reader.read_flags()?;
+        let draw_count = reader.read_u32()?;
+        let num_takes = reader.read_u32()?;
+        if num_takes as usize != recycled.len() {
+            return Err(InvalidBytes);
+        }
+        let takes = recycled.into_iter()
+            .map(|recycled| reader.read_u8().map(|x| match recycled {
+                true => Take::RecycleAndDraw(x as u32),
+                false => Take::Draw(x as u32),
+            }))
+            .collect::<Result<VecDeque<Take>, InvalidBytes>>()?;
+
+        Ok(Deck { stock, waste, takes, draw_count })
+    }
+
+    /// Undoes the last take not undone yet. A deck without takes recorded,
+    /// like one created by from_cards, moves the top card of the waste
+    /// back to the stock.
+    pub fn undo_take(&mut self) {
+        let take = self.takes.pop_back().unwrap_or(Take::Draw(1));
+        let taken = match take {
+            Take::Draw(taken) | Take::RecycleAndDraw(taken) => taken,
+        };
+        for _i in 0..taken {
+            if let Some(card) = self.waste.pop() {
+                self.stock.push(card);
+            }
         }
 
-        let flipped = self.take_caused_flip.pop().unwrap_or(false);
-        if  flipped && self.waste.is_empty() && !self.stock.is_empty() {
+        if let Take::RecycleAndDraw(_) = take {
             self.stock.reverse();
             self.waste.append(&mut self.stock);
         }
+    }
 
+    /// Forgets the oldest take, which won't be undone any more, so that
+    /// the takes kept don't grow beyond the history of the game
+    pub(crate) fn forget_oldest_take(&mut self) {
+        self.takes.pop_front();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
     use crate::card_game::card_containers::test_common::*;
 
     #[test]
@@ -145,6 +317,76 @@ mod tests {
         assert_eq!(deck.peek(1), Vec::new());
     }
 
+    #[test]
+    fn deck_cards_until_recycle() {
+        let mut deck = create_test_deck();
+        for remaining in (0..3).rev() {
+            deck.take();
+            assert_eq!(deck.get_status().cards_until_recycle, remaining);
+        }
+
+        assert!(deck.will_recycle());
+        deck.take();
+        assert_eq!(deck.get_status().cards_until_recycle, 5);
+    }
+
+    #[test]
+    fn deck_try_take() {
+        let mut deck = create_test_deck();
+        assert!(deck.try_take());
+        assert_eq!(deck.takes, vec![Take::Draw(1)]);
+
+        let mut deck = Deck::from_cards(Vec::new(), Vec::new(), 1);
+        assert!(!deck.try_take());
+        assert!(deck.takes.is_empty());
+    }
+
+    #[test]
+    fn deck_can_take() {
+        let mut deck = create_test_deck();
+        assert!(deck.get_status().can_take);
+
+        // Taking turns the waste over
+        deck.stock.clear();
+        assert!(deck.get_status().can_take);
+
+        // Every card was played from the waste
+        deck.waste.clear();
+        assert!(!deck.get_status().can_take);
+        assert!(!deck.try_take());
+    }
+
+    #[test]
+    fn deck_shuffle_remaining() {
+        let cards: Vec<Card> = Card::full_deck();
+        let mut deck = Deck::new(&cards);
+        let waste = deck.waste_snapshot().to_vec();
+        let stock = deck.stock_snapshot().to_vec();
+
+        deck.shuffle_remaining(&mut StdRng::seed_from_u64(7));
+        assert_eq!(deck.waste_snapshot(), &waste[..]);
+        assert_ne!(deck.stock_snapshot(), &stock[..]);
+
+        assert!(deck.reorder_stock(&stock));
+        assert_eq!(deck.stock_snapshot(), &stock[..]);
+        assert!(!deck.reorder_stock(&stock[1..]));
+        assert!(!deck.reorder_stock(&waste));
+    }
+
+    #[test]
+    fn deck_upcoming() {
+        let mut deck = create_test_deck();
+        let upcoming: Vec<Card> = deck.upcoming().copied().collect();
+        assert_eq!(upcoming.len(), 6);
+        assert_eq!(upcoming[0], Card { suit: CardSuit::DIAMONDS, rank: CardRank::THREE });
+
+        // The waste cards come after recycling, from its bottom card
+        for card in upcoming.iter().chain(upcoming.iter()) {
+            deck.take();
+            assert_eq!(deck.try_peek(1), Some(vec![*card]));
+        }
+    }
+
     #[test]
     fn deck_peek_one_empty() {
         let mut deck = create_test_deck();
@@ -196,7 +438,8 @@ mod tests {
                     rank: CardRank::THREE,
                 },
             ],
-            take_caused_flip: Vec::new(),
+            takes: VecDeque::new(),
+            draw_count: 1,
         }
     }
 
@@ -285,7 +528,8 @@ mod tests {
         let mut deck = Deck {
             stock: Vec::new(),
             waste: Vec::new(),
-            take_caused_flip: Vec::new(),
+            takes: VecDeque::new(),
+            draw_count: 1,
         };
         deck.take();
 
@@ -348,6 +592,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deck_peek_stock_top_and_waste_snapshot() {
+        let mut deck = create_test_deck();
+        let status = deck.get_status();
+
+        assert_eq!(
+            deck.peek_stock_top(),
+            Some(Card {
+                suit: CardSuit::DIAMONDS,
+                rank: CardRank::THREE,
+            })
+        );
+        assert_eq!(
+            deck.waste_snapshot(),
+            &[
+                Card {
+                    suit: CardSuit::CLUBS,
+                    rank: CardRank::ACE,
+                },
+                Card {
+                    suit: CardSuit::CLUBS,
+                    rank: CardRank::TWO,
+                },
+                Card {
+                    suit: CardSuit::CLUBS,
+                    rank: CardRank::THREE,
+                },
+            ]
+        );
+        assert_eq!(deck.get_status(), status);
+
+        deck.stock.clear();
+        assert_eq!(deck.peek_stock_top(), None);
+    }
+
+    #[test]
+    fn deck_total_cards_and_will_recycle() {
+        let mut deck = create_test_deck();
+        assert_eq!(deck.total_cards(), 6);
+        assert!(!deck.will_recycle());
+
+        let waste = deck.waste.clone();
+        deck.waste.clear();
+        assert_eq!(deck.total_cards(), 3);
+        assert!(!deck.will_recycle());
+
+        deck.stock.clear();
+        assert_eq!(deck.total_cards(), 0);
+        assert!(!deck.will_recycle());
+
+        deck.waste = waste;
+        assert_eq!(deck.total_cards(), 3);
+        assert!(deck.will_recycle());
+
+        deck.take();
+        assert_eq!(deck.total_cards(), 3);
+        assert!(!deck.will_recycle());
+    }
+
     #[test]
     fn deck_undo_take() {
         const NUMBER_OF_UNDOS:u32 = 10;
@@ -365,6 +668,95 @@ mod tests {
         }
     }
 
+    #[test]
+    fn deck_take_draw_three() {
+        let mut deck = create_test_deck();
+        deck.draw_count = 3;
+
+        deck.take();
+        assert_deck(&deck, 0, 6, CardSuit::DIAMONDS, CardRank::ACE);
+
+        deck.take();
+        assert_deck(&deck, 3, 3, CardSuit::CLUBS, CardRank::THREE);
+
+        deck.take();
+        assert_deck(&deck, 0, 6, CardSuit::DIAMONDS, CardRank::ACE);
+
+        let cards = create_test_deck().stock;
+        let deck = Deck::new_with_draw_count(&cards, 3);
+        assert_deck(&deck, 0, 3, CardSuit::DIAMONDS, CardRank::ACE);
+
+        let deck = Deck::new_with_draw_count(&cards[..2].to_vec(), 3);
+        assert_deck(&deck, 0, 2, CardSuit::DIAMONDS, CardRank::ACE);
+    }
+
+    #[test]
+    fn deck_undo_take_draw_three() {
+        const NUMBER_OF_UNDOS:u32 = 10;
+        let mut deck = create_test_deck();
+        deck.draw_count = 3;
+        deck.stock.push(Card { suit: CardSuit::HEARTS, rank: CardRank::KING });
+        let mut history:Vec<DeckStatus> = Vec::new();
+
+        for _i in 0..NUMBER_OF_UNDOS {
+            history.push(deck.get_status());
+            deck.take();
+        }
+
+        for _i in 0..NUMBER_OF_UNDOS {
+            deck.undo_take();
+            assert_eq!(history.pop().unwrap(), deck.get_status())
+        }
+    }
+
+    #[test]
+    fn deck_undo_take_random() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for draw_count in 1..=3 {
+            let mut deck = Deck::new_with_draw_count(&generate_random_card_set(12), draw_count);
+            let mut history: Vec<Deck> = Vec::new();
+
+            for _i in 0..500 {
+                // Takes twice as often as it undoes, playing a waste
+                // card now and then to leave it uneven
+                match rng.next_u32() % 6 {
+                    0 | 1 if !history.is_empty() => {
+                        deck.undo_take();
+                        let prev = history.pop().unwrap();
+                        assert_eq!(deck.stock, prev.stock);
+                        assert_eq!(deck.waste, prev.waste);
+                    }
+                    2 if deck.waste.len() > 1 => {
+                        deck.peek(1);
+                        history.clear();
+                    }
+                    _ => {
+                        history.push(deck.clone());
+                        deck.take();
+                    }
+                }
+            }
+
+            while let Some(prev) = history.pop() {
+                deck.undo_take();
+                assert_eq!(deck, prev);
+            }
+        }
+    }
+
+    #[test]
+    fn deck_forget_oldest_take() {
+        let mut deck = create_test_deck();
+        deck.take();
+        deck.take();
+        deck.forget_oldest_take();
+        assert_eq!(deck.takes, vec![Take::Draw(1)]);
+
+        let mut deck = Deck::from_cards(Vec::new(), Vec::new(), 1);
+        deck.forget_oldest_take();
+        assert!(deck.takes.is_empty());
+    }
+
     #[test]
     fn deck_undo_peek() {
         const NUMBER_OF_UNDOS:u32 = 10;
@@ -406,7 +798,8 @@ mod tests {
                     rank: CardRank::ACE,
                 },
             ],
-            take_caused_flip: Vec::new(),
+            takes: VecDeque::new(),
+            draw_count: 1,
         };
 
         let status0 = deck.get_status();
@@ -1,12 +1,83 @@
 use crate::card_game::american_cards::*;
 use crate::card_game::card_containers::*;
+use super::compact::*;
 use serde::Serialize;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Foundation {
     hidden: Vec<Card>,
     visible: Vec<Card>,
-    peek_caused_flip: Vec<bool>
+    peek_caused_flip: Vec<bool>,
+    build_rule: BuildRule,
+    empty_column_rule: EmptyColumnRule,
+    /// Whether the top hidden card is turned visible as soon as there
+    /// are no visible cards, or only when flip is called
+    auto_flip: bool,
+}
+
+/// Which cards can be placed on top of a visible card of a foundation.
+/// Its rank has to be the immediate previous one in all of them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BuildRule {
+    /// Red cards on black ones and black cards on red ones
+    AlternatingColors,
+    SameSuit,
+    AnySuit,
+}
+
+impl BuildRule {
+    /// Checks whether second can be placed on top of first
+    pub fn can_build(self, first: Card, second: Card) -> bool {
+        match self {
+            BuildRule::AlternatingColors =>
+                Card::check_alternate_colors_and_descending_rank(first, second),
+            BuildRule::SameSuit =>
+                first.suit == second.suit && second.rank as u8 + 1 == first.rank as u8,
+            BuildRule::AnySuit =>
+                second.rank as u8 + 1 == first.rank as u8,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            BuildRule::AlternatingColors => 0,
+            BuildRule::SameSuit => 1,
+            BuildRule::AnySuit => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<BuildRule, InvalidBytes> {
+        match value {
+            0 => Ok(BuildRule::AlternatingColors),
+            1 => Ok(BuildRule::SameSuit),
+            2 => Ok(BuildRule::AnySuit),
+            _ => Err(InvalidBytes),
+        }
+    }
+}
+
+/// Which cards can be placed on a foundation without cards
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EmptyColumnRule {
+    KingsOnly,
+    AnyCard,
+}
+
+impl EmptyColumnRule {
+    fn to_u8(self) -> u8 {
+        match self {
+            EmptyColumnRule::KingsOnly => 0,
+            EmptyColumnRule::AnyCard => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<EmptyColumnRule, InvalidBytes> {
+        match value {
+            0 => Ok(EmptyColumnRule::KingsOnly),
+            1 => Ok(EmptyColumnRule::AnyCard),
+            _ => Err(InvalidBytes),
+        }
+    }
 }
 
 /// Value object used by UI for representing the status of a Foundation
@@ -18,28 +89,178 @@ pub struct FoundationStatus {
 
 /// Each of the foundations of the game. Acts as a Card Origin an Card Destination.
 /// Multiple cards can be peek or poke at a time.
-/// When poking one or various cards, it has to alternate suit colors and 
-/// the next rank has to be the immediate previous value of the rank of the 
-/// preceding card. If the foundation is empty a KING of any suit is allowed.
+/// When poking one or various cards, the first one has to follow the build
+/// rule of the foundation (alternating colors by default) and its rank has
+/// to be the immediate previous value of the rank of the preceding card.
+/// If the foundation is empty a KING of any suit is allowed, or any card
+/// if its empty column rule says so.
 impl Foundation {
     pub fn new(cards: Vec<Card>) -> Foundation {
+        Foundation::new_with_build_rule(cards, BuildRule::AlternatingColors)
+    }
+
+    /// Creates a foundation like new, where cards are placed following
+    /// the given rule.
+    pub fn new_with_build_rule(cards: Vec<Card>, build_rule: BuildRule) -> Foundation {
+        Foundation::dealt(cards, 1, build_rule, EmptyColumnRule::KingsOnly, true)
+    }
+
+    /// Creates a foundation like new, with the given number of cards
+    /// visible instead of only the last one. It is clamped between one
+    /// and the number of cards.
+    pub fn new_with_faceup(cards: Vec<Card>, faceup: usize) -> Foundation {
+        Foundation::dealt(cards, faceup, BuildRule::AlternatingColors, EmptyColumnRule::KingsOnly, true)
+    }
+
+    /// Creates a foundation with the last faceup cards visible, where cards
+    /// are placed following the given rules
+    pub(crate) fn dealt(cards: Vec<Card>, faceup: usize, build_rule: BuildRule,
+            empty_column_rule: EmptyColumnRule, auto_flip: bool) -> Foundation {
+        let faceup = faceup.clamp(1, cards.len());
         Foundation {
-            hidden: cards[..cards.len() - 1].to_vec(),
-            visible: cards[cards.len() - 1..].to_vec(),
+            hidden: cards[..cards.len() - faceup].to_vec(),
+            visible: cards[cards.len() - faceup..].to_vec(),
             peek_caused_flip: Vec::new(),
+            build_rule,
+            empty_column_rule,
+            auto_flip,
         }
     }
 
+    /// Creates a foundation with the given hidden and visible cards, from
+    /// the bottom to the top one. None if the visible cards don't alternate
+    /// colors in descending rank, or there are hidden cards but no visible ones.
+    pub fn from_cards(hidden: Vec<Card>, visible: Vec<Card>) -> Option<Foundation> {
+        if visible.is_empty() && !hidden.is_empty() {
+            return None;
+        }
+
+        let build_rule = BuildRule::AlternatingColors;
+        let valid_run = visible.windows(2).all(|x| build_rule.can_build(x[0], x[1]));
+        if !valid_run {
+            return None;
+        }
+
+        Some(Foundation {
+            hidden,
+            visible,
+            peek_caused_flip: Vec::new(),
+            build_rule,
+            empty_column_rule: EmptyColumnRule::KingsOnly,
+            auto_flip: true,
+        })
+    }
+
+    /// Number of cards not turned visible yet
+    pub fn hidden_count(&self) -> usize {
+        self.hidden.len()
+    }
+
+    /// The hidden cards, from the bottom to the top one
+    pub(crate) fn hidden_cards(&self) -> &[Card] {
+        &self.hidden
+    }
+
+    pub fn get_build_rule(&self) -> BuildRule {
+        self.build_rule
+    }
+
+    pub fn get_empty_column_rule(&self) -> EmptyColumnRule {
+        self.empty_column_rule
+    }
+
+    pub fn get_auto_flip(&self) -> bool {
+        self.auto_flip
+    }
+
+    /// Turns the top hidden card visible if there are no visible cards,
+    /// which only happens without auto flip. Returns false if there was
+    /// nothing to flip.
+    pub fn flip(&mut self) -> bool {
+        if !self.visible.is_empty() {
+            return false;
+        }
+        match self.hidden.pop() {
+            Some(card) => {
+                self.visible.push(card);
+                // Counted as caused by the peek that left no visible cards,
+                // so undoing that peek hides it again like with auto flip
+                if let Some(flipped) = self.peek_caused_flip.last_mut() {
+                    *flipped = true;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Hides again the card turned visible by flip
+    pub(crate) fn undo_flip(&mut self) {
+        if self.visible.len() == 1 {
+            self.hidden.push(self.visible.pop().unwrap());
+            if let Some(flipped) = self.peek_caused_flip.last_mut() {
+                *flipped = false;
+            }
+        }
+    }
+
+    /// Number of visible cards from the one at index (0 being the bottom
+    /// visible card) to the top, if they form a run following the build
+    /// rule. None otherwise, or if there is no visible card at index.
+    pub fn movable_from(&self, index: usize) -> Option<usize> {
+        let run = self.visible.get(index..).filter(|x| !x.is_empty())?;
+        if run.windows(2).all(|x| self.build_rule.can_build(x[0], x[1])) {
+            Some(run.len())
+        } else {
+            None
+        }
+    }
+
+    /// Only the runs on top of the visible cards can be taken. Dealt with
+    /// several cards face up, the visible ones may not be a run.
     fn can_peek(&self, number: usize) -> bool {
         number > 0 && number <= self.visible.len()
+            && self.movable_from(self.visible.len() - number).is_some()
     }
 
     pub fn get_status(&self) -> FoundationStatus {
         FoundationStatus {
-            num_hidden: self.hidden.len() as u32,
+            num_hidden: self.hidden_count() as u32,
             visible: self.visible[..].to_vec(),
         }
     }
+
+    /// Number of hidden cards that have been flipped to visible by the
+    /// peeks not undone yet.
+    pub fn flips(&self) -> u32 {
+        self.peek_caused_flip.iter().filter(|flipped| **flipped).count() as u32
+    }
+
+    /// Appends the compact representation of the foundation
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_cards(out, &self.hidden);
+        write_cards(out, &self.visible);
+        write_flags(out, &self.peek_caused_flip);
+        out.push(self.build_rule.to_u8());
+        out.push(self.empty_column_rule.to_u8());
+        out.push(self.auto_flip as u8);
+    }
+
+    /// Reads a foundation written by write_bytes
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Result<Foundation, InvalidBytes> {
+        Ok(Foundation {
+            hidden: reader.read_cards()?,
+            visible: reader.read_cards()?,
+            peek_caused_flip: reader.read_flags()?,
+            build_rule: BuildRule::from_u8(reader.read_u8()?)?,
+            empty_column_rule: EmptyColumnRule::from_u8(reader.read_u8()?)?,
+            auto_flip: match reader.read_u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(InvalidBytes),
+            },
+        })
+    }
 }
 
 impl CardOrigin for Foundation {
@@ -55,7 +276,7 @@ impl CardOrigin for Foundation {
             let res: Vec<Card> = self.visible.drain(self.visible.len() - number..).collect();
 
             let mut flipped = false;
-            if self.visible.is_empty() {
+            if self.visible.is_empty() && self.auto_flip {
                 if let Some(card) = self.hidden.pop() {
                     self.visible.push(card);
                     flipped = true;
@@ -86,12 +307,20 @@ impl CardDestination for Foundation {
         }
 
         if self.visible.is_empty() {
-            return cards[0].rank == CardRank::KING;
+            // Hidden cards waiting to be flipped aren't an empty column
+            if !self.hidden.is_empty() {
+                return false;
+            }
+            return match self.empty_column_rule {
+                EmptyColumnRule::KingsOnly => cards[0].rank == CardRank::KING,
+                EmptyColumnRule::AnyCard => true,
+            };
         }
 
-        Card::check_alternate_colors_and_descending_rank(
-            self.visible[self.visible.len() - 1], 
-            cards[0])
+        // Runs given by other origins, like the piles, may not follow
+        // the rule of the column
+        self.build_rule.can_build(self.visible[self.visible.len() - 1], cards[0])
+            && cards.windows(2).all(|x| self.build_rule.can_build(x[0], x[1]))
     }
 
     fn poke(&mut self, cards: &Vec<Card>) {
@@ -112,7 +341,52 @@ impl CardDestination for Foundation {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::pile::Pile;
     use crate::card_game::card_containers::test_common::*;
+
+    #[test]
+    fn foundation_build_rules() {
+        let top = vec![Card { suit: CardSuit::HEARTS, rank: CardRank::NINE }];
+        let black = vec![Card { suit: CardSuit::SPADES, rank: CardRank::EIGHT }];
+        let same_suit = vec![Card { suit: CardSuit::HEARTS, rank: CardRank::EIGHT }];
+        let red = vec![Card { suit: CardSuit::DIAMONDS, rank: CardRank::EIGHT }];
+        let wrong_rank = vec![Card { suit: CardSuit::SPADES, rank: CardRank::SEVEN }];
+
+        let cases = [
+            (BuildRule::AlternatingColors, [true, false, false, false]),
+            (BuildRule::SameSuit, [false, true, false, false]),
+            (BuildRule::AnySuit, [true, true, true, false]),
+        ];
+        for (rule, expected) in cases.iter() {
+            let mut foundation = Foundation::new_with_build_rule(top.clone(), *rule);
+            assert_eq!(foundation.get_build_rule(), *rule);
+            for (cards, accepted) in [&black, &same_suit, &red, &wrong_rank].iter().zip(expected.iter()) {
+                assert_eq!(foundation.try_poke(cards), *accepted, "{:?} {:?}", rule, cards);
+            }
+
+            // Empty foundations only accept kings, whatever the rule
+            foundation.peek(1);
+            assert!(!foundation.try_poke(&same_suit));
+            assert!(foundation.try_poke(&vec![Card { suit: CardSuit::CLUBS, rank: CardRank::KING }]));
+        }
+    }
+
+    #[test]
+    fn foundation_from_cards() {
+        let hidden = vec![Card { suit: CardSuit::HEARTS, rank: CardRank::TWO }];
+        let visible = vec![
+            Card { suit: CardSuit::SPADES, rank: CardRank::NINE },
+            Card { suit: CardSuit::DIAMONDS, rank: CardRank::EIGHT },
+        ];
+
+        let foundation = Foundation::from_cards(hidden.clone(), visible.clone()).unwrap();
+        assert_eq!(foundation.get_status(), FoundationStatus { num_hidden: 1, visible: visible.clone() });
+
+        assert!(Foundation::from_cards(Vec::new(), Vec::new()).is_some());
+        assert!(Foundation::from_cards(hidden.clone(), Vec::new()).is_none());
+        assert!(Foundation::from_cards(hidden, vec![visible[1], visible[0]]).is_none());
+    }
+
     #[test]
     fn foundation_new() {
         let cards = vec![
@@ -175,6 +449,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn foundation_empty_column_rules() {
+        let queen = vec![Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN }];
+        let king = vec![Card { suit: CardSuit::SPADES, rank: CardRank::KING }];
+
+        let mut found = create_test_foundation(0, 0, 0);
+        assert!(!found.try_poke(&queen));
+        assert!(found.try_poke(&king));
+
+        found.empty_column_rule = EmptyColumnRule::AnyCard;
+        assert!(found.try_poke(&queen));
+        found.poke(&queen);
+        assert_eq!(found.get_status().visible, queen);
+        // Only empty columns take any card
+        assert!(!found.try_poke(&king));
+
+        let mut out = Vec::new();
+        found.write_bytes(&mut out);
+        let read = Foundation::read_bytes(&mut ByteReader::new(&out)).unwrap();
+        assert_eq!(read.get_empty_column_rule(), EmptyColumnRule::AnyCard);
+    }
+
+    #[test]
+    fn foundation_new_with_faceup() {
+        let cards: Vec<Card> = [CardRank::FOUR, CardRank::THREE, CardRank::TWO].iter()
+            .map(|rank| Card { suit: CardSuit::DIAMONDS, rank: *rank })
+            .collect();
+
+        let found = Foundation::new_with_faceup(cards.clone(), 2);
+        assert_eq!(found.get_status().visible.len(), 2);
+        assert_eq!(found.get_status().num_hidden, 1);
+        assert_eq!(found.get_status().visible, cards[1..].to_vec());
+        // Two diamonds aren't a run, so only the top one can be taken
+        assert_eq!(found.try_peek(2), None);
+        assert_eq!(found.try_peek(1), Some(vec![cards[2]]));
+
+        assert_eq!(Foundation::new_with_faceup(cards.clone(), 5).get_status().visible, cards);
+        assert_eq!(Foundation::new_with_faceup(cards.clone(), 0), Foundation::new(cards));
+    }
+
     #[test]
     fn foundation_peek() {
         foundation_peek_test(3, 1, 1, 2, 1);
@@ -223,6 +537,9 @@ mod tests {
             hidden: generate_random_card_set(hidden),
             visible: generate_descending_alt_color_starting(visible_start, visible_number),
             peek_caused_flip: Vec::new(),
+            build_rule: BuildRule::AlternatingColors,
+            empty_column_rule: EmptyColumnRule::KingsOnly,
+            auto_flip: true,
         }
     }
 
@@ -376,6 +693,50 @@ mod tests {
         assert_eq!(status, found.get_status());
     }
 
+    #[test]
+    fn foundation_flips() {
+        let mut found = create_test_foundation(2, 0, 2);
+        assert_eq!(found.flips(), 0);
+
+        found.peek(1);
+        assert_eq!(found.flips(), 0);
+
+        found.peek(1);
+        assert_eq!(found.flips(), 1);
+
+        let cards = found.peek(1);
+        assert_eq!(found.flips(), 2);
+
+        found.undo_peek(&cards);
+        assert_eq!(found.flips(), 1);
+        assert_eq!(found.hidden.len(), 1);
+    }
+
+    #[test]
+    fn foundation_poke_homed_run() {
+        let card = |suit, rank| Card { suit, rank };
+        let mut pile = Pile::new_with_multi_peek();
+        for rank in [CardRank::ACE, CardRank::TWO, CardRank::THREE] {
+            pile.poke(&vec![card(CardSuit::SPADES, rank)]);
+        }
+        let mut mover = SimpleCardMover {};
+
+        // The three of spades fits, but not the two on top of it
+        let mut found = Foundation::new(vec![card(CardSuit::HEARTS, CardRank::FOUR)]);
+        assert!(found.try_poke(&vec![card(CardSuit::SPADES, CardRank::THREE)]));
+        assert!(!mover.move_cards(&mut pile, &mut found, 2));
+        assert_eq!(pile.get_status().num_cards, 3);
+        assert_eq!(found.visible.len(), 1);
+
+        let mut found = Foundation::new_with_build_rule(vec![card(CardSuit::SPADES, CardRank::FOUR)], BuildRule::SameSuit);
+        assert!(mover.move_cards(&mut pile, &mut found, 2));
+        assert_eq!(found.visible, vec![
+            card(CardSuit::SPADES, CardRank::FOUR),
+            card(CardSuit::SPADES, CardRank::THREE),
+            card(CardSuit::SPADES, CardRank::TWO),
+        ]);
+    }
+
     #[test]
     fn foundation_undo_poke() {
         foundation_undo_poke_case(0, 1, 1);
@@ -402,7 +763,7 @@ mod tests {
         let visible = vec![Card {rank:CardRank::SEVEN, suit: CardSuit::CLUBS}];
         let hidden = vec![Card {rank:CardRank::EIGHT, suit: CardSuit::HEARTS}];
 
-        let mut found = Foundation { visible, hidden, peek_caused_flip: Vec::new() };
+        let mut found = Foundation::from_cards(hidden, visible).unwrap();
 
         let status = found.get_status();
 
@@ -411,4 +772,106 @@ mod tests {
 
         assert_eq! (found.get_status(), status);
     }
+
+    #[test]
+    fn foundation_auto_flip() {
+        let cards = generate_random_card_set(3);
+        let mut found = Foundation::dealt(cards.clone(), 1, BuildRule::AlternatingColors,
+            EmptyColumnRule::KingsOnly, true);
+
+        found.peek(1);
+        assert_eq!(found.get_status(), FoundationStatus { num_hidden: 1, visible: vec![cards[1]] });
+        assert!(!found.flip());
+        assert_eq!(found.flips(), 1);
+    }
+
+    #[test]
+    fn foundation_movable_from() {
+        let card = |suit, rank| Card { suit, rank };
+        let found = Foundation::from_cards(
+            vec![card(CardSuit::CLUBS, CardRank::TWO)],
+            vec![
+                card(CardSuit::SPADES, CardRank::NINE),
+                card(CardSuit::HEARTS, CardRank::EIGHT),
+                card(CardSuit::CLUBS, CardRank::SEVEN),
+            ],
+        ).unwrap();
+
+        assert_eq!(found.movable_from(0), Some(3));
+        assert_eq!(found.movable_from(1), Some(2));
+        assert_eq!(found.movable_from(2), Some(1));
+        assert_eq!(found.movable_from(3), None);
+        assert_eq!(found.movable_from(9), None);
+
+        // Dealt face up, the visible cards needn't be a run
+        let found = Foundation::dealt(
+            vec![
+                card(CardSuit::SPADES, CardRank::NINE),
+                card(CardSuit::HEARTS, CardRank::FOUR),
+                card(CardSuit::CLUBS, CardRank::THREE),
+            ],
+            3, BuildRule::AlternatingColors, EmptyColumnRule::KingsOnly, true);
+        assert_eq!(found.movable_from(0), None);
+        assert_eq!(found.movable_from(1), Some(2));
+    }
+
+    #[test]
+    fn foundation_manual_flip() {
+        let cards = generate_random_card_set(3);
+        let mut found = Foundation::dealt(cards.clone(), 1, BuildRule::AlternatingColors,
+            EmptyColumnRule::KingsOnly, false);
+        let dealt = found.get_status();
+
+        let peeked = found.peek(1);
+        assert_eq!(found.get_status(), FoundationStatus { num_hidden: 2, visible: Vec::new() });
+        assert_eq!(found.flips(), 0);
+        // Nothing can be placed until the hidden card is flipped
+        assert!(!found.try_poke(&vec![Card { suit: CardSuit::SPADES, rank: CardRank::KING }]));
+
+        assert!(found.flip());
+        assert_eq!(found.get_status(), FoundationStatus { num_hidden: 1, visible: vec![cards[1]] });
+        assert_eq!(found.flips(), 1);
+        assert!(!found.flip());
+
+        found.undo_flip();
+        assert_eq!(found.get_status(), FoundationStatus { num_hidden: 2, visible: Vec::new() });
+        assert_eq!(found.flips(), 0);
+
+        // Undoing the peek after a flip hides the flipped card again
+        assert!(found.flip());
+        found.undo_peek(&peeked);
+        assert_eq!(found.get_status(), dealt);
+        assert_eq!(found.flips(), 0);
+    }
+
+    #[test]
+    fn foundation_undo_peek_unhidden_exact() {
+        let hidden = generate_random_card_set(3);
+        let visible = generate_descending_alt_color_starting(2, 3);
+        let mut found = Foundation {
+            hidden: hidden.clone(),
+            visible: visible.clone(),
+            peek_caused_flip: Vec::new(),
+            build_rule: BuildRule::AlternatingColors,
+            empty_column_rule: EmptyColumnRule::KingsOnly,
+            auto_flip: true,
+        };
+
+        // Peeking all the visible cards flips the last hidden one
+        let cards = found.peek(3);
+        assert_eq!(cards, visible);
+        assert_eq!(found.hidden, hidden[..2].to_vec());
+        assert_eq!(found.visible, hidden[2..].to_vec());
+
+        found.undo_peek(&cards);
+        assert_eq!(found.hidden, hidden);
+        assert_eq!(found.visible, visible);
+        assert!(found.peek_caused_flip.is_empty());
+
+        // Undoing the poke removes just the requested visible cards
+        assert_eq!(found.undo_poke(2), visible[1..].to_vec());
+        assert_eq!(found.visible, visible[..1].to_vec());
+        assert_eq!(found.undo_poke(2), Vec::new());
+        assert_eq!(found.hidden, hidden);
+    }
 }
@@ -3,16 +3,29 @@ pub mod pile;
 pub mod foundation;
 pub mod ui;
 pub mod storage;
+pub mod compact;
+pub mod scoring;
+pub mod solver;
 
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use strum::IntoEnumIterator;
+use rand::{thread_rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 use crate::card_game::american_cards::*;
 use crate::card_game::card_containers::*;
 use deck::*;
 use pile::*;
 use foundation::*;
+use compact::*;
+use scoring::*;
 use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fmt;
+use std::sync::Mutex;
+use strum::IntoEnumIterator;
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::{Datelike, NaiveDate};
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CardHolder {
@@ -21,10 +34,20 @@ pub enum CardHolder {
     FOUNDATION(u32),
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum KlondikeAction {
+/// Each of the actions done during a game, as stored in its history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum KlondikeAction {
     MOVE(CardHolder, CardHolder, u32),
-    TAKE
+    /// Cards moved by move_column: origin, destination and number
+    #[allow(non_camel_case_types)]
+    MOVE_COLUMN(u32, u32, u32),
+    TAKE,
+    /// Hidden card of a foundation without auto flip turned visible
+    FLIP(u32),
+    /// Stock shuffled by shuffle_stock: its cards before and after, from
+    /// the bottom to the top one
+    #[allow(non_camel_case_types)]
+    SHUFFLE_STOCK(Vec<Card>, Vec<Card>),
 }
 
 #[derive(Debug, Clone,  PartialEq)]
@@ -33,23 +56,735 @@ pub struct KlondikeMockable<T: CardMover> {
     piles: Vec<Pile>,
     foundations: Vec<Foundation>,
     mover: T,
-    history: Vec<KlondikeAction>,
+    history: VecDeque<KlondikeAction>,
+    /// Actions undone, the last one at the end, to be redone until
+    /// another action is done
+    redo: Vec<KlondikeAction>,
+    /// When present, the oldest actions are dropped from the history,
+    /// and can't be undone, to keep at most this many
+    max_history: Option<usize>,
+    /// Sequence number of the last action requested by a client
+    last_seq: Option<u64>,
+    log: ActionLog,
+    score: Score,
+    /// Cards dealt face up on each foundation, kept for redealing. No more
+    /// than the foundations, the cards of the largest one, as more would
+    /// deal the same.
+    faceup: usize,
+    /// Increased on every change, so clients can detect conflicting ones
+    version: u64,
+    last_modified: LastModified,
+    moves_cache: MovesCache,
+}
+
+/// Time of the last change of a game, in milliseconds since the epoch.
+/// It is left out when comparing games, so games dealt the same way are
+/// equal even if created at different times.
+#[derive(Debug, Copy, Clone)]
+struct LastModified(u64);
+
+impl PartialEq for LastModified {
+    fn eq(&self, _other: &LastModified) -> bool {
+        true
+    }
+}
+
+/// Entry of the action log of a game
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LogEntry {
+    /// Action added to the history
+    Action(KlondikeAction),
+    /// Last action of the history undone
+    Undo,
+}
+
+/// Entries kept on the action log, dropping the oldest ones beyond it
+const MAX_LOG_ENTRIES: usize = 1000;
+
+/// Every action applied to a game, undos included, with its time in
+/// milliseconds since the epoch. It only lasts while the game is in
+/// memory: to_bytes doesn't store it and redeal clears it. Like
+/// LastModified, it is left out when comparing games.
+#[derive(Debug, Clone, Default)]
+struct ActionLog(VecDeque<(u64, LogEntry)>);
+
+impl PartialEq for ActionLog {
+    fn eq(&self, _other: &ActionLog) -> bool {
+        true
+    }
+}
+
+/// Moves found by moves_available and legal_destinations, along with the
+/// version of the game they were found on. Every change increases the
+/// version, so moves kept for an older one are never returned. Like
+/// LastModified, it is left out when comparing games, and isn't copied by
+/// clone.
+#[derive(Debug, Default)]
+struct MovesCache(Mutex<Option<(u64, FoundMoves)>>);
+
+/// Origin, destination and number of cards of a move
+type Move = (CardHolder, CardHolder, u32);
+
+/// Every legal move of a game, and the ones of them listed by moves_available
+#[derive(Debug, Clone)]
+struct FoundMoves {
+    legal: Vec<Move>,
+    available: Vec<Move>,
+}
+
+impl MovesCache {
+    fn get(&self, version: u64) -> Option<FoundMoves> {
+        match &*self.0.lock().unwrap() {
+            Some((cached, moves)) if *cached == version => Some(moves.clone()),
+            _ => None,
+        }
+    }
+
+    fn set(&self, version: u64, moves: &FoundMoves) {
+        *self.0.lock().unwrap() = Some((version, moves.clone()));
+    }
+}
+
+impl Clone for MovesCache {
+    fn clone(&self) -> MovesCache {
+        MovesCache::default()
+    }
+}
+
+impl PartialEq for MovesCache {
+    fn eq(&self, _other: &MovesCache) -> bool {
+        true
+    }
 }
 
 pub type Klondike = KlondikeMockable<SimpleCardMover>;
 
+/// Seed of the games created by Klondike::new with the deterministic-deal feature
+pub const DETERMINISTIC_DEAL_SEED: u64 = 1;
+
+/// Seed of the deal of the given day: the date as the number yyyymmdd.
+/// Unlike the std hashers, it doesn't change between builds.
+pub fn daily_seed(date: NaiveDate) -> u64 {
+    date.year() as u64 * 10_000 + date.month() as u64 * 100 + date.day() as u64
+}
+
 impl Klondike {
+    /// Creates a random game, or always the same one with the
+    /// deterministic-deal feature
     pub fn new() -> Self {
+        if cfg!(feature = "deterministic-deal") {
+            return Klondike::new_with_seed(DETERMINISTIC_DEAL_SEED);
+        }
+
         let mover = SimpleCardMover {};
         KlondikeMockable::new_with_mover(mover)
     }
+
+    /// Creates the deal of the given day, the same for everyone playing it
+    pub fn daily(date: NaiveDate) -> Self {
+        Klondike::new_with_seed(daily_seed(date))
+    }
+
+    /// Creates a game whose deal only depends on the given seed, so the
+    /// same seed always gives the same game.
+    pub fn new_with_seed(seed: u64) -> Self {
+        let cards = Klondike::generate_randomized_card_deck(&mut StdRng::seed_from_u64(seed));
+        KlondikeMockable::deal(&cards, &KlondikeConfig::default(), SimpleCardMover {})
+    }
+
+    /// Creates a game shuffling the cards with the given random number
+    /// generator, so tests or callers needing a particular one can choose it
+    pub fn new_with_rng(rng: &mut dyn RngCore) -> Self {
+        let cards = Klondike::generate_randomized_card_deck(rng);
+        KlondikeMockable::deal(&cards, &KlondikeConfig::default(), SimpleCardMover {})
+    }
+
+    /// Creates a game dealing the given cards in order, without shuffling them.
+    /// The cards must be a full deck: 52 cards with no repetitions.
+    pub fn from_cards(cards: Vec<Card>) -> Result<Self, InvalidCardSet> {
+        check_full_deck(&cards)?;
+
+        Ok(KlondikeMockable::deal(&cards, &KlondikeConfig::default(), SimpleCardMover {}))
+    }
+
+    /// Creates a game with its cards placed as in the given layout. It must
+    /// contain the 52 cards, and the piles and foundations must be in an
+    /// order that could have been reached playing.
+    pub fn from_layout(layout: &KlondikeLayout, draw_count: u32) -> Result<Self, InvalidCardSet> {
+        let mut cards: Vec<Card> = layout.stock.iter().chain(layout.waste.iter()).copied().collect();
+        for pile in &layout.piles {
+            cards.extend(pile);
+        }
+        for foundation in &layout.foundations {
+            cards.extend(&foundation.hidden);
+            cards.extend(&foundation.visible);
+        }
+        check_full_deck(&cards)?;
+        if layout.piles.len() > MAX_HOLDERS || layout.foundations.len() > MAX_HOLDERS {
+            return Err(InvalidCardSet::TooManyHolders(layout.piles.len().max(layout.foundations.len())));
+        }
+
+        let piles = layout.piles.iter().enumerate()
+            .map(|(i, cards)| Pile::from_cards(cards).ok_or(InvalidCardSet::InvalidPile(i)))
+            .collect::<Result<Vec<Pile>, InvalidCardSet>>()?;
+        let foundations = layout.foundations.iter().enumerate()
+            .map(|(i, x)| Foundation::from_cards(x.hidden.clone(), x.visible.clone())
+                .ok_or(InvalidCardSet::InvalidFoundation(i)))
+            .collect::<Result<Vec<Foundation>, InvalidCardSet>>()?;
+
+        Ok(KlondikeMockable {
+            deck: Box::new(Deck::from_cards(layout.stock.clone(), layout.waste.clone(), draw_count)),
+            piles,
+            foundations,
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            score: Score::new(ScoringMode::Standard, now_millis()),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(now_millis()),
+            moves_cache: MovesCache::default(),
+        })
+    }
+
+    /// Creates a game with a custom number of piles and foundations.
+    /// The foundations get 1, 2, 3... cards, so the deck must have enough
+    /// cards to fill them.
+    pub fn new_with_config(config: KlondikeConfig) -> Result<Self, InvalidConfig> {
+        let cards = match config.seed {
+            Some(seed) => Klondike::generate_randomized_card_deck(&mut StdRng::seed_from_u64(seed)),
+            None => Klondike::generate_randomized_card_deck(&mut thread_rng()),
+        };
+        Klondike::new_with_deck(cards, config)
+    }
+
+    /// Creates a game dealing the given cards in order, without shuffling
+    /// them, on the board of the given config. Unlike from_cards, the
+    /// cards needn't be a full deck, for variants using other decks, as
+    /// long as there are enough of them to fill the foundations.
+    pub fn new_with_deck(cards: Vec<Card>, config: KlondikeConfig) -> Result<Self, InvalidConfig> {
+        if config.piles > MAX_HOLDERS || config.foundations > MAX_HOLDERS {
+            return Err(InvalidConfig::TooManyHolders(config.piles.max(config.foundations)));
+        }
+
+        let needed = config.foundations * (config.foundations + 1) / 2;
+        if needed > cards.len() {
+            return Err(InvalidConfig::NotEnoughCards { needed, available: cards.len() });
+        }
+
+        if config.draw_count == 0 || config.draw_count as usize > cards.len() {
+            return Err(InvalidConfig::InvalidDrawCount(config.draw_count));
+        }
+
+        Ok(KlondikeMockable::deal(&cards, &config, SimpleCardMover {}))
+    }
+
+    /// Restores a game from the bytes returned by to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidBytes> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.read_u8()? != COMPACT_FORMAT_VERSION {
+            return Err(InvalidBytes);
+        }
+
+        let num_piles = reader.read_u8()?;
+        let num_foundations = reader.read_u8()?;
+
+        let deck = Box::new(Deck::read_bytes(&mut reader)?);
+        let piles = (0..num_piles)
+            .map(|_i| Pile::read_bytes(&mut reader))
+            .collect::<Result<Vec<Pile>, InvalidBytes>>()?;
+        let foundations = (0..num_foundations)
+            .map(|_i| Foundation::read_bytes(&mut reader))
+            .collect::<Result<Vec<Foundation>, InvalidBytes>>()?;
+
+        let num_actions = reader.read_u32()?;
+        let history = (0..num_actions)
+            .map(|_i| read_action(&mut reader, num_piles, num_foundations))
+            .collect::<Result<VecDeque<KlondikeAction>, InvalidBytes>>()?;
+        let num_undone = reader.read_u32()?;
+        let redo = (0..num_undone)
+            .map(|_i| read_action(&mut reader, num_piles, num_foundations))
+            .collect::<Result<Vec<KlondikeAction>, InvalidBytes>>()?;
+
+        let last_seq = match reader.read_u8()? {
+            0 => None,
+            1 => Some(reader.read_u64()?),
+            _ => return Err(InvalidBytes),
+        };
+        let score = Score::read_bytes(&mut reader)?;
+        let faceup = reader.read_u8()? as usize;
+        let version = reader.read_u64()?;
+        let max_history = match reader.read_u8()? {
+            0 => None,
+            1 => Some(reader.read_u32()? as usize),
+            _ => return Err(InvalidBytes),
+        };
+        let last_modified = LastModified(reader.read_u64()?);
+
+        if !reader.is_finished() {
+            return Err(InvalidBytes);
+        }
+
+        Ok(KlondikeMockable {
+            deck,
+            piles,
+            foundations,
+            mover: SimpleCardMover {},
+            history,
+            redo,
+            max_history,
+            last_seq,
+            log: ActionLog::default(),
+            score,
+            faceup,
+            version,
+            last_modified,
+            moves_cache: MovesCache::default(),
+        })
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+const COMPACT_FORMAT_VERSION: u8 = 15;
+
+fn write_card_holder(out: &mut Vec<u8>, holder: CardHolder) {
+    match holder {
+        CardHolder::DECK => out.extend_from_slice(&[0, 0]),
+        CardHolder::PILE(idx) => out.extend_from_slice(&[1, idx as u8]),
+        CardHolder::FOUNDATION(idx) => out.extend_from_slice(&[2, idx as u8]),
+    }
+}
+
+fn write_action(out: &mut Vec<u8>, action: &KlondikeAction) {
+    match action {
+        KlondikeAction::TAKE => out.push(0),
+        KlondikeAction::FLIP(foundation) => out.extend_from_slice(&[4, *foundation as u8]),
+        KlondikeAction::SHUFFLE_STOCK(before, after) => {
+            out.push(5);
+            write_cards(out, before);
+            write_cards(out, after);
+        }
+        KlondikeAction::MOVE_COLUMN(origin, destination, number) => {
+            out.extend_from_slice(&[3, *origin as u8, *destination as u8, *number as u8]);
+        }
+        KlondikeAction::MOVE(origin, destination, number) => {
+            out.push(1);
+            write_card_holder(out, *origin);
+            write_card_holder(out, *destination);
+            // Only successful moves are stored, so they never exceed a deck
+            out.push(*number as u8);
+        }
+    }
+}
+
+/// Reads an action checking its holders exist on a board of the given size
+fn read_action(reader: &mut ByteReader, num_piles: u8, num_foundations: u8)
+        -> Result<KlondikeAction, InvalidBytes> {
+    Ok(match reader.read_u8()? {
+        0 => KlondikeAction::TAKE,
+        3 => {
+            let origin = reader.read_u8()?;
+            let destination = reader.read_u8()?;
+            if origin >= num_foundations || destination >= num_foundations {
+                return Err(InvalidBytes);
+            }
+            KlondikeAction::MOVE_COLUMN(origin as u32, destination as u32, reader.read_u8()? as u32)
+        }
+        4 => {
+            let foundation = reader.read_u8()?;
+            if foundation >= num_foundations {
+                return Err(InvalidBytes);
+            }
+            KlondikeAction::FLIP(foundation as u32)
+        }
+        5 => KlondikeAction::SHUFFLE_STOCK(reader.read_cards()?, reader.read_cards()?),
+        1 => {
+            let origin = read_card_holder(reader, num_piles, num_foundations)?;
+            let destination = read_card_holder(reader, num_piles, num_foundations)?;
+            KlondikeAction::MOVE(origin, destination, reader.read_u8()? as u32)
+        }
+        _ => return Err(InvalidBytes),
+    })
+}
+
+/// Reads a card holder checking it exists on a board of the given size
+fn read_card_holder(reader: &mut ByteReader, num_piles: u8, num_foundations: u8)
+        -> Result<CardHolder, InvalidBytes> {
+    let kind = reader.read_u8()?;
+    let idx = reader.read_u8()?;
+    match kind {
+        0 => Ok(CardHolder::DECK),
+        1 if idx < num_piles => Ok(CardHolder::PILE(idx as u32)),
+        2 if idx < num_foundations => Ok(CardHolder::FOUNDATION(idx as u32)),
+        _ => Err(InvalidBytes),
+    }
+}
+
+/// Size of the board and rules of a game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KlondikeConfig {
+    pub piles: usize,
+    pub foundations: usize,
+    /// Number of cards moved from the stock to the waste on each take
+    pub draw_count: u32,
+    /// When present, the deal only depends on this seed
+    pub seed: Option<u64>,
+    /// Cards that can be placed on the foundations
+    pub build_rule: BuildRule,
+    pub scoring: ScoringMode,
+    /// Cards dealt face up on each foundation, all of them if it has less
+    pub faceup: usize,
+    /// Cards that can be placed on the foundations without cards
+    pub empty_column: EmptyColumnRule,
+    /// Whether hidden cards are turned visible by themselves, instead
+    /// of with flip
+    pub auto_flip: bool,
+    /// Actions kept to be undone, all of them if None
+    pub max_history: Option<usize>,
+}
+
+impl Default for KlondikeConfig {
+    fn default() -> Self {
+        KlondikeConfig {
+            piles: 4,
+            foundations: 7,
+            draw_count: 1,
+            seed: None,
+            build_rule: BuildRule::AlternatingColors,
+            scoring: ScoringMode::Standard,
+            faceup: 1,
+            empty_column: EmptyColumnRule::KingsOnly,
+            auto_flip: true,
+            max_history: None,
+        }
+    }
+}
+
+/// Piles or foundations a game can have at most, as to_bytes stores
+/// their number and indexes in a byte
+pub const MAX_HOLDERS: usize = u8::MAX as usize;
+
+/// Reason for rejecting the configuration given to create a game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InvalidConfig {
+    NotEnoughCards { needed: usize, available: usize },
+    InvalidDrawCount(u32),
+    /// More piles or foundations than MAX_HOLDERS, the largest number given
+    TooManyHolders(usize),
+}
+
+/// Reason for rejecting the cards given to create a game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InvalidCardSet {
+    WrongNumberOfCards(usize),
+    RepeatedCard(Card),
+    /// The cards of the pile with this index are not in a valid order
+    InvalidPile(usize),
+    /// The cards of the foundation with this index are not in a valid order
+    InvalidFoundation(usize),
+    /// More piles or foundations than MAX_HOLDERS, the largest number given
+    TooManyHolders(usize),
+}
+
+impl fmt::Display for InvalidCardSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidCardSet::WrongNumberOfCards(number) =>
+                write!(f, "Expected 52 cards, found {}", number),
+            InvalidCardSet::RepeatedCard(card) =>
+                write!(f, "Repeated card: {}", card.to_plain_string().trim()),
+            InvalidCardSet::InvalidPile(idx) =>
+                write!(f, "Invalid cards on pile {}", idx + 1),
+            InvalidCardSet::InvalidFoundation(idx) =>
+                write!(f, "Invalid cards on foundation {}", idx + 1),
+            InvalidCardSet::TooManyHolders(number) =>
+                write!(f, "At most {} piles and {} foundations, found {}", MAX_HOLDERS, MAX_HOLDERS, number),
+        }
+    }
+}
+
+/// Reason for not allowing a move
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MoveError {
+    SameOriginAndDestination,
+    InvalidOrigin(CardHolder),
+    InvalidDestination(CardHolder),
+    /// Cards are never placed back on the deck, wherever they come from
+    DestinationIsDeck,
+    /// The origin doesn't have that number of cards that can be taken
+    CannotTake(u32),
+    /// The destination doesn't accept the cards
+    CannotPlace,
+    /// The cards would go straight back to where the last move took them
+    /// from, and the mover doesn't allow it
+    ReversesLastMove,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::SameOriginAndDestination =>
+                write!(f, "The origin and the destination are the same"),
+            MoveError::InvalidOrigin(holder) =>
+                write!(f, "Cards can't be taken from {}", holder_name(*holder)),
+            MoveError::InvalidDestination(holder) =>
+                write!(f, "Cards can't be placed on {}", holder_name(*holder)),
+            MoveError::DestinationIsDeck =>
+                write!(f, "Cards can't be placed on the deck"),
+            MoveError::CannotTake(number) =>
+                write!(f, "There aren't {} cards to take from the origin", number),
+            MoveError::CannotPlace =>
+                write!(f, "The destination doesn't accept the cards"),
+            MoveError::ReversesLastMove =>
+                write!(f, "The cards can't go straight back where they came from"),
+        }
+    }
+}
+
+fn holder_name(holder: CardHolder) -> String {
+    match holder {
+        CardHolder::DECK => "the deck".to_string(),
+        CardHolder::PILE(idx) => format!("pile {}", idx + 1),
+        CardHolder::FOUNDATION(idx) => format!("foundation {}", idx + 1),
+    }
+}
+
+/// Cards of a game in progress, each list from the bottom to the top card.
+/// The last card of the stock is the next one to be taken.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KlondikeLayout {
+    pub stock: Vec<Card>,
+    pub waste: Vec<Card>,
+    pub piles: Vec<Vec<Card>>,
+    pub foundations: Vec<FoundationLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FoundationLayout {
+    pub hidden: Vec<Card>,
+    pub visible: Vec<Card>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct KlondikeStatus {
     pub deck: DeckStatus,
     pub piles: Vec<PileStatus>,
-    pub foundations: Vec<FoundationStatus>
+    pub foundations: Vec<FoundationStatus>,
+    /// Number of hidden cards turned visible during the game
+    pub flips: u32,
+    pub score: i32,
+    /// Whether there is any action that can be undone
+    pub undo_available: bool,
+    /// Highest rank sent home for each suit, whatever the pile holding it
+    pub homes_by_suit: HashMap<CardSuit, Option<CardRank>>,
+    /// Estimate of how hard the deal is, only while no action is done
+    pub difficulty: Option<Difficulty>,
+    /// Version of the game, see get_version
+    pub version: u64,
+    /// See get_last_modified
+    pub last_modified: u64,
+}
+
+/// Statuses showing the same position are equal, even if the game went
+/// through different changes to reach it
+impl PartialEq for KlondikeStatus {
+    fn eq(&self, other: &KlondikeStatus) -> bool {
+        self.deck == other.deck
+            && self.piles == other.piles
+            && self.foundations == other.foundations
+            && self.flips == other.flips
+            && self.score == other.score
+            && self.undo_available == other.undo_available
+            && self.homes_by_suit == other.homes_by_suit
+            && self.difficulty == other.difficulty
+    }
+}
+
+/// Rough estimate of how hard it is to win a deal
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// Why a game is stuck or not, see stuck_analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuckReport {
+    /// Moves and flips that can be done right now
+    pub available_moves: usize,
+    /// Whether taking from the stock would still change it
+    pub can_cycle: bool,
+    /// One of the available moves or flips, if any
+    pub sample_move: Option<KlondikeAction>,
+}
+
+/// Which cards auto_moves sends home on its own
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AutoPolicy {
+    /// Only aces and twos
+    AcesAndTwosOnly,
+    /// Aces, twos and the cards no longer needed to build on the
+    /// foundations: those whose opposite color homes are both at most one
+    /// rank behind them
+    SafeHomes,
+    /// Every card that can be sent home
+    Aggressive,
+}
+
+impl AutoPolicy {
+    fn allows(self, card: Card, homes: &HashMap<CardSuit, Option<CardRank>>) -> bool {
+        match self {
+            AutoPolicy::AcesAndTwosOnly => card.rank.to_u8() <= CardRank::TWO.to_u8(),
+            AutoPolicy::SafeHomes => is_safe_home(card, homes),
+            AutoPolicy::Aggressive => true,
+        }
+    }
+}
+
+/// Aces and twos are always safe to send home. Any other card is once
+/// both homes of the opposite color are at most one rank behind it, as
+/// no card left could be built on it.
+fn is_safe_home(card: Card, homes: &HashMap<CardSuit, Option<CardRank>>) -> bool {
+    card.rank.to_u8() <= CardRank::TWO.to_u8() || CardSuit::iter()
+        .filter(|suit| suit.color() != card.suit.color())
+        .all(|suit| homes[&suit].map_or(0, |rank| rank.to_u8()) + 1 >= card.rank.to_u8())
+}
+
+/// Aces with at least this number of hidden cards on top of them are
+/// considered buried
+const BURIED_ACE_DEPTH: usize = 3;
+
+/// Parts of a status that changed since a previous one. The piles and
+/// foundations are given by index, and the fields left out didn't change.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KlondikeStatusDiff {
+    /// Version of the previous status, None if everything is included
+    pub since_version: Option<u64>,
+    pub version: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deck: Option<DeckStatus>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub piles: BTreeMap<usize, PileStatus>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub foundations: BTreeMap<usize, FoundationStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flips: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undo_available: Option<bool>,
+}
+
+impl KlondikeStatus {
+    /// Changes from prev to this status
+    pub fn diff(&self, prev: &KlondikeStatus) -> KlondikeStatusDiff {
+        self.changes_since(Some(prev))
+    }
+
+    /// Diff including every part of the status, for clients without a
+    /// previous one
+    pub fn as_diff(&self) -> KlondikeStatusDiff {
+        self.changes_since(None)
+    }
+
+    fn changes_since(&self, prev: Option<&KlondikeStatus>) -> KlondikeStatusDiff {
+        fn changed<T: PartialEq + Clone>(current: &T, prev: Option<&T>) -> Option<T> {
+            if prev == Some(current) { None } else { Some(current.clone()) }
+        }
+
+        KlondikeStatusDiff {
+            since_version: prev.map(|x| x.version),
+            version: self.version,
+            deck: changed(&self.deck, prev.map(|x| &x.deck)),
+            piles: self.piles.iter().enumerate()
+                .filter_map(|(i, x)| changed(x, prev.and_then(|p| p.piles.get(i))).map(|x| (i, x)))
+                .collect(),
+            foundations: self.foundations.iter().enumerate()
+                .filter_map(|(i, x)| changed(x, prev.and_then(|p| p.foundations.get(i))).map(|x| (i, x)))
+                .collect(),
+            flips: changed(&self.flips, prev.map(|x| &x.flips)),
+            score: changed(&self.score, prev.map(|x| &x.score)),
+            undo_available: changed(&self.undo_available, prev.map(|x| &x.undo_available)),
+        }
+    }
+
+    /// Single line with the number of cards on each place, like
+    /// "D(w1/s23) P:0,0,0,0 F:(h0,v1)(h1,v1)". Only ascii, to be logged.
+    pub fn summary(&self) -> String {
+        let piles: Vec<String> = self.piles.iter().map(|x| x.num_cards.to_string()).collect();
+        let foundations: String = self.foundations.iter()
+            .map(|x| format!("(h{},v{})", x.num_hidden, x.visible.len()))
+            .collect();
+
+        format!("D(w{}/s{}) P:{} F:{}",
+            self.deck.cards_on_waste, self.deck.cards_on_stock, piles.join(","), foundations)
+    }
+}
+
+/// Renders the board as plain text (no colors): the piles and the deck
+/// on top, and the foundations below them.
+impl fmt::Display for KlondikeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut header = String::new();
+        for i in 0..self.piles.len() {
+            header.push_str(&format!("  P{:<3}", i + 1));
+        }
+        writeln!(f, "{}        D", header)?;
+
+        let piles: Vec<String> = self.piles.iter()
+            .map(|x| fmt_card_cell(x.top_card))
+            .collect();
+        writeln!(f, "{}       {} [{:^width$}]",
+            piles.join(" "),
+            fmt_card_cell(self.deck.top_card_on_waste),
+            self.deck.cards_on_stock,
+            width = CARD_WIDTH)?;
+
+        writeln!(f)?;
+
+        let mut header = String::new();
+        for i in 0..self.foundations.len() {
+            header.push_str(&format!("  F{:<3}", i + 1));
+        }
+        write!(f, "{}", header.trim_end())?;
+
+        let max_num_cards = self.foundations.iter()
+            .map(|x| -> u32 {x.num_hidden + x.visible.len() as u32})
+            .max().unwrap_or(0);
+
+        for i in 0..max_num_cards {
+            let row: Vec<String> = self.foundations.iter()
+                .map(|x| fmt_foundation_cell(x, i))
+                .collect();
+            write!(f, "\n{}", row.join(" ").trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Every cell of the board is CARD_WIDTH characters between brackets,
+/// whatever the rank of the card, so the columns stay aligned
+fn fmt_card_cell(card: Option<Card>) -> String {
+    match card {
+        None => format!("[{}]", " ".repeat(CARD_WIDTH)),
+        Some(card) => format!("[{}]", card.to_plain_string()),
+    }
+}
+
+fn fmt_foundation_cell(foundation: &FoundationStatus, index: u32) -> String {
+    if index < foundation.num_hidden {
+        format!("[{:^width$}]", "X", width = CARD_WIDTH)
+    } else {
+        match foundation.visible.get((index - foundation.num_hidden) as usize) {
+            None => " ".repeat(CARD_WIDTH + 2),
+            Some(card) => fmt_card_cell(Some(*card)),
+        }
+    }
 }
 
 macro_rules! exec_move_cards {
@@ -65,65 +800,219 @@ macro_rules! exec_move_cards {
 
 impl<T: CardMover> KlondikeMockable<T> {
 
-    fn new_with_mover(mover: T) -> Self {
-        let cards = KlondikeMockable::<T>::generate_randomized_card_deck();
-        let mut card_idx = 0;
-
-        let mut piles: Vec<Pile> = Vec::new();
-        for _i in 0..4 {
-            piles.push(Pile::new());
-        }
-
-        let mut foundations: Vec<Foundation> = Vec::new();
+    /// Creates a random game where the cards are moved by the given mover
+    pub fn new_with_mover(mover: T) -> Self {
+        let cards = KlondikeMockable::<T>::generate_randomized_card_deck(&mut thread_rng());
+        KlondikeMockable::deal(&cards, &KlondikeConfig::default(), mover)
+    }
 
-        for i in 1..8 {
-            foundations.push(Foundation::new(cards[card_idx..card_idx + i].to_vec()));
-            card_idx += i;
-        }
+    fn deal(cards: &[Card], config: &KlondikeConfig, mover: T) -> Self {
+        let (deck, piles, foundations) = deal_cards(cards, config);
 
         KlondikeMockable {
             piles,
             foundations,
-            deck: Box::new(Deck::new(&cards[card_idx..].to_vec())),
+            deck,
             mover,
-            history: Vec::new()
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: config.max_history,
+            last_seq: None,
+            log: ActionLog::default(),
+            score: Score::new(config.scoring, now_millis()),
+            faceup: config.faceup.min(config.foundations),
+            version: 0,
+            last_modified: LastModified(now_millis()),
+            moves_cache: MovesCache::default(),
         }
     }
 
-    fn generate_randomized_card_deck() -> Vec<Card> {
-        let mut cards: Vec<Card> = Vec::new();
-        for suit in CardSuit::iter() {
-            for rank in CardRank::iter() {
-                cards.push(Card {
-                    rank: rank,
-                    suit: suit,
-                });
-            }
-        }
-        let mut rng = thread_rng();
-        cards.shuffle(&mut rng);
+    /// Starts a new random game on the same board, discarding the current one
+    /// and its history. The cards of the game are shuffled, so games with
+    /// other decks keep theirs.
+    pub fn redeal(&mut self) {
+        let mut cards: Vec<Card> = self.deck.upcoming().copied()
+            .chain(self.piles.iter().flat_map(|x| x.cards().iter().copied()))
+            .chain(self.foundations.iter().flat_map(|x| x.hidden_cards().iter().copied()
+                .chain(x.get_status().visible)))
+            .collect();
+        cards.shuffle(&mut thread_rng());
+        let config = KlondikeConfig {
+            piles: self.piles.len(),
+            foundations: self.foundations.len(),
+            draw_count: self.deck.get_draw_count(),
+            seed: None,
+            build_rule: self.foundations.first()
+                .map_or(BuildRule::AlternatingColors, |x| x.get_build_rule()),
+            scoring: self.score.mode(),
+            faceup: self.faceup,
+            empty_column: self.foundations.first()
+                .map_or(EmptyColumnRule::KingsOnly, |x| x.get_empty_column_rule()),
+            auto_flip: self.foundations.first().is_none_or(|x| x.get_auto_flip()),
+            max_history: self.max_history,
+        };
+        let (deck, piles, foundations) = deal_cards(&cards, &config);
+
+        self.deck = deck;
+        self.piles = piles;
+        self.foundations = foundations;
+        self.history.clear();
+        self.redo.clear();
+        self.log.0.clear();
+        self.score = Score::new(self.score.mode(), now_millis());
+        self.version += 1;
+        self.last_modified = LastModified(now_millis());
+    }
+
+    fn generate_randomized_card_deck(rng: &mut dyn RngCore) -> Vec<Card> {
+        let mut cards = Card::full_deck();
+        cards.shuffle(rng);
         return cards;
     }
 
     pub fn move_cards(&mut self, origin: CardHolder, destination: CardHolder, number: u32) -> bool {
+        if self.reverses_last_move(origin, destination, number) {
+            return false;
+        }
         if self.do_move_cards(origin, destination, number, false) {
-            self.history.push(KlondikeAction::MOVE(origin, destination, number));
+            self.record(KlondikeAction::MOVE(origin, destination, number));
             return true;
         }
         false
     }
 
+    /// Moves the longest run of visible cards of the origin foundation
+    /// that the destination foundation accepts. It is undone as a
+    /// single action. Returns true if any card was moved.
+    pub fn move_column(&mut self, origin_foundation: u32, dest_foundation: u32) -> bool {
+        let origin = CardHolder::FOUNDATION(origin_foundation);
+        let destination = CardHolder::FOUNDATION(dest_foundation);
+        let visible = match self.foundations.get(origin_foundation as usize) {
+            Some(foundation) => foundation.get_status().visible.len() as u32,
+            None => return false,
+        };
+
+        for number in (1..=visible).rev() {
+            if self.can_move(origin, destination, number) {
+                if !self.do_move_cards(origin, destination, number, false) {
+                    return false;
+                }
+                self.record(KlondikeAction::MOVE_COLUMN(origin_foundation, dest_foundation, number));
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Moves the given card together with all the cards on top of it.
+    /// Returns false if the card can't be taken from the origin or the
+    /// destination doesn't accept them.
+    pub fn move_from_card(&mut self, origin: CardHolder, card: Card, destination: CardHolder) -> bool {
+        // The cards taken from a foundation must be a run
+        if let CardHolder::FOUNDATION(idx) = origin {
+            let number = self.foundations.get(idx as usize).and_then(|x| {
+                let index = x.get_status().visible.iter().position(|c| *c == card)?;
+                x.movable_from(index)
+            });
+            return number.is_some_and(|number| self.move_cards(origin, destination, number as u32));
+        }
+
+        let card_origin = match self.get_card_origin(origin) {
+            Some(card_origin) => card_origin,
+            None => return false,
+        };
+
+        let mut number = 1;
+        while let Some(cards) = card_origin.try_peek(number) {
+            if cards[0] == card {
+                return self.move_cards(origin, destination, number as u32);
+            }
+            number += 1;
+        }
+
+        false
+    }
+
+    /// Turns visible the top hidden card of the given foundation, when it
+    /// has no visible cards. Only needed without auto flip.
+    pub fn flip(&mut self, foundation: u32) -> bool {
+        let flipped = self.foundations.get_mut(foundation as usize)
+            .is_some_and(|x| x.flip());
+        if flipped {
+            self.record(KlondikeAction::FLIP(foundation));
+        }
+        flipped
+    }
+
+    /// Checks whether the cards could be moved, without moving them
+    pub fn can_move(&self, origin: CardHolder, destination: CardHolder, number: u32) -> bool {
+        self.check_move(origin, destination, number).is_ok()
+    }
+
+    /// Like can_move, telling why the cards can't be moved
+    pub fn check_move(&self, origin: CardHolder, destination: CardHolder, number: u32) -> Result<(), MoveError> {
+        if destination == CardHolder::DECK {
+            return Err(MoveError::DestinationIsDeck);
+        }
+        if origin == destination {
+            return Err(MoveError::SameOriginAndDestination);
+        }
+        if origin == CardHolder::DECK && number != 1 {
+            return Err(MoveError::CannotTake(number));
+        }
+        let card_origin = self.get_card_origin(origin)
+            .ok_or(MoveError::InvalidOrigin(origin))?;
+        let card_destination = self.get_card_destination(destination)
+            .ok_or(MoveError::InvalidDestination(destination))?;
+
+        let cards = card_origin.try_peek(number as usize)
+            .ok_or(MoveError::CannotTake(number))?;
+        if self.reverses_last_move(origin, destination, number) {
+            return Err(MoveError::ReversesLastMove);
+        }
+        if card_destination.try_poke(&cards) { Ok(()) } else { Err(MoveError::CannotPlace) }
+    }
+
+    /// Whether the move only takes the cards of the last action back to
+    /// where they came from, and the mover doesn't allow it
+    fn reverses_last_move(&self, origin: CardHolder, destination: CardHolder, number: u32) -> bool {
+        if self.mover.allows_reversing() {
+            return false;
+        }
+
+        let last_move = match self.history.back() {
+            Some(KlondikeAction::MOVE(origin, destination, number)) => (*origin, *destination, *number),
+            Some(KlondikeAction::MOVE_COLUMN(origin, destination, number)) =>
+                (CardHolder::FOUNDATION(*origin), CardHolder::FOUNDATION(*destination), *number),
+            _ => return false,
+        };
+        last_move == (destination, origin, number)
+    }
+
+    /// Both card holders exist, they are different and the destination
+    /// is not the deck
+    fn is_valid_route(&self, origin: CardHolder, destination: CardHolder) -> bool {
+        origin != destination
+            && self.get_card_origin(origin).is_some()
+            && self.get_card_destination(destination).is_some()
+    }
+
     fn do_move_cards(&mut self, origin: CardHolder, destination: CardHolder, number: u32, is_undo: bool) -> bool {
+        // Undos use the origin and destination of the move, so nothing,
+        // not even an undo, has the deck as destination
+        if destination == CardHolder::DECK || !self.is_valid_route(origin, destination) {
+            return false;
+        }
+        // Only the top card of the waste is ever taken from the deck
+        if origin == CardHolder::DECK && number != 1 {
+            return false;
+        }
+
         match destination {
             CardHolder::FOUNDATION(dest_idx) => match origin {
                 CardHolder::FOUNDATION(origin_idx) => {
                     // Both Origin and Destination are Foundations
-                    // First do some sanitary checks
-                    let elements = self.foundations.len() as u32;
-                    if origin_idx == dest_idx || elements < origin_idx || elements < dest_idx {
-                        return false;
-                    }
-
                     // Now whe have to split the vector in order to extract 
                     // the two mutable elements safely
                     let (origin, destination) = extract_two_mutable_elements(
@@ -158,12 +1047,6 @@ impl<T: CardMover> KlondikeMockable<T> {
                 }
                 CardHolder::PILE(origin_idx) => {
                     // Both Origin and Destination are Piles
-                    // First do some sanitary checks
-                    let elements = self.piles.len() as u32;
-                    if origin_idx == dest_idx || elements < origin_idx || elements < dest_idx {
-                        return false;
-                    }
-
                     // Now whe have to split the vector in order to extract the 
                     // two mutable elements safely
                     let (origin, destination) = extract_two_mutable_elements(
@@ -187,44 +1070,711 @@ impl<T: CardMover> KlondikeMockable<T> {
         }
     }
 
+    /// Takes from the stock. Nothing is recorded if there are no cards
+    /// on the deck.
     pub fn take(&mut self) {
-        (*(self.deck)).take();
-        self.history.push(KlondikeAction::TAKE);
+        if self.deck.try_take() {
+            self.record(KlondikeAction::TAKE);
+        }
     }
 
-    pub fn get_status(&self) -> KlondikeStatus {
-        KlondikeStatus {
-            deck: self.deck.get_status(),
-            piles: self.piles.iter()
-                .map(|x| -> PileStatus {return x.get_status();}).collect(),
-            foundations: self.foundations.iter()
-                .map(|x| -> FoundationStatus {return x.get_status();}).collect()
+    /// Shuffles the cards left on the stock, leaving the waste as it is.
+    /// It is undone like any other action, putting them back in order.
+    pub fn shuffle_stock(&mut self) {
+        if self.deck.stock_snapshot().len() < 2 {
+            return;
         }
+        let before = self.deck.stock_snapshot().to_vec();
+        self.deck.shuffle_remaining(&mut thread_rng());
+        let after = self.deck.stock_snapshot().to_vec();
+        self.record(KlondikeAction::SHUFFLE_STOCK(before, after));
     }
 
-    /// Move the top card of the given origin to the corresponding pile 
-    /// (the first empty one in case is an Ace). return true if success
-    pub fn to_pile(&mut self, origin: CardHolder) -> bool {
-        for i in 0..self.piles.len() {
-            if self.move_cards(origin, CardHolder::PILE(i as u32), 1) {
-                return true;
+    /// Takes n times from the stock. Each take is recorded on its own,
+    /// so it takes n undos to revert it.
+    pub fn take_n(&mut self, n: u32) {
+        for _i in 0..n {
+            self.take();
+        }
+    }
+
+    /// Compact binary representation of the game, including the history
+    /// so undo keeps working after restoring it with from_bytes.
+    /// Each card uses a single byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![
+            COMPACT_FORMAT_VERSION,
+            self.piles.len() as u8,
+            self.foundations.len() as u8,
+        ];
+
+        self.deck.write_bytes(&mut out);
+        for pile in &self.piles {
+            pile.write_bytes(&mut out);
+        }
+        for foundation in &self.foundations {
+            foundation.write_bytes(&mut out);
+        }
+
+        write_u32(&mut out, self.history.len() as u32);
+        for action in &self.history {
+            write_action(&mut out, action);
+        }
+        write_u32(&mut out, self.redo.len() as u32);
+        for action in &self.redo {
+            write_action(&mut out, action);
+        }
+
+        match self.last_seq {
+            None => out.push(0),
+            Some(seq) => {
+                out.push(1);
+                write_u64(&mut out, seq);
+            }
+        }
+        self.score.write_bytes(&mut out);
+        // Never more than the cards of a deck
+        out.push(self.faceup as u8);
+        write_u64(&mut out, self.version);
+        match self.max_history {
+            None => out.push(0),
+            Some(max) => {
+                out.push(1);
+                write_u32(&mut out, max as u32);
+            }
+        }
+        write_u64(&mut out, self.last_modified.0);
+
+        out
+    }
+
+    /// Sequence number of the last action requested by a client, used
+    /// to detect repeated requests.
+    pub fn get_last_seq(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    pub fn set_last_seq(&mut self, seq: Option<u64>) {
+        self.last_seq = seq;
+    }
+
+    /// Returns the actions done so far, not including the undone ones.
+    /// Applying them to a game with the same deal reproduces this one,
+    /// unless the oldest ones were dropped because of max_history.
+    pub fn replay(&self) -> Vec<KlondikeAction> {
+        self.history.iter().cloned().collect()
+    }
+
+    /// Executes the given actions in order, as returned by replay.
+    /// Moves that aren't allowed are ignored.
+    pub fn apply_replay(&mut self, actions: &[KlondikeAction]) {
+        for action in actions {
+            match action {
+                KlondikeAction::MOVE(origin, destination, number) => {
+                    self.move_cards(*origin, *destination, *number);
+                },
+                KlondikeAction::MOVE_COLUMN(origin, destination, _number) => {
+                    self.move_column(*origin, *destination);
+                },
+                KlondikeAction::TAKE => self.take(),
+                KlondikeAction::FLIP(foundation) => { self.flip(*foundation); },
+                KlondikeAction::SHUFFLE_STOCK(before, after) => {
+                    if self.deck.stock_snapshot() == &before[..] && self.deck.reorder_stock(after) {
+                        self.record(action.clone());
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn get_status(&self) -> KlondikeStatus {
+        KlondikeStatus {
+            deck: self.deck.get_status(),
+            piles: self.piles.iter()
+                .map(|x| -> PileStatus {return x.get_status();}).collect(),
+            foundations: self.foundations.iter()
+                .map(|x| -> FoundationStatus {return x.get_status();}).collect(),
+            flips: self.foundations.iter().map(|x| x.flips()).sum(),
+            score: self.get_score(),
+            homes_by_suit: self.homes_by_suit(),
+            undo_available: !self.history.is_empty(),
+            difficulty: if self.history.is_empty() { Some(self.estimate_difficulty()) } else { None },
+            version: self.version,
+            last_modified: self.last_modified.0,
+        }
+    }
+
+    /// Estimates the difficulty of the current layout without solving it,
+    /// from how deep the aces and twos are hidden and whether any card on
+    /// the foundations can be moved right away.
+    pub fn estimate_difficulty(&self) -> Difficulty {
+        let mut blocking = 0;
+        let mut buried_aces = 0;
+        for foundation in &self.foundations {
+            let hidden = foundation.hidden_cards();
+            for (i, card) in hidden.iter().enumerate() {
+                let above = hidden.len() - i - 1;
+                match card.rank {
+                    CardRank::ACE => {
+                        blocking += above;
+                        if above >= BURIED_ACE_DEPTH {
+                            buried_aces += 1;
+                        }
+                    },
+                    CardRank::TWO => blocking += above,
+                    _ => {}
+                }
+            }
+        }
+
+        let foundation_moves = self.moves_available().iter()
+            .any(|(origin, _, _)| matches!(origin, CardHolder::FOUNDATION(_)));
+
+        let points = blocking / 4 + buried_aces + if foundation_moves { 0 } else { 2 };
+        match points {
+            0..=2 => Difficulty::Easy,
+            3..=5 => Difficulty::Medium,
+            _ => Difficulty::Hard,
+        }
+    }
+
+    /// Whether sending the card home can't get in the way of solving the
+    /// game, given the cards already on the piles. It doesn't check the
+    /// card can be sent home now.
+    pub fn is_safe_autoplay(&self, card: Card) -> bool {
+        is_safe_home(card, &self.homes_by_suit())
+    }
+
+    fn homes_by_suit(&self) -> HashMap<CardSuit, Option<CardRank>> {
+        let mut result: HashMap<CardSuit, Option<CardRank>> =
+            CardSuit::iter().map(|suit| (suit, None)).collect();
+        for card in self.piles.iter().filter_map(|x| x.get_status().top_card) {
+            result.insert(card.suit, Some(card.rank));
+        }
+        result
+    }
+
+    /// Score of the game, computed as the scoring mode given on creation says
+    pub fn get_score(&self) -> i32 {
+        self.score.get(self.points(), now_millis())
+    }
+
+    /// Makes the score start again from the initial value, as if the game
+    /// had just started
+    pub fn reset_score(&mut self) {
+        let points = self.points();
+        let now = now_millis();
+        self.score.reset(points, now);
+        self.version += 1;
+        self.last_modified = LastModified(now);
+    }
+
+    /// Every card of the game: the ones on the deck, then the piles and
+    /// then the foundations
+    pub fn dealt_cards(&self) -> Vec<Card> {
+        let mut cards: Vec<Card> = self.deck.upcoming().copied().collect();
+        for pile in &self.piles {
+            cards.extend_from_slice(pile.cards());
+        }
+        for foundation in &self.foundations {
+            cards.extend_from_slice(foundation.hidden_cards());
+            cards.extend(foundation.get_status().visible);
+        }
+        cards
+    }
+
+    /// Number of cards still face down on the foundations
+    pub fn hidden_cards_remaining(&self) -> u32 {
+        self.foundations.iter().map(|x| x.hidden_count() as u32).sum()
+    }
+
+    /// Number of changes done to the game since it was created
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    /// Time of the last change done to the game, or of its creation if
+    /// none, in milliseconds since the epoch
+    pub fn get_last_modified(&self) -> u64 {
+        self.last_modified.0
+    }
+
+    /// The mover given on creation, moving the cards of the game
+    pub fn get_mover(&self) -> &T {
+        &self.mover
+    }
+
+    /// Continues the game with the given mover, keeping its cards and its
+    /// history. The actions recorded don't depend on the mover, so the
+    /// new one can undo those done by the previous one.
+    pub fn swap_mover<U: CardMover>(self, mover: U) -> KlondikeMockable<U> {
+        KlondikeMockable {
+            deck: self.deck,
+            piles: self.piles,
+            foundations: self.foundations,
+            mover,
+            history: self.history,
+            redo: self.redo,
+            max_history: self.max_history,
+            last_seq: self.last_seq,
+            log: self.log,
+            score: self.score,
+            faceup: self.faceup,
+            version: self.version,
+            last_modified: self.last_modified,
+            moves_cache: self.moves_cache,
+        }
+    }
+
+    pub fn get_scoring_mode(&self) -> ScoringMode {
+        self.score.mode()
+    }
+
+    /// Points earned with the moves done so far. In Vegas mode 5 for each
+    /// card on the piles. Otherwise 5 for each card turned visible, plus
+    /// the ones of each move not undone:
+    /// - From the waste to a foundation: 5
+    /// - From the waste or a foundation to a pile: 10
+    /// - From a pile to a foundation: -15
+    fn points(&self) -> i32 {
+        if self.score.mode() == ScoringMode::Vegas {
+            return 5 * self.piles.iter().map(|x| x.get_status().num_cards as i32).sum::<i32>();
+        }
+
+        let moves: i32 = self.history.iter().map(action_points).sum();
+        let flips: u32 = self.foundations.iter().map(|x| x.flips()).sum();
+
+        moves + 5 * flips as i32
+    }
+
+    /// Every holder accepting the top card of the origin, or any run of
+    /// visible cards on top of it. Unlike moves_available, every empty
+    /// holder is listed. Taken from the same moves kept until the game
+    /// changes.
+    pub fn legal_destinations(&self, origin: CardHolder) -> Vec<CardHolder> {
+        let legal = self.found_moves().legal;
+
+        self.card_holders().into_iter()
+            .filter(|destination| legal.iter().any(|(o, d, _n)| *o == origin && d == destination))
+            .collect()
+    }
+
+    /// Tells whether any move or flip can be done, and whether the stock
+    /// can still be cycled
+    pub fn stuck_analysis(&self) -> StuckReport {
+        let moves: Vec<KlondikeAction> = self.moves_available().into_iter()
+            .map(|(origin, destination, number)| KlondikeAction::MOVE(origin, destination, number))
+            .chain(self.foundations.iter().enumerate()
+                .filter(|(_i, x)| x.hidden_count() > 0 && x.get_status().visible.is_empty())
+                .map(|(i, _x)| KlondikeAction::FLIP(i as u32)))
+            .collect();
+
+        StuckReport {
+            available_moves: moves.len(),
+            can_cycle: self.deck.get_status().can_take,
+            sample_move: moves.into_iter().next(),
+        }
+    }
+
+    /// True when nothing can be moved nor flipped, and the stock can't be
+    /// cycled either
+    pub fn is_stuck(&self) -> bool {
+        let report = self.stuck_analysis();
+        report.available_moves == 0 && !report.can_cycle
+    }
+
+    /// Returns all the legal moves of the game as (origin, destination, number of cards).
+    /// Moves that just relocate a whole pile or foundation into an empty one are
+    /// left out, and when several empty piles or foundations could receive the 
+    /// same cards only the first one of them is listed.
+    /// The moves are kept until the game changes, so asking again for
+    /// them is cheap.
+    pub fn moves_available(&self) -> Vec<(CardHolder, CardHolder, u32)> {
+        self.found_moves().available
+    }
+
+    /// The moves found for this version of the game, found now if they
+    /// weren't yet
+    fn found_moves(&self) -> FoundMoves {
+        if let Some(moves) = self.moves_cache.get(self.version) {
+            return moves;
+        }
+        let moves = self.find_moves();
+        self.moves_cache.set(self.version, &moves);
+        moves
+    }
+
+    fn find_moves(&self) -> FoundMoves {
+        let mut legal = Vec::new();
+        let mut available = Vec::new();
+
+        for origin in self.card_holders() {
+            let card_origin = match self.get_card_origin(origin) {
+                Some(card_origin) => card_origin,
+                None => continue,
+            };
+
+            let mut number = 1;
+            while let Some(cards) = card_origin.try_peek(number) {
+                let mut empty_pile_listed = false;
+                let mut empty_foundation_listed = false;
+
+                for destination in self.card_holders() {
+                    let card_destination = match self.get_card_destination(destination) {
+                        Some(card_destination) => card_destination,
+                        None => continue,
+                    };
+
+                    if destination == origin || !card_destination.try_poke(&cards)
+                            || self.reverses_last_move(origin, destination, number as u32) {
+                        continue;
+                    }
+                    legal.push((origin, destination, number as u32));
+
+                    if self.count_cards(destination) == 0 {
+                        let listed = match destination {
+                            CardHolder::PILE(_) => &mut empty_pile_listed,
+                            _ => &mut empty_foundation_listed,
+                        };
+                        let whole_relocation = same_kind(origin, destination)
+                            && self.count_cards(origin) == number;
+
+                        if *listed || whole_relocation {
+                            continue;
+                        }
+                        *listed = true;
+                    }
+
+                    available.push((origin, destination, number as u32));
+                }
+                number += 1;
+            }
+        }
+
+        FoundMoves { legal, available }
+    }
+
+    /// All the card holders of the game: the deck, the piles and the foundations
+    fn card_holders(&self) -> Vec<CardHolder> {
+        let mut holders = vec![CardHolder::DECK];
+        holders.extend((0..self.piles.len()).map(|i| CardHolder::PILE(i as u32)));
+        holders.extend((0..self.foundations.len()).map(|i| CardHolder::FOUNDATION(i as u32)));
+        holders
+    }
+
+    fn get_card_origin(&self, holder: CardHolder) -> Option<&dyn CardOrigin> {
+        match holder {
+            CardHolder::DECK => Some(&*self.deck),
+            CardHolder::PILE(idx) => self.piles.get(idx as usize).map(|x| x as &dyn CardOrigin),
+            CardHolder::FOUNDATION(idx) => self.foundations.get(idx as usize).map(|x| x as &dyn CardOrigin),
+        }
+    }
+
+    fn get_card_destination(&self, holder: CardHolder) -> Option<&dyn CardDestination> {
+        match holder {
+            CardHolder::DECK => None,
+            CardHolder::PILE(idx) => self.piles.get(idx as usize).map(|x| x as &dyn CardDestination),
+            CardHolder::FOUNDATION(idx) => self.foundations.get(idx as usize).map(|x| x as &dyn CardDestination),
+        }
+    }
+
+    /// Number of cards in the card holder, including the hidden ones
+    fn count_cards(&self, holder: CardHolder) -> usize {
+        match holder {
+            CardHolder::DECK => {
+                let status = self.deck.get_status();
+                (status.cards_on_stock + status.cards_on_waste) as usize
+            }
+            CardHolder::PILE(idx) => self.piles.get(idx as usize)
+                .map_or(0, |x| x.get_status().num_cards as usize),
+            CardHolder::FOUNDATION(idx) => self.foundations.get(idx as usize)
+                .map_or(0, |x| {
+                    let status = x.get_status();
+                    status.num_hidden as usize + status.visible.len()
+                }),
+        }
+    }
+
+    /// True when every card has been sent to the piles
+    pub fn is_won(&self) -> bool {
+        self.count_cards(CardHolder::DECK) == 0
+            && (0..self.foundations.len()).all(|i| self.count_cards(CardHolder::FOUNDATION(i as u32)) == 0)
+    }
+
+    /// True when the game isn't won yet but only sending cards home is
+    /// left: the deck is empty and the foundations have no hidden cards,
+    /// their visible ones forming runs. The lowest card left is then always
+    /// on top of its foundation, so auto_moves with any policy but
+    /// AcesAndTwosOnly finishes it.
+    pub fn is_trivially_winnable(&self) -> bool {
+        !self.is_won()
+            && self.count_cards(CardHolder::DECK) == 0
+            && self.hidden_cards_remaining() == 0
+            && self.foundations.iter()
+                .all(|x| x.get_status().visible.is_empty() || x.movable_from(0).is_some())
+    }
+
+    /// Bytes identifying the position of the cards, whatever the actions
+    /// leading to it. The hidden cards of each foundation are only counted.
+    pub(crate) fn position_key(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.deck.write_position(&mut out);
+        for pile in &self.piles {
+            pile.write_bytes(&mut out);
+        }
+        for foundation in &self.foundations {
+            let status = foundation.get_status();
+            write_u32(&mut out, status.num_hidden);
+            write_cards(&mut out, &status.visible);
+        }
+        out
+    }
+
+    /// Fingerprint of the position of the cards, like position_key but
+    /// telling apart positions that only differ in a hidden card
+    pub fn state_hash(&self) -> u64 {
+        let mut key = self.position_key();
+        for foundation in &self.foundations {
+            write_cards(&mut key, foundation.hidden_cards());
+        }
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Move the top card of the given origin to the corresponding pile 
+    /// (the first empty one in case is an Ace). return true if success
+    pub fn to_pile(&mut self, origin: CardHolder) -> bool {
+        self.send_home(origin).is_some()
+    }
+
+    /// Move the top card of the given origin (the waste or a foundation) to 
+    /// the pile where it belongs. Returns the index of that pile if success.
+    pub fn send_home(&mut self, origin: CardHolder) -> Option<u32> {
+        for i in 0..self.piles.len() {
+            if self.move_cards(origin, CardHolder::PILE(i as u32), 1) {
+                return Some(i as u32);
+            }
+        }
+        None
+    }
+
+    /// Moves the top card of the waste home or, if it can't go there, to
+    /// the first foundation accepting it. Returns true if it was moved.
+    pub fn play_waste(&mut self) -> bool {
+        if self.to_pile(CardHolder::DECK) {
+            return true;
+        }
+        (0..self.foundations.len() as u32)
+            .any(|i| self.move_cards(CardHolder::DECK, CardHolder::FOUNDATION(i), 1))
+    }
+
+    /// Flips the foundations left with only hidden cards and sends home the
+    /// top cards of the waste and the foundations the policy allows, until
+    /// none is left. Each one is recorded as its own action. Returns the
+    /// number of actions done.
+    pub fn auto_moves(&mut self, policy: AutoPolicy) -> u32 {
+        let origins: Vec<CardHolder> = std::iter::once(CardHolder::DECK)
+            .chain((0..self.foundations.len() as u32).map(CardHolder::FOUNDATION))
+            .collect();
+        let mut done = 0;
+        loop {
+            let before = done;
+            for i in 0..self.foundations.len() as u32 {
+                if self.flip(i) {
+                    done += 1;
+                }
+            }
+            for origin in &origins {
+                let allowed = self.get_card_origin(*origin)
+                    .and_then(|x| x.try_peek(1))
+                    .is_some_and(|cards| policy.allows(cards[0], &self.homes_by_suit()));
+                if allowed && self.send_home(*origin).is_some() {
+                    done += 1;
+                }
+            }
+            if done == before {
+                return done;
+            }
+        }
+    }
+
+    /// Takes from the stock and then sends home the cards the policy
+    /// allows, like auto_moves, returning the number of actions done after
+    /// the take. Each of them is recorded, so they are undone one by one.
+    pub fn take_and_autoplay(&mut self, policy: AutoPolicy) -> u32 {
+        self.take();
+        self.auto_moves(policy)
+    }
+
+    /// Undoes actions until one changing the piles or the foundations is
+    /// undone, so the takes cycling the stock are skipped, or there is
+    /// nothing left to undo
+    pub fn undo_significant(&mut self) {
+        while let Some(action) = self.history.back() {
+            let significant = matches!(action,
+                KlondikeAction::MOVE(_, _, _) | KlondikeAction::MOVE_COLUMN(_, _, _)
+                | KlondikeAction::FLIP(_) | KlondikeAction::SHUFFLE_STOCK(_, _));
+            self.undo();
+            if significant {
+                break;
             }
         }
-        false
     }
 
     pub fn undo(&mut self) {
-        if let Some(action) = self.history.pop() {
+        if let Some(action) = self.history.pop_back() {
+            self.redo.push(action.clone());
             match action {
                 KlondikeAction::MOVE(origin, destination, number) => {
                     self.do_move_cards(origin, destination, number, true);
                 },
+                KlondikeAction::MOVE_COLUMN(origin, destination, number) => {
+                    self.do_move_cards(
+                        CardHolder::FOUNDATION(origin),
+                        CardHolder::FOUNDATION(destination),
+                        number, true
+                    );
+                },
                 KlondikeAction::TAKE => {
                     self.deck.undo_take();
+                },
+                KlondikeAction::FLIP(foundation) => {
+                    self.foundations[foundation as usize].undo_flip();
+                },
+                KlondikeAction::SHUFFLE_STOCK(before, _after) => {
+                    self.deck.reorder_stock(&before);
+                },
+            }
+            self.log_action(LogEntry::Undo);
+        }
+    }
+
+    /// The last actions applied, undos included, with their time in
+    /// milliseconds since the epoch. Unlike replay, undos don't shrink it,
+    /// but only the last MAX_LOG_ENTRIES are kept, and only since the
+    /// game was dealt or loaded.
+    pub fn action_log(&self) -> &VecDeque<(u64, LogEntry)> {
+        &self.log.0
+    }
+
+    /// Does again the last action undone, if no other was done since.
+    /// Returns true if there was one and it could be done.
+    pub fn redo(&mut self) -> bool {
+        let action = match self.redo.pop() {
+            Some(action) => action,
+            None => return false,
+        };
+        // Recording the action would forget the rest of the undone ones
+        let undone = std::mem::take(&mut self.redo);
+        let version = self.version;
+        self.apply_replay(&[action]);
+        self.redo = undone;
+        self.version != version
+    }
+
+    /// Whether there is an action undone that redo can do again
+    pub fn redo_available(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Adds the action to the history, dropping the oldest one if it
+    /// gets longer than max_history, and logs it. The actions undone
+    /// can't be redone after it.
+    fn record(&mut self, action: KlondikeAction) {
+        self.redo.clear();
+        self.history.push_back(action.clone());
+        if self.max_history.is_some_and(|max| self.history.len() > max) {
+            if let Some(dropped) = self.history.pop_front() {
+                if dropped == KlondikeAction::TAKE {
+                    self.deck.forget_oldest_take();
+                }
+                // The points of the dropped action are no longer counted
+                // from the history, so the score keeps them
+                if self.score.mode() != ScoringMode::Vegas {
+                    self.score.keep_points(action_points(&dropped));
                 }
             }
         }
+        self.log_action(LogEntry::Action(action));
     }
+
+    /// Keeps the action applied in the log, and counts it as a new version
+    fn log_action(&mut self, entry: LogEntry) {
+        let now = now_millis();
+        self.log.0.push_back((now, entry));
+        if self.log.0.len() > MAX_LOG_ENTRIES {
+            self.log.0.pop_front();
+        }
+        self.version += 1;
+        self.last_modified = LastModified(now);
+    }
+}
+
+/// Points earned by an action of the history, see points
+fn action_points(action: &KlondikeAction) -> i32 {
+    match action {
+        KlondikeAction::MOVE(origin, destination, _number) => match (origin, destination) {
+            (CardHolder::DECK, CardHolder::FOUNDATION(_)) => 5,
+            (_, CardHolder::PILE(_)) => 10,
+            (CardHolder::PILE(_), CardHolder::FOUNDATION(_)) => -15,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Milliseconds since the epoch
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_millis() as u64)
+}
+
+/// Checks the cards are a full deck: 52 cards with no repetitions
+fn check_full_deck(cards: &[Card]) -> Result<(), InvalidCardSet> {
+    if cards.len() != 52 {
+        return Err(InvalidCardSet::WrongNumberOfCards(cards.len()));
+    }
+
+    for i in 0..cards.len() {
+        if cards[..i].contains(&cards[i]) {
+            return Err(InvalidCardSet::RepeatedCard(cards[i]));
+        }
+    }
+
+    Ok(())
+}
+
+/// Distributes the cards: one to the first foundation, two to the
+/// second one... and the remaining ones to the deck.
+fn deal_cards(cards: &[Card], config: &KlondikeConfig) -> (Box<Deck>, Vec<Pile>, Vec<Foundation>) {
+    let mut card_idx = 0;
+
+    let mut piles: Vec<Pile> = Vec::new();
+    for _i in 0..config.piles {
+        piles.push(Pile::new());
+    }
+
+    let mut foundations: Vec<Foundation> = Vec::new();
+
+    for i in 1..=config.foundations {
+        foundations.push(Foundation::dealt(
+            cards[card_idx..card_idx + i].to_vec(), config.faceup, config.build_rule,
+            config.empty_column, config.auto_flip));
+        card_idx += i;
+    }
+
+    let deck = Deck::new_with_draw_count(&cards[card_idx..].to_vec(), config.draw_count);
+
+    (Box::new(deck), piles, foundations)
+}
+
+fn same_kind(first: CardHolder, second: CardHolder) -> bool {
+    matches!((first, second),
+        (CardHolder::DECK, CardHolder::DECK)
+        | (CardHolder::PILE(_), CardHolder::PILE(_))
+        | (CardHolder::FOUNDATION(_), CardHolder::FOUNDATION(_)))
 }
 
 fn extract_two_mutable_elements<T>(
@@ -251,11 +1801,31 @@ fn extract_two_mutable_elements<T>(
     panic!("indexes cannot be equal");
 }
 
+pub mod test_common {
+    use super::*;
+
+    /// Layout with the given cards, and the rest of the deck on the stock,
+    /// the last one of it being the next to take
+    pub fn layout_with(piles: Vec<Vec<Card>>, foundations: Vec<FoundationLayout>, waste: Vec<Card>) -> KlondikeLayout {
+        let placed: Vec<Card> = piles.iter().flatten()
+            .chain(waste.iter())
+            .chain(foundations.iter().flat_map(|x| x.hidden.iter().chain(x.visible.iter())))
+            .copied()
+            .collect();
+        KlondikeLayout {
+            stock: Card::full_deck().into_iter().filter(|x| !placed.contains(x)).collect(),
+            waste, piles, foundations,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use crate::card_game::card_containers::test_common::*;
+    use test_common::*;
+    use rand::rngs::mock::StepRng;
 
     #[test]
     #[should_panic]
@@ -269,6 +1839,9 @@ mod tests {
     #[test]
     fn klondike_new() {
         let mut klondike = Klondike::new();
+        // No card missing nor repeated
+        assert_eq!(check_full_deck(&klondike.dealt_cards()), Ok(()));
+
         // Four empty piles
         assert_eq!(klondike.piles.len(), 4);
         for pile in klondike.piles {
@@ -286,9 +1859,553 @@ mod tests {
 
         // The remaining cards are on the deck
         assert_eq!(get_deck_number_of_cards(&mut klondike.deck), 24); // 52 - 1 - 2 - 3 - 4 - 5 - 6 - 7
+    }
+
+    #[test]
+    fn klondike_dealt_cards() {
+        for _i in 0..20 {
+            let mut klondike = Klondike::new();
+            assert_eq!(check_full_deck(&klondike.dealt_cards()), Ok(()));
+
+            klondike.take_n(5);
+            klondike.send_home(CardHolder::DECK);
+            assert_eq!(check_full_deck(&klondike.dealt_cards()), Ok(()));
+        }
+
+        let config = KlondikeConfig { piles: 3, foundations: 9, faceup: 3, ..KlondikeConfig::default() };
+        let klondike = Klondike::new_with_config(config).unwrap();
+        assert_eq!(check_full_deck(&klondike.dealt_cards()), Ok(()));
+    }
+    #[test]
+    fn klondike_from_cards() {
+        let cards = Card::full_deck();
+
+        let klondike = Klondike::from_cards(cards.clone()).unwrap();
+        let status = klondike.get_status();
+
+        assert_eq!(status.piles.len(), 4);
+        assert_eq!(status.foundations.len(), 7);
+
+        // Cards are dealt in order: 1 to F1, 2 to F2 ... the rest to the deck
+        let mut card_idx = 0;
+        for i in 0..7 {
+            assert_eq!(status.foundations[i].num_hidden, i as u32);
+            assert_eq!(status.foundations[i].visible, vec![cards[card_idx + i]]);
+            card_idx += i + 1;
+        }
+
+        assert_eq!(status.deck.cards_on_stock, 23);
+        assert_eq!(status.deck.cards_on_waste, 1);
+        assert_eq!(status.deck.top_card_on_waste, Some(Card { suit: CardSuit::SPADES, rank: CardRank::KING }));
+    }
 
-        //TODO: check cards not repeated and randomized (if possible)
+    #[test]
+    fn klondike_new_with_seed() {
+        assert_eq!(Klondike::new_with_seed(42), Klondike::new_with_seed(42));
+        assert_ne!(Klondike::new_with_seed(42).get_status(), Klondike::new_with_seed(43).get_status());
     }
+
+    #[test]
+    fn klondike_new_with_rng() {
+        let klondike = Klondike::new_with_rng(&mut StepRng::new(0, 0));
+        assert_eq!(klondike, Klondike::new_with_rng(&mut StepRng::new(0, 0)));
+
+        // Always shuffling with the first card gives a fixed deal
+        let status = klondike.get_status();
+        let card = |suit, rank| Card { suit, rank };
+        assert_eq!(status.deck.top_card_on_waste, Some(card(CardSuit::CLUBS, CardRank::ACE)));
+        let tops: Vec<Card> = status.foundations.iter().map(|x| *x.visible.last().unwrap()).collect();
+        assert_eq!(tops, vec![
+            card(CardSuit::CLUBS, CardRank::TWO),
+            card(CardSuit::CLUBS, CardRank::FOUR),
+            card(CardSuit::CLUBS, CardRank::SEVEN),
+            card(CardSuit::CLUBS, CardRank::JACK),
+            card(CardSuit::DIAMONDS, CardRank::THREE),
+            card(CardSuit::DIAMONDS, CardRank::NINE),
+            card(CardSuit::HEARTS, CardRank::THREE),
+        ]);
+    }
+
+    #[test]
+    fn klondike_last_modified() {
+        let mut klondike = Klondike::new_with_seed(3);
+        let created = klondike.get_last_modified();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(klondike.get_status().last_modified, created);
+        assert!(!klondike.move_cards(CardHolder::PILE(0), CardHolder::PILE(1), 1));
+        assert_eq!(klondike.get_last_modified(), created);
+
+        klondike.take();
+        let taken = klondike.get_last_modified();
+        assert!(taken > created);
+        assert_eq!(klondike.get_status().last_modified, taken);
+        assert_eq!(Klondike::from_bytes(&klondike.to_bytes()).unwrap().get_last_modified(), taken);
+    }
+
+    #[test]
+    fn klondike_state_hash() {
+        let mut klondike = Klondike::new_with_seed(42);
+        let hash = klondike.state_hash();
+        assert_eq!(Klondike::new_with_seed(42).state_hash(), hash);
+
+        klondike.take();
+        assert_ne!(klondike.state_hash(), hash);
+        klondike.undo();
+        assert_eq!(klondike.state_hash(), hash);
+
+        // Swapping two hidden cards leaves the status as it was, but not
+        // the hash
+        let mut swapped = klondike.clone();
+        let mut hidden = swapped.foundations[6].hidden_cards().to_vec();
+        hidden.swap(0, 1);
+        let visible = swapped.foundations[6].get_status().visible;
+        swapped.foundations[6] = Foundation::from_cards(hidden, visible).unwrap();
+        assert_eq!(swapped.get_status(), klondike.get_status());
+        assert_ne!(swapped.state_hash(), hash);
+    }
+
+    #[test]
+    fn klondike_daily() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(daily_seed(date), 20240229);
+        assert_eq!(Klondike::daily(date), Klondike::daily(date));
+
+        let next = date.succ_opt().unwrap();
+        assert_ne!(Klondike::daily(date).get_status(), Klondike::daily(next).get_status());
+    }
+
+    #[test]
+    fn klondike_hidden_cards_remaining() {
+        let mut klondike = Klondike::new_with_seed(21);
+        // 0 + 1 + 2 + 3 + 4 + 5 + 6 on the seven foundations
+        assert_eq!(klondike.hidden_cards_remaining(), 21);
+
+        klondike.foundations[1] = Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]);
+        assert_eq!(klondike.hidden_cards_remaining(), 20);
+    }
+
+    #[test]
+    fn klondike_version() {
+        let mut klondike = Klondike::new_with_seed(8);
+        assert_eq!(klondike.get_version(), 0);
+
+        klondike.take();
+        assert_eq!(klondike.get_version(), 1);
+        assert!(!klondike.move_cards(CardHolder::PILE(0), CardHolder::PILE(1), 1));
+        assert_eq!(klondike.get_version(), 1);
+        klondike.undo();
+        assert_eq!(klondike.get_status().version, 2);
+
+        assert_eq!(Klondike::from_bytes(&klondike.to_bytes()).map(|x| x.get_version()), Ok(2));
+        klondike.redeal();
+        assert_eq!(klondike.get_version(), 3);
+    }
+
+    #[test]
+    fn klondike_new_with_config_faceup() {
+        let config = KlondikeConfig { faceup: 2, seed: Some(7), ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+
+        let visible = |x: &Klondike| -> Vec<usize> {
+            x.get_status().foundations.iter().map(|x| x.visible.len()).collect()
+        };
+        assert_eq!(visible(&klondike), vec![1, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(Klondike::from_bytes(&klondike.to_bytes()).map(|x| x.faceup), Ok(2));
+
+        klondike.redeal();
+        assert_eq!(visible(&klondike), vec![1, 2, 2, 2, 2, 2, 2]);
+
+        // All of them, even if more than fit in a byte
+        let config = KlondikeConfig { faceup: 1000, seed: Some(7), ..KlondikeConfig::default() };
+        let klondike = Klondike::new_with_config(config).unwrap();
+        assert_eq!(visible(&klondike), vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(klondike.faceup, 7);
+        assert_eq!(Klondike::from_bytes(&klondike.to_bytes()), Ok(klondike));
+    }
+
+    #[test]
+    fn klondike_faceup_moves_only_runs() {
+        let is_run = |cards: &[Card]| cards.windows(2).all(|x| BuildRule::AlternatingColors.can_build(x[0], x[1]));
+        let mut broken = 0;
+
+        for seed in 0..50 {
+            let config = KlondikeConfig { faceup: 7, seed: Some(seed), ..KlondikeConfig::default() };
+            let klondike = Klondike::new_with_config(config).unwrap();
+            let status = klondike.get_status();
+
+            for (origin, destination, number) in klondike.moves_available() {
+                if let CardHolder::FOUNDATION(idx) = origin {
+                    let visible = &status.foundations[idx as usize].visible;
+                    assert!(is_run(&visible[visible.len() - number as usize..]),
+                        "seed {}: {:?} -> {:?} ({})", seed, origin, destination, number);
+                }
+            }
+
+            // The two top cards of a column that aren't a run can't be moved together
+            for (idx, foundation) in status.foundations.iter().enumerate() {
+                let visible = &foundation.visible;
+                if visible.len() >= 2 && !is_run(&visible[visible.len() - 2..]) {
+                    broken += 1;
+                    for destination in klondike.card_holders() {
+                        let origin = CardHolder::FOUNDATION(idx as u32);
+                        assert!(!klondike.can_move(origin, destination, 2));
+                        assert!(!klondike.clone().move_cards(origin, destination, 2));
+                    }
+                }
+            }
+        }
+        assert!(broken > 0);
+    }
+
+    #[test]
+    fn klondike_new_with_config() {
+        let config = KlondikeConfig { piles: 3, foundations: 5, ..KlondikeConfig::default() };
+        let klondike = Klondike::new_with_config(config).unwrap();
+        let status = klondike.get_status();
+
+        assert_eq!(status.piles.len(), 3);
+        assert_eq!(status.foundations.len(), 5);
+        for i in 0..5 {
+            assert_eq!(status.foundations[i].num_hidden as usize + status.foundations[i].visible.len(), i + 1);
+        }
+
+        // 52 - 1 - 2 - 3 - 4 - 5
+        assert_eq!(status.deck.cards_on_stock + status.deck.cards_on_waste, 37);
+    }
+
+    #[test]
+    fn klondike_new_with_config_draw_three() {
+        let config = KlondikeConfig { draw_count: 3, seed: Some(42), ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+        let status = klondike.get_status();
+        assert_eq!(status.deck.cards_on_waste, 3);
+        assert_eq!(status.deck.cards_on_stock, 21);
+        assert_eq!(klondike, Klondike::new_with_config(config).unwrap());
+
+        klondike.take();
+        assert_eq!(klondike.get_status().deck.cards_on_waste, 6);
+        klondike.undo();
+        assert_eq!(klondike.get_status(), status);
+
+        klondike.redeal();
+        assert_eq!(klondike.get_status().deck.cards_on_waste, 3);
+
+        assert_eq!(
+            Klondike::new_with_config(KlondikeConfig { draw_count: 0, ..KlondikeConfig::default() }),
+            Err(InvalidConfig::InvalidDrawCount(0))
+        );
+    }
+
+    #[test]
+    fn klondike_new_with_config_not_enough_cards() {
+        assert!(Klondike::new_with_config(KlondikeConfig { foundations: 9, ..KlondikeConfig::default() }).is_ok());
+        assert_eq!(
+            Klondike::new_with_config(KlondikeConfig { foundations: 10, ..KlondikeConfig::default() }),
+            Err(InvalidConfig::NotEnoughCards { needed: 55, available: 52 })
+        );
+    }
+
+    #[test]
+    fn klondike_too_many_holders() {
+        let klondike = Klondike::new_with_config(
+            KlondikeConfig { piles: MAX_HOLDERS, seed: Some(1), ..KlondikeConfig::default() }).unwrap();
+        assert_eq!(Klondike::from_bytes(&klondike.to_bytes()), Ok(klondike));
+
+        assert_eq!(
+            Klondike::new_with_config(KlondikeConfig { piles: MAX_HOLDERS + 1, ..KlondikeConfig::default() }),
+            Err(InvalidConfig::TooManyHolders(MAX_HOLDERS + 1))
+        );
+        assert_eq!(
+            Klondike::new_with_deck(Vec::new(), KlondikeConfig { foundations: 300, ..KlondikeConfig::default() }),
+            Err(InvalidConfig::TooManyHolders(300))
+        );
+
+        let layout = layout_with(vec![Vec::new(); MAX_HOLDERS + 1], Vec::new(), Vec::new());
+        assert_eq!(Klondike::from_layout(&layout, 1), Err(InvalidCardSet::TooManyHolders(MAX_HOLDERS + 1)));
+    }
+
+    #[test]
+    fn klondike_new_with_deck() {
+        // A 40 card deck, without eights, nines nor tens
+        let cards: Vec<Card> = Card::full_deck().into_iter()
+            .filter(|x| ![CardRank::EIGHT, CardRank::NINE, CardRank::TEN].contains(&x.rank))
+            .collect();
+        let config = KlondikeConfig { piles: 4, foundations: 6, ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_deck(cards.clone(), config).unwrap();
+
+        let status = klondike.get_status();
+        assert_eq!(status.piles.len(), 4);
+        assert_eq!(status.foundations.len(), 6);
+        assert_eq!(status.foundations[5].visible, vec![cards[20]]);
+        assert_eq!(status.deck.cards_on_waste, 1);
+        assert_eq!(status.deck.cards_on_stock, 18);
+        assert_eq!(status.deck.top_card_on_waste, Some(cards[39]));
+
+        // Dealt again with the same cards
+        klondike.redeal();
+        let status = klondike.get_status();
+        assert_eq!(status.deck.cards_on_stock, 18);
+        assert!(status.foundations.iter().all(|x| x.visible.iter().all(|card| cards.contains(card))));
+
+        assert_eq!(
+            Klondike::new_with_deck(cards[..20].to_vec(), config),
+            Err(InvalidConfig::NotEnoughCards { needed: 21, available: 20 })
+        );
+    }
+
+    #[test]
+    fn klondike_from_invalid_cards() {
+        let mut cards = Card::full_deck();
+
+        assert_eq!(
+            Klondike::from_cards(cards[1..].to_vec()),
+            Err(InvalidCardSet::WrongNumberOfCards(51))
+        );
+
+        cards[51] = cards[0];
+        assert_eq!(
+            Klondike::from_cards(cards),
+            Err(InvalidCardSet::RepeatedCard(Card { suit: CardSuit::CLUBS, rank: CardRank::ACE }))
+        );
+    }
+
+    #[test]
+    fn klondike_from_layout() {
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let two = Card { suit: CardSuit::SPADES, rank: CardRank::TWO };
+        let five = Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE };
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let three = Card { suit: CardSuit::CLUBS, rank: CardRank::THREE };
+
+        let mut layout = layout_with(
+            vec![vec![ace, two], Vec::new()],
+            vec![FoundationLayout { hidden: vec![five], visible: vec![king] }],
+            vec![three],
+        );
+
+        let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+        let status = klondike.get_status();
+        assert_eq!(status.deck.cards_on_stock, 47);
+        assert_eq!(status.deck.top_card_on_waste, Some(three));
+        assert_eq!(status.piles[0], PileStatus { top_card: Some(two), num_cards: 2, suit: Some(two.suit) });
+        assert_eq!(status.piles[1].num_cards, 0);
+        assert_eq!(status.foundations, vec![FoundationStatus { num_hidden: 1, visible: vec![king] }]);
+
+        // The game goes on from there
+        klondike.take();
+        assert_eq!(klondike.get_status().deck.top_card_on_waste, layout.stock.last().copied());
+
+        layout.piles = vec![vec![two, ace]];
+        assert_eq!(Klondike::from_layout(&layout, 1), Err(InvalidCardSet::InvalidPile(0)));
+
+        layout.piles = vec![vec![ace, two]];
+        layout.foundations[0].visible = Vec::new();
+        layout.foundations.push(FoundationLayout { hidden: Vec::new(), visible: vec![king] });
+        assert_eq!(Klondike::from_layout(&layout, 1), Err(InvalidCardSet::InvalidFoundation(0)));
+
+        layout.foundations = Vec::new();
+        assert_eq!(Klondike::from_layout(&layout, 1), Err(InvalidCardSet::WrongNumberOfCards(50)));
+    }
+
+    #[test]
+    fn klondike_build_rule_from_config() {
+        let config = KlondikeConfig { build_rule: BuildRule::AnySuit, seed: Some(3), ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+        assert!(klondike.foundations.iter().all(|x| x.get_build_rule() == BuildRule::AnySuit));
+
+        let restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+        assert_eq!(restored, klondike);
+
+        klondike.redeal();
+        assert!(klondike.foundations.iter().all(|x| x.get_build_rule() == BuildRule::AnySuit));
+    }
+
+    #[test]
+    fn klondike_empty_column_rule_from_config() {
+        for rule in [EmptyColumnRule::KingsOnly, EmptyColumnRule::AnyCard].iter() {
+            let config = KlondikeConfig { empty_column: *rule, seed: Some(3), ..KlondikeConfig::default() };
+            let mut klondike = Klondike::new_with_config(config).unwrap();
+            assert!(klondike.foundations.iter().all(|x| x.get_empty_column_rule() == *rule));
+
+            // The first foundation only has a card
+            klondike.foundations[0].peek(1);
+            let waste = klondike.get_status().deck.top_card_on_waste.unwrap();
+            assert_ne!(waste.rank, CardRank::KING);
+            assert_eq!(
+                klondike.move_cards(CardHolder::DECK, CardHolder::FOUNDATION(0), 1),
+                *rule == EmptyColumnRule::AnyCard
+            );
+
+            let restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+            assert_eq!(restored, klondike);
+
+            klondike.redeal();
+            assert!(klondike.foundations.iter().all(|x| x.get_empty_column_rule() == *rule));
+        }
+    }
+
+    #[test]
+    fn klondike_flip_without_auto_flip() {
+        let config = KlondikeConfig { auto_flip: false, seed: Some(3), ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+        assert!(klondike.foundations.iter().all(|x| !x.get_auto_flip()));
+
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let hidden = Card { suit: CardSuit::HEARTS, rank: CardRank::TWO };
+        klondike.foundations[1] = Foundation::dealt(vec![hidden, ace], 1,
+            BuildRule::AlternatingColors, EmptyColumnRule::KingsOnly, false);
+        let status = klondike.get_status();
+
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(1), CardHolder::PILE(0), 1));
+        assert_eq!(klondike.get_status().foundations[1], FoundationStatus { num_hidden: 1, visible: Vec::new() });
+        assert!(!klondike.flip(0));
+        assert!(klondike.flip(1));
+        assert!(!klondike.flip(9));
+        assert_eq!(klondike.get_status().foundations[1], FoundationStatus { num_hidden: 0, visible: vec![hidden] });
+        assert_eq!(klondike.get_status().flips, 1);
+        assert_eq!(klondike.replay(), vec![
+            KlondikeAction::MOVE(CardHolder::FOUNDATION(1), CardHolder::PILE(0), 1),
+            KlondikeAction::FLIP(1),
+        ]);
+
+        let restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+        assert_eq!(restored, klondike);
+
+        klondike.undo();
+        assert_eq!(klondike.get_status().foundations[1], FoundationStatus { num_hidden: 1, visible: Vec::new() });
+        klondike.undo();
+        assert_eq!(klondike.get_status(), status);
+
+        klondike.redeal();
+        assert!(klondike.foundations.iter().all(|x| !x.get_auto_flip()));
+    }
+
+    #[test]
+    fn klondike_max_history() {
+        let config = KlondikeConfig { max_history: Some(3), seed: Some(3), ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+        let dealt = klondike.get_status();
+        assert!(!dealt.undo_available);
+
+        klondike.take_n(5);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::TAKE; 3]);
+        assert!(klondike.get_status().undo_available);
+        assert_ne!(klondike.get_status(), KlondikeStatus { undo_available: false, ..klondike.get_status() });
+
+        let restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+        assert_eq!(restored, klondike);
+
+        // The two oldest takes can't be undone
+        for _i in 0..5 {
+            klondike.undo();
+        }
+        assert!(!klondike.get_status().undo_available);
+        assert_eq!(klondike.get_status().deck.cards_on_waste, dealt.deck.cards_on_waste + 2);
+
+        klondike.redeal();
+        assert_eq!(klondike.max_history, Some(3));
+    }
+
+    #[test]
+    fn klondike_max_history_keeps_score() {
+        let ace = Card { suit: CardSuit::CLUBS, rank: CardRank::ACE };
+        let layout = layout_with(vec![Vec::new(); 4], Vec::new(), vec![ace]);
+        let mut klondike = Klondike { max_history: Some(2), ..Klondike::from_layout(&layout, 1).unwrap() };
+
+        assert!(klondike.move_cards(CardHolder::DECK, CardHolder::PILE(0), 1));
+        assert_eq!(klondike.get_score(), 10);
+        klondike.take_n(2);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::TAKE; 2]);
+        assert_eq!(klondike.get_score(), 10);
+    }
+
+    #[test]
+    fn klondike_flip_with_auto_flip() {
+        let mut klondike = Klondike::new_with_seed(3);
+        assert!(klondike.foundations.iter().all(|x| x.get_auto_flip()));
+
+        assert!(!klondike.flip(1));
+        assert!(klondike.replay().is_empty());
+        assert_eq!(klondike.get_version(), 0);
+    }
+
+    /// Score after moving a card from the waste to a foundation, and
+    /// another one from a foundation to a pile turning a card visible
+    fn score_after_moves(mode: ScoringMode, elapsed: u64) -> (i32, i32) {
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let two = Card { suit: CardSuit::SPADES, rank: CardRank::TWO };
+        let three = Card { suit: CardSuit::SPADES, rank: CardRank::THREE };
+        let queen = Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN };
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let five = Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE };
+        let seven = Card { suit: CardSuit::CLUBS, rank: CardRank::SEVEN };
+        let layout = layout_with(
+            vec![vec![ace, two], Vec::new()],
+            vec![
+                FoundationLayout { hidden: vec![five], visible: vec![king] },
+                FoundationLayout { hidden: vec![seven], visible: vec![three] },
+            ],
+            vec![queen],
+        );
+
+        let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+        klondike.score = Score::new(mode, 1_000);
+        let initial = klondike.score.get(klondike.points(), 1_000);
+
+        assert!(klondike.move_cards(CardHolder::DECK, CardHolder::FOUNDATION(0), 1));
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(1), CardHolder::PILE(0), 1));
+
+        (initial, klondike.score.get(klondike.points(), 1_000 + elapsed))
+    }
+
+    #[test]
+    fn klondike_score() {
+        assert_eq!(score_after_moves(ScoringMode::Standard, 0), (0, 20));
+        assert_eq!(score_after_moves(ScoringMode::Standard, 60_000), (0, 20));
+        assert_eq!(score_after_moves(ScoringMode::Timed, 0), (0, 20));
+        assert_eq!(score_after_moves(ScoringMode::Timed, 25_000), (0, 16));
+        assert_eq!(score_after_moves(ScoringMode::Timed, 600_000), (0, 0));
+        assert_eq!(score_after_moves(ScoringMode::Vegas, 0), (-42, -37));
+        assert_eq!(score_after_moves(ScoringMode::Vegas, 600_000), (-42, -37));
+    }
+
+    #[test]
+    fn klondike_score_undo_and_reset() {
+        let config = KlondikeConfig { scoring: ScoringMode::Vegas, ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+        assert_eq!(klondike.get_scoring_mode(), ScoringMode::Vegas);
+        assert_eq!(klondike.get_score(), -52);
+
+        klondike.piles[0].poke(&vec![Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }]);
+        assert_eq!(klondike.get_status().score, -47);
+
+        klondike.reset_score();
+        assert_eq!(klondike.get_score(), -52);
+
+        let mut klondike = Klondike::new_with_seed(5);
+        klondike.take();
+        while klondike.send_home(CardHolder::DECK).is_none() {
+            klondike.take();
+        }
+        assert_eq!(klondike.get_score(), 10);
+        klondike.undo();
+        assert_eq!(klondike.get_score(), 0);
+    }
+
+    #[test]
+    fn klondike_equality_includes_hidden_cards() {
+        let cards = Card::full_deck();
+        let klondike = Klondike::from_cards(cards.clone()).unwrap();
+        assert_eq!(Klondike::from_cards(cards.clone()).unwrap(), klondike);
+
+        // Cards 1 and 3 are both hidden on foundations 2 and 3
+        let mut swapped = cards;
+        swapped.swap(1, 3);
+        let other = Klondike::from_cards(swapped).unwrap();
+        assert_eq!(other.get_status(), klondike.get_status());
+        assert_ne!(other, klondike);
+    }
+
     fn get_card_origin_number_of_cards(origin: &mut dyn CardOrigin) -> u32 {
         let mut count = 0;
         while origin.peek(1).len() == 1 {
@@ -336,6 +2453,300 @@ mod tests {
         );
     }
 
+    #[test]
+    fn legal_destinations_ace() {
+        let ace = Card { suit: CardSuit::CLUBS, rank: CardRank::ACE };
+        let klondike = Klondike::from_layout(&layout_with(vec![Vec::new(); 4], Vec::new(), vec![ace]), 1).unwrap();
+
+        assert_eq!(klondike.legal_destinations(CardHolder::DECK), vec![
+            CardHolder::PILE(0), CardHolder::PILE(1), CardHolder::PILE(2), CardHolder::PILE(3),
+        ]);
+        assert!(klondike.legal_destinations(CardHolder::PILE(0)).is_empty());
+        assert!(klondike.legal_destinations(CardHolder::PILE(9)).is_empty());
+    }
+
+    #[test]
+    fn legal_destinations_mid_rank() {
+        let card = |suit, rank| Card { suit, rank };
+        let mut klondike = Klondike::new_with_seed(5);
+        klondike.foundations = vec![
+            Foundation::new(vec![card(CardSuit::HEARTS, CardRank::SEVEN)]),
+            Foundation::new(vec![card(CardSuit::SPADES, CardRank::EIGHT)]),
+            Foundation::new(vec![card(CardSuit::DIAMONDS, CardRank::EIGHT)]),
+            Foundation::new(vec![card(CardSuit::CLUBS, CardRank::EIGHT)]),
+            Foundation::from_cards(Vec::new(), vec![
+                card(CardSuit::CLUBS, CardRank::NINE), card(CardSuit::HEARTS, CardRank::EIGHT),
+            ]).unwrap(),
+            Foundation::new(vec![card(CardSuit::DIAMONDS, CardRank::TEN)]),
+            Foundation::new(vec![card(CardSuit::CLUBS, CardRank::TWO)]),
+        ];
+
+        assert_eq!(klondike.legal_destinations(CardHolder::FOUNDATION(0)),
+            vec![CardHolder::FOUNDATION(1), CardHolder::FOUNDATION(3)]);
+        // Only the whole run fits on the ten
+        assert_eq!(klondike.legal_destinations(CardHolder::FOUNDATION(4)),
+            vec![CardHolder::FOUNDATION(5)]);
+        assert!(klondike.legal_destinations(CardHolder::FOUNDATION(6)).is_empty());
+    }
+
+    #[test]
+    fn check_move_reasons() {
+        let mut klondike = Klondike::new_with_seed(5);
+        klondike.foundations[0] = Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]);
+        klondike.foundations[1] = Foundation::new(vec![Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN }]);
+        let f1 = CardHolder::FOUNDATION(0);
+        let f2 = CardHolder::FOUNDATION(1);
+
+        assert_eq!(klondike.check_move(f2, f1, 1), Ok(()));
+        assert_eq!(klondike.check_move(f1, f1, 1), Err(MoveError::SameOriginAndDestination));
+        assert_eq!(klondike.check_move(CardHolder::PILE(9), f1, 1), Err(MoveError::InvalidOrigin(CardHolder::PILE(9))));
+        assert_eq!(klondike.check_move(f1, CardHolder::DECK, 1), Err(MoveError::DestinationIsDeck));
+        assert_eq!(klondike.check_move(f2, f1, 2), Err(MoveError::CannotTake(2)));
+        for origin in [f2, CardHolder::PILE(0), CardHolder::DECK] {
+            assert_eq!(klondike.check_move(origin, f1, u32::MAX), Err(MoveError::CannotTake(u32::MAX)));
+            assert!(!klondike.clone().move_cards(origin, f1, u32::MAX));
+        }
+        assert_eq!(klondike.check_move(f1, f2, 1), Err(MoveError::CannotPlace));
+        assert_eq!(MoveError::DestinationIsDeck.to_string(), "Cards can't be placed on the deck");
+        assert_eq!(MoveError::InvalidDestination(CardHolder::PILE(9)).to_string(), "Cards can't be placed on pile 10");
+    }
+
+    #[test]
+    fn move_to_deck_is_rejected() {
+        let mut klondike = Klondike::new_with_seed(5);
+        klondike.piles[0] = Pile::from_cards(&[Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }]).unwrap();
+        let status = klondike.get_status();
+
+        let mut origins = klondike.card_holders();
+        origins.push(CardHolder::PILE(9));
+        origins.push(CardHolder::FOUNDATION(9));
+        for origin in origins {
+            assert_eq!(klondike.check_move(origin, CardHolder::DECK, 1), Err(MoveError::DestinationIsDeck),
+                "{:?}", origin);
+            assert!(!klondike.can_move(origin, CardHolder::DECK, 1));
+            assert!(!klondike.move_cards(origin, CardHolder::DECK, 1));
+        }
+        assert_eq!(klondike.get_status(), status);
+        assert!(klondike.replay().is_empty());
+    }
+
+    #[test]
+    fn move_several_from_deck_is_rejected() {
+        let card = |suit, rank| Card { suit, rank };
+        let waste = vec![
+            card(CardSuit::CLUBS, CardRank::QUEEN),
+            card(CardSuit::DIAMONDS, CardRank::KING),
+            card(CardSuit::HEARTS, CardRank::KING),
+        ];
+        let mut klondike = Klondike::new_with_seed(5);
+        *klondike.deck = Deck::from_cards(Vec::new(), waste, 3);
+        klondike.foundations[0] = Foundation::from_cards(Vec::new(), Vec::new()).unwrap();
+        let destination = CardHolder::FOUNDATION(0);
+
+        for number in [0, 2, 3] {
+            assert_eq!(klondike.check_move(CardHolder::DECK, destination, number), Err(MoveError::CannotTake(number)));
+            assert!(!klondike.move_cards(CardHolder::DECK, destination, number));
+        }
+        assert!(klondike.replay().is_empty());
+
+        assert_eq!(klondike.check_move(CardHolder::DECK, destination, 1), Ok(()));
+        assert!(klondike.move_cards(CardHolder::DECK, destination, 1));
+        assert_eq!(klondike.get_status().foundations[0].visible, vec![card(CardSuit::HEARTS, CardRank::KING)]);
+    }
+
+    #[test]
+    fn can_move_agrees_with_move_cards() {
+        for seed in 0..4 {
+            let mut klondike = Klondike::new_with_seed(seed);
+            let mut holders = klondike.card_holders();
+            holders.push(CardHolder::PILE(4));
+            holders.push(CardHolder::FOUNDATION(7));
+
+            for step in 0..25 {
+                for origin in holders.iter() {
+                    for destination in holders.iter() {
+                        for number in 0..14 {
+                            let expected = klondike.clone().move_cards(*origin, *destination, number);
+                            assert_eq!(
+                                klondike.can_move(*origin, *destination, number),
+                                expected,
+                                "{:?} -> {:?} ({})", origin, destination, number
+                            );
+                        }
+                    }
+
+                    let destinations: Vec<CardHolder> = klondike.card_holders().into_iter()
+                        .filter(|x| (1..14).any(|number| klondike.can_move(*origin, *x, number)))
+                        .collect();
+                    assert_eq!(klondike.legal_destinations(*origin), destinations, "{:?}", origin);
+                }
+
+                let moves = klondike.moves_available();
+                if moves.is_empty() || step % 3 == 0 {
+                    klondike.take();
+                } else {
+                    let (origin, destination, number) = moves[step % moves.len()];
+                    assert!(klondike.move_cards(origin, destination, number));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn action_log_keeps_undos() {
+        let mut klondike = Klondike::new_with_seed(1);
+        klondike.take();
+        klondike.take();
+        assert_eq!(klondike.action_log().len(), 2);
+
+        klondike.undo();
+        assert_eq!(klondike.replay(), vec![KlondikeAction::TAKE]);
+        assert_eq!(
+            klondike.action_log().iter().map(|x| x.1.clone()).collect::<Vec<LogEntry>>(),
+            vec![
+                LogEntry::Action(KlondikeAction::TAKE),
+                LogEntry::Action(KlondikeAction::TAKE),
+                LogEntry::Undo,
+            ]
+        );
+        let times: Vec<u64> = klondike.action_log().iter().map(|x| x.0).collect();
+        assert!(times.windows(2).all(|x| x[0] <= x[1]));
+
+        // Failed moves and undos with nothing to undo are not logged
+        klondike.undo();
+        klondike.undo();
+        klondike.move_cards(CardHolder::PILE(0), CardHolder::PILE(1), 1);
+        assert_eq!(klondike.action_log().len(), 4);
+
+        // Neither a game loaded nor a new deal keep it
+        assert!(Klondike::from_bytes(&klondike.to_bytes()).unwrap().action_log().is_empty());
+        klondike.redeal();
+        assert!(klondike.action_log().is_empty());
+    }
+
+    #[test]
+    fn action_log_keeps_the_last_entries() {
+        let mut klondike = Klondike::new_with_seed(1);
+        for _i in 0..MAX_LOG_ENTRIES {
+            klondike.take();
+        }
+        klondike.undo();
+
+        assert_eq!(klondike.action_log().len(), MAX_LOG_ENTRIES);
+        assert_eq!(klondike.action_log().back().map(|x| x.1.clone()), Some(LogEntry::Undo));
+    }
+
+    fn klondike_with_foundations(foundations: Vec<Foundation>) -> Klondike {
+        KlondikeMockable {
+            deck: Box::new(Deck::new(&vec![Card { suit: CardSuit::CLUBS, rank: CardRank::ACE }])),
+            piles: vec![Pile::new()],
+            foundations,
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
+        }
+    }
+
+    #[test]
+    fn move_column_full_run() {
+        let mut origin = Foundation::new(vec![
+            Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE },
+            Card { suit: CardSuit::HEARTS, rank: CardRank::QUEEN },
+        ]);
+        origin.poke(&vec![Card { suit: CardSuit::CLUBS, rank: CardRank::JACK }]);
+        let mut klondike = klondike_with_foundations(vec![
+            origin,
+            Foundation::new(vec![Card { suit: CardSuit::SPADES, rank: CardRank::KING }]),
+        ]);
+        let initial = klondike.clone();
+
+        assert!(klondike.move_column(0, 1));
+        let status = klondike.get_status();
+        assert_eq!(status.foundations[0].visible, vec![Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE }]);
+        assert_eq!(status.foundations[1].visible.len(), 3);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::MOVE_COLUMN(0, 1, 2)]);
+
+        // Nothing left that fits
+        assert!(!klondike.move_column(0, 1));
+        assert!(!klondike.move_column(0, 2));
+
+        klondike.undo();
+        assert_eq!(klondike.get_status(), initial.get_status());
+        assert!(klondike.replay().is_empty());
+    }
+
+    #[test]
+    fn move_column_partial_run() {
+        let mut origin = Foundation::new(vec![
+            Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE },
+            Card { suit: CardSuit::SPADES, rank: CardRank::KING },
+        ]);
+        origin.poke(&vec![
+            Card { suit: CardSuit::HEARTS, rank: CardRank::QUEEN },
+            Card { suit: CardSuit::CLUBS, rank: CardRank::JACK },
+        ]);
+        let mut klondike = klondike_with_foundations(vec![
+            origin,
+            Foundation::new(vec![Card { suit: CardSuit::CLUBS, rank: CardRank::KING }]),
+        ]);
+        let initial = klondike.clone();
+
+        assert!(klondike.move_column(0, 1));
+        let status = klondike.get_status();
+        assert_eq!(status.foundations[0].visible, vec![Card { suit: CardSuit::SPADES, rank: CardRank::KING }]);
+        assert_eq!(status.foundations[1].visible, vec![
+            Card { suit: CardSuit::CLUBS, rank: CardRank::KING },
+            Card { suit: CardSuit::HEARTS, rank: CardRank::QUEEN },
+            Card { suit: CardSuit::CLUBS, rank: CardRank::JACK },
+        ]);
+
+        let mut restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+        restored.undo();
+        klondike.undo();
+        assert_eq!(klondike.get_status(), initial.get_status());
+        assert_eq!(restored.get_status(), initial.get_status());
+    }
+
+    #[test]
+    fn move_from_card_moves_the_cards_on_top() {
+        let mut origin = Foundation::new(vec![
+            Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE },
+            Card { suit: CardSuit::SPADES, rank: CardRank::KING },
+        ]);
+        origin.poke(&vec![
+            Card { suit: CardSuit::HEARTS, rank: CardRank::QUEEN },
+            Card { suit: CardSuit::CLUBS, rank: CardRank::JACK },
+            Card { suit: CardSuit::DIAMONDS, rank: CardRank::TEN },
+        ]);
+        let mut klondike = klondike_with_foundations(vec![
+            origin,
+            Foundation::new(vec![Card { suit: CardSuit::CLUBS, rank: CardRank::KING }]),
+        ]);
+        let origin = CardHolder::FOUNDATION(0);
+        let destination = CardHolder::FOUNDATION(1);
+
+        // Not visible, not on the origin, or not accepted by the destination
+        assert!(!klondike.move_from_card(origin, Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE }, destination));
+        assert!(!klondike.move_from_card(origin, Card { suit: CardSuit::CLUBS, rank: CardRank::KING }, destination));
+        assert!(!klondike.move_from_card(origin, Card { suit: CardSuit::CLUBS, rank: CardRank::JACK }, destination));
+        assert!(!klondike.move_from_card(CardHolder::FOUNDATION(5), Card { suit: CardSuit::CLUBS, rank: CardRank::JACK }, destination));
+        assert!(klondike.replay().is_empty());
+
+        assert!(klondike.move_from_card(origin, Card { suit: CardSuit::HEARTS, rank: CardRank::QUEEN }, destination));
+        let status = klondike.get_status();
+        assert_eq!(status.foundations[0].visible, vec![Card { suit: CardSuit::SPADES, rank: CardRank::KING }]);
+        assert_eq!(status.foundations[1].visible.len(), 4);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::MOVE(origin, destination, 3)]);
+    }
+
     #[test]
     fn test_extract_two_mutables() {
         test_extract_two_mutables_case(0, 1);
@@ -365,6 +2776,22 @@ mod tests {
         assert_eq!(*b, second);
     }
 
+    #[test]
+    fn move_cards_missing_holders() {
+        let mut klondike = Klondike::new();
+        let status = klondike.get_status();
+
+        assert!(!klondike.move_cards(CardHolder::DECK, CardHolder::PILE(4), 1));
+        assert!(!klondike.move_cards(CardHolder::DECK, CardHolder::FOUNDATION(7), 1));
+        assert!(!klondike.move_cards(CardHolder::FOUNDATION(9), CardHolder::PILE(0), 1));
+        assert!(!klondike.move_cards(CardHolder::PILE(4), CardHolder::FOUNDATION(0), 1));
+        assert!(!klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::FOUNDATION(7), 1));
+        assert!(!klondike.move_cards(CardHolder::PILE(7), CardHolder::PILE(0), 1));
+        assert!(!klondike.to_pile(CardHolder::FOUNDATION(7)));
+
+        assert_eq!(klondike.get_status(), status);
+    }
+
     #[test]
     fn klondike_card_movements() {
         let (piles, foundations, deck) = prepare_card_movement_test();
@@ -486,7 +2913,16 @@ mod tests {
             piles,
             deck,
             mover: TestCardMover::new(number as usize, result, origin_str, destination_str),
-            history: Vec::new(),
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
         };
 
         let res = klondike.move_cards(origin, destination, number);
@@ -603,7 +3039,16 @@ mod tests {
             piles,
             deck,
             mover: TestPileCardMover::new(origin_str, destination_str, result),
-            history: Vec::new()
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
         };
 
         let res = klondike.to_pile(origin);
@@ -671,6 +3116,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn klondike_status_homes_by_suit() {
+        let mut klondike = Klondike::new_with_seed(3);
+        let hearts: Vec<Card> = [CardRank::ACE, CardRank::TWO, CardRank::THREE]
+            .iter().map(|rank| Card { suit: CardSuit::HEARTS, rank: *rank }).collect();
+        // Hearts on the last pile, to check the pile doesn't matter
+        klondike.piles[3] = Pile::from_cards(&hearts).unwrap();
+        klondike.piles[1] = Pile::from_cards(&[Card { suit: CardSuit::CLUBS, rank: CardRank::ACE }]).unwrap();
+
+        let homes = klondike.get_status().homes_by_suit;
+        assert_eq!(homes.len(), 4);
+        assert_eq!(homes[&CardSuit::HEARTS], Some(CardRank::THREE));
+        assert_eq!(homes[&CardSuit::CLUBS], Some(CardRank::ACE));
+        assert_eq!(homes[&CardSuit::SPADES], None);
+        assert_eq!(homes[&CardSuit::DIAMONDS], None);
+    }
+
+    /// Layout with four foundations, the hidden cards of each one taking
+    /// the given ranks first, and the remaining cards on the stock.
+    fn layout_with_hidden(hidden: [&[CardRank]; 4], visible: [Card; 4]) -> KlondikeLayout {
+        let suits = [CardSuit::CLUBS, CardSuit::DIAMONDS, CardSuit::HEARTS, CardSuit::SPADES];
+        let mut stock = layout_with(Vec::new(), Vec::new(), visible.to_vec()).stock;
+
+        let mut foundations = Vec::new();
+        for i in 0..4 {
+            let mut cards: Vec<Card> = hidden[i].iter()
+                .map(|rank| Card { suit: suits[i], rank: *rank })
+                .collect();
+            stock.retain(|x| !cards.contains(x));
+            // Fill up to six hidden cards with other ones from the stock
+            while cards.len() < 6 {
+                let card = stock.iter().rev()
+                    .find(|x| x.rank != CardRank::ACE && x.rank != CardRank::TWO)
+                    .copied().unwrap();
+                stock.retain(|x| *x != card);
+                cards.push(card);
+            }
+            foundations.push(FoundationLayout { hidden: cards, visible: vec![visible[i]] });
+        }
+
+        layout_with(vec![Vec::new(); 4], foundations, Vec::new())
+    }
+
+    #[test]
+    fn klondike_estimate_difficulty() {
+        let card = |suit, rank| Card { suit, rank };
+
+        // Aces and twos on the stock, and the queen can go on the king
+        let layout = layout_with_hidden([&[], &[], &[], &[]], [
+            card(CardSuit::HEARTS, CardRank::KING), card(CardSuit::SPADES, CardRank::QUEEN),
+            card(CardSuit::CLUBS, CardRank::FIVE), card(CardSuit::CLUBS, CardRank::NINE),
+        ]);
+        let klondike = Klondike::from_layout(&layout, 1).unwrap();
+        assert_eq!(klondike.estimate_difficulty(), Difficulty::Easy);
+        assert_eq!(klondike.get_status().difficulty, Some(Difficulty::Easy));
+
+        // Two aces at the bottom of their foundations
+        let layout = layout_with_hidden([&[CardRank::ACE], &[CardRank::ACE], &[], &[]], [
+            card(CardSuit::HEARTS, CardRank::KING), card(CardSuit::SPADES, CardRank::QUEEN),
+            card(CardSuit::CLUBS, CardRank::FIVE), card(CardSuit::CLUBS, CardRank::NINE),
+        ]);
+        let klondike = Klondike::from_layout(&layout, 1).unwrap();
+        assert_eq!(klondike.estimate_difficulty(), Difficulty::Medium);
+
+        // Every ace and two at the bottom, and nothing to move
+        let low = [CardRank::ACE, CardRank::TWO];
+        let layout = layout_with_hidden([&low, &low, &low, &low], [
+            card(CardSuit::CLUBS, CardRank::FIVE), card(CardSuit::SPADES, CardRank::NINE),
+            card(CardSuit::CLUBS, CardRank::JACK), card(CardSuit::SPADES, CardRank::THREE),
+        ]);
+        let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+        assert_eq!(klondike.estimate_difficulty(), Difficulty::Hard);
+
+        // Only given for the deal
+        klondike.take();
+        assert_eq!(klondike.get_status().difficulty, None);
+        klondike.undo();
+        assert_eq!(klondike.get_status().difficulty, Some(Difficulty::Hard));
+    }
+
     use rand::distributions::{Distribution, Uniform};
     use mockall::*;
     use mockall::predicate::*;
@@ -786,7 +3311,16 @@ mod tests {
             piles,
             foundations,
             mover,
-            history: Vec::new(),
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
         };
 
         movements.reverse();
@@ -808,6 +3342,287 @@ mod tests {
         }
     }
 
+    #[test]
+    fn klondike_status_diff() {
+        let mut klondike = Klondike::new_with_seed(11);
+        let mut moves = klondike.moves_available();
+        while !moves.iter().any(|x| x.0 != CardHolder::DECK) {
+            klondike.take();
+            moves = klondike.moves_available();
+        }
+        let (origin, destination, number) = *moves.iter().find(|x| x.0 != CardHolder::DECK).unwrap();
+
+        let prev = klondike.get_status();
+        assert!(klondike.move_cards(origin, destination, number));
+        let status = klondike.get_status();
+        let diff = status.diff(&prev);
+
+        assert_eq!(diff.since_version, Some(prev.version));
+        assert_eq!(diff.version, status.version);
+        assert_eq!(diff.deck, None);
+        let mut holders: Vec<CardHolder> = diff.piles.keys().map(|i| CardHolder::PILE(*i as u32))
+            .chain(diff.foundations.keys().map(|i| CardHolder::FOUNDATION(*i as u32)))
+            .collect();
+        holders.sort_by_key(|x| format!("{:?}", x));
+        let mut expected = vec![origin, destination];
+        expected.sort_by_key(|x| format!("{:?}", x));
+        assert_eq!(holders, expected);
+
+        let full = status.as_diff();
+        assert_eq!(full.since_version, None);
+        assert_eq!(full.deck, Some(status.deck));
+        assert_eq!(full.foundations.len(), 7);
+        assert_eq!(status.diff(&status).piles.len(), 0);
+    }
+
+    #[test]
+    fn klondike_status_summary() {
+        let summary = Klondike::new().get_status().summary();
+        assert_eq!(summary, "D(w1/s23) P:0,0,0,0 F:(h0,v1)(h1,v1)(h2,v1)(h3,v1)(h4,v1)(h5,v1)(h6,v1)");
+        assert!(summary.is_ascii());
+    }
+
+    #[test]
+    fn klondike_logging_card_mover() {
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let queen = Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN };
+        let mut moved = Vec::new();
+        {
+            let mover = LoggingCardMover::new(|cards: &[Card]| moved.push(cards.to_vec()));
+            let mut klondike = KlondikeMockable::new_with_mover(mover);
+            klondike.foundations[0] = Foundation::new(vec![king]);
+            klondike.foundations[1] = Foundation::new(vec![queen]);
+            klondike.piles[0] = Pile::new();
+
+            assert!(klondike.move_cards(CardHolder::FOUNDATION(1), CardHolder::FOUNDATION(0), 1));
+            assert!(!klondike.move_cards(CardHolder::PILE(0), CardHolder::PILE(1), 1));
+            assert!(klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::FOUNDATION(1), 2));
+            klondike.undo();
+        }
+
+        assert_eq!(moved, vec![vec![queen], vec![king, queen]]);
+    }
+
+    #[test]
+    #[cfg(feature = "deterministic-deal")]
+    fn klondike_new_deterministic() {
+        assert_eq!(Klondike::new().dealt_cards(), Klondike::new().dealt_cards());
+        assert_eq!(Klondike::new().dealt_cards(), Klondike::new_with_seed(DETERMINISTIC_DEAL_SEED).dealt_cards());
+    }
+
+    #[test]
+    fn klondike_counting_card_mover() {
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let queen = Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN };
+        let mut klondike = KlondikeMockable::new_with_mover(CountingCardMover::new(SimpleCardMover {}));
+        klondike.foundations[0] = Foundation::new(vec![king]);
+        klondike.foundations[1] = Foundation::new(vec![queen]);
+
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(1), CardHolder::FOUNDATION(0), 1));
+        assert!(!klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::FOUNDATION(1), 3));
+        assert!(!klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::PILE(0), 2));
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::FOUNDATION(1), 2));
+        klondike.undo();
+        klondike.undo();
+
+        let mover = klondike.get_mover();
+        assert_eq!(mover.attempts(), 4);
+        assert_eq!(mover.successes(), 2);
+        assert_eq!(mover.undos(), 2);
+    }
+
+    #[test]
+    fn klondike_swap_mover() {
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let queen = Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN };
+        let jack = Card { suit: CardSuit::DIAMONDS, rank: CardRank::JACK };
+        let mut klondike = Klondike::new();
+        klondike.foundations[0] = Foundation::new(vec![king]);
+        klondike.foundations[1] = Foundation::new(vec![queen]);
+        klondike.foundations[2] = Foundation::new(vec![jack]);
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(1), CardHolder::FOUNDATION(0), 1));
+        let before = klondike.get_status();
+
+        let mut klondike = klondike.swap_mover(CountingCardMover::new(SimpleCardMover {}));
+        assert_eq!(klondike.get_status(), before);
+        assert_eq!(klondike.replay().len(), 1);
+
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(2), CardHolder::FOUNDATION(0), 1));
+        assert_eq!(klondike.get_status().foundations[0].visible, vec![king, queen, jack]);
+
+        // Undoing the moves of both movers
+        klondike.undo();
+        klondike.undo();
+        assert_eq!(klondike.get_status().foundations[0].visible, vec![king]);
+        assert_eq!(klondike.get_status().foundations[1].visible, vec![queen]);
+        let mover = klondike.get_mover();
+        assert_eq!(mover.attempts(), 1);
+        assert_eq!(mover.successes(), 1);
+        assert_eq!(mover.undos(), 2);
+    }
+
+    #[test]
+    fn klondike_no_repeat_mover() {
+        let mut klondike = KlondikeMockable::new_with_mover(NoRepeatMover::new(SimpleCardMover {}));
+        klondike.foundations[0] = Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]);
+        klondike.foundations[1] = Foundation::new(vec![Card { suit: CardSuit::DIAMONDS, rank: CardRank::KING }]);
+        klondike.foundations[2] = Foundation::new(vec![Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN }]);
+        let f1 = CardHolder::FOUNDATION(0);
+        let f2 = CardHolder::FOUNDATION(1);
+
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(2), f1, 1));
+        // Moving the queen on is fine, moving it straight back isn't
+        assert!(klondike.move_cards(f1, f2, 1));
+        assert_eq!(klondike.check_move(f2, f1, 1), Err(MoveError::ReversesLastMove));
+        assert!(!klondike.legal_destinations(f2).contains(&f1));
+        assert!(!klondike.moves_available().contains(&(f2, f1, 1)));
+        assert!(!klondike.move_cards(f2, f1, 1));
+        assert_eq!(klondike.replay().len(), 2);
+
+        // Nor is it after being cloned
+        let mut cloned = klondike.clone();
+        assert!(!cloned.move_cards(f2, f1, 1));
+        assert!(!cloned.move_column(1, 0));
+
+        // Undoing leaves nothing to repeat
+        klondike.undo();
+        assert!(klondike.move_cards(f1, f2, 1));
+        klondike.undo();
+        assert_eq!(klondike.get_status().foundations[0].visible.len(), 2);
+    }
+
+    #[test]
+    fn klondike_counting_no_repeat_mover() {
+        let mover = CountingCardMover::new(NoRepeatMover::new(SimpleCardMover {}));
+        let mut klondike = KlondikeMockable::new_with_mover(mover);
+        klondike.foundations[0] = Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]);
+        klondike.foundations[1] = Foundation::new(vec![Card { suit: CardSuit::DIAMONDS, rank: CardRank::KING }]);
+        klondike.foundations[2] = Foundation::new(vec![Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN }]);
+        let f1 = CardHolder::FOUNDATION(0);
+        let f2 = CardHolder::FOUNDATION(1);
+
+        // Counting the moves still rejects moving the queen straight back
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(2), f1, 1));
+        assert!(klondike.move_cards(f1, f2, 1));
+        assert_eq!(klondike.check_move(f2, f1, 1), Err(MoveError::ReversesLastMove));
+        assert!(!klondike.move_cards(f2, f1, 1));
+        assert_eq!(klondike.replay().len(), 2);
+    }
+
+    #[test]
+    fn klondike_shuffle_stock() {
+        let mut klondike = Klondike::new_with_seed(12);
+        klondike.take();
+        let status = klondike.get_status();
+        let stock = klondike.deck.stock_snapshot().to_vec();
+
+        klondike.shuffle_stock();
+        assert_eq!(klondike.get_status().deck, status.deck);
+        let shuffled = klondike.deck.stock_snapshot().to_vec();
+        assert_ne!(shuffled, stock);
+        assert_eq!(klondike.replay().last(),
+            Some(&KlondikeAction::SHUFFLE_STOCK(stock.clone(), shuffled.clone())));
+
+        // Replaying and restoring keep the shuffled order
+        let mut replayed = Klondike::new_with_seed(12);
+        replayed.apply_replay(&klondike.replay());
+        assert_eq!(replayed.deck.stock_snapshot(), &shuffled[..]);
+        let mut restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+        assert_eq!(restored.deck.stock_snapshot(), &shuffled[..]);
+
+        klondike.undo();
+        restored.undo();
+        assert_eq!(klondike.deck.stock_snapshot(), &stock[..]);
+        assert_eq!(restored.deck.stock_snapshot(), &stock[..]);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::TAKE]);
+    }
+
+    /// Layout where nothing can be done: the hearts up to the nine and the
+    /// other suits on the piles, the ten of hearts hidden below the jack,
+    /// and the queen and king of hearts on their own foundations
+    fn dead_layout() -> KlondikeLayout {
+        let card = |rank| Card { suit: CardSuit::HEARTS, rank };
+        layout_with(
+            CardSuit::iter()
+                .map(|suit| Card::full_deck().into_iter()
+                    .filter(|x| x.suit == suit && (suit != CardSuit::HEARTS || x.rank.to_u8() < 10))
+                    .collect())
+                .collect(),
+            vec![
+                FoundationLayout { hidden: vec![card(CardRank::TEN)], visible: vec![card(CardRank::JACK)] },
+                FoundationLayout { hidden: Vec::new(), visible: vec![card(CardRank::QUEEN)] },
+                FoundationLayout { hidden: Vec::new(), visible: vec![card(CardRank::KING)] },
+            ],
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn klondike_stuck_analysis_dead() {
+        let klondike = Klondike::from_layout(&dead_layout(), 1).unwrap();
+
+        assert_eq!(klondike.stuck_analysis(), StuckReport { available_moves: 0, can_cycle: false, sample_move: None });
+        assert!(klondike.is_stuck());
+    }
+
+    #[test]
+    fn klondike_stuck_analysis_live() {
+        let klondike = play_waste_game(Card { suit: CardSuit::SPADES, rank: CardRank::TWO });
+
+        let report = klondike.stuck_analysis();
+        assert!(report.available_moves > 0);
+        assert!(report.can_cycle);
+        assert_eq!(report.sample_move, Some(KlondikeAction::MOVE(CardHolder::DECK, CardHolder::PILE(0), 1)));
+        assert!(!klondike.is_stuck());
+
+        // Without auto flip, the ten left alone under the jack can still
+        // be flipped
+        let mut klondike = Klondike::from_layout(&dead_layout(), 1).unwrap();
+        let ten = Card { suit: CardSuit::HEARTS, rank: CardRank::TEN };
+        let jack = Card { suit: CardSuit::HEARTS, rank: CardRank::JACK };
+        klondike.foundations[0] = Foundation::dealt(vec![ten, jack], 1,
+            BuildRule::AlternatingColors, EmptyColumnRule::KingsOnly, false);
+        klondike.foundations[0].peek(1);
+        assert_eq!(klondike.stuck_analysis(),
+            StuckReport { available_moves: 1, can_cycle: false, sample_move: Some(KlondikeAction::FLIP(0)) });
+        assert!(!klondike.is_stuck());
+    }
+
+    #[test]
+    fn klondike_take_empty_deck() {
+        let piles = CardSuit::iter()
+            .map(|suit| Card::full_deck().into_iter().filter(|x| x.suit == suit).collect())
+            .collect();
+        let layout = layout_with(piles, Vec::new(), Vec::new());
+        let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+
+        klondike.take();
+        assert!(klondike.replay().is_empty());
+        assert_eq!(klondike.get_version(), 0);
+    }
+
+    #[test]
+    fn klondike_undo_significant() {
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let layout = layout_with(
+            vec![Vec::new(); 4], vec![FoundationLayout { hidden: Vec::new(), visible: vec![ace] }], Vec::new());
+        let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+
+        klondike.take_n(3);
+        let status = klondike.get_status();
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::PILE(0), 1));
+        klondike.take_n(2);
+
+        klondike.undo_significant();
+        assert_eq!(klondike.get_status(), status);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::TAKE; 3]);
+
+        // Only takes left
+        klondike.undo_significant();
+        assert!(klondike.replay().is_empty());
+        assert_eq!(klondike.get_status().deck.cards_on_waste, 0);
+    }
+
     #[test]
     fn klondike_undo_take() {
         let mut klondike = Klondike::new();
@@ -850,15 +3665,610 @@ mod tests {
         }
     }
 
-    fn log_status(status: &KlondikeStatus) {
-        print!("Deck: (waste: {} stock: {} ) Piles:", status.deck.cards_on_waste, status.deck.cards_on_stock);
-        for i in &status.piles {
-            print!(" {}", i.num_cards);
+    #[test]
+    fn klondike_take_n() {
+        let mut klondike = Klondike::new();
+        let status = klondike.get_status();
+
+        klondike.take_n(5);
+        assert_eq!(klondike.get_status().deck.cards_on_waste, status.deck.cards_on_waste + 5);
+        assert_eq!(klondike.get_status().deck.cards_on_stock, status.deck.cards_on_stock - 5);
+
+        for _i in 0..4 {
+            klondike.undo();
+            assert_ne!(klondike.get_status(), status);
+        }
+
+        klondike.undo();
+        assert_eq!(klondike.get_status(), status);
+    }
+
+    #[test]
+    fn klondike_bytes_round_trip() {
+        let mut klondike = Klondike::new();
+        for i in 0..klondike.foundations.len() {
+            klondike.to_pile(CardHolder::FOUNDATION(i as u32));
+        }
+        for _i in 0..30 {
+            klondike.to_pile(CardHolder::DECK);
+            klondike.take();
+        }
+
+        let bytes = klondike.to_bytes();
+        let mut restored = Klondike::from_bytes(&bytes).unwrap();
+        assert_eq!(restored, klondike);
+
+        let json = serde_json::to_string(&klondike.get_status()).unwrap();
+        assert!(bytes.len() * 3 < json.len());
+
+        // The history is kept, so both games undo the same way
+        while !klondike.history.is_empty() {
+            klondike.undo();
+            restored.undo();
+            assert_eq!(restored.get_status(), klondike.get_status());
+        }
+    }
+
+    #[test]
+    fn klondike_from_invalid_bytes() {
+        let bytes = Klondike::new().to_bytes();
+
+        assert_eq!(Klondike::from_bytes(&[]), Err(InvalidBytes));
+        assert_eq!(Klondike::from_bytes(&bytes[..bytes.len() - 1]), Err(InvalidBytes));
+
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert_eq!(Klondike::from_bytes(&extra), Err(InvalidBytes));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 0;
+        assert_eq!(Klondike::from_bytes(&wrong_version), Err(InvalidBytes));
+    }
+
+    #[test]
+    fn klondike_moves_available() {
+        let mut pile = Pile::new();
+        pile.poke(&vec![Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }]);
+
+        let klondike = KlondikeMockable {
+            deck: Box::new(Deck::new(&vec![
+                Card { suit: CardSuit::DIAMONDS, rank: CardRank::SEVEN },
+                Card { suit: CardSuit::CLUBS, rank: CardRank::ACE },
+            ])),
+            piles: vec![pile, Pile::new(), Pile::new()],
+            foundations: vec![
+                Foundation::new(vec![
+                    Card { suit: CardSuit::CLUBS, rank: CardRank::FIVE },
+                    Card { suit: CardSuit::HEARTS, rank: CardRank::TWO },
+                ]),
+                Foundation::new(vec![
+                    Card { suit: CardSuit::DIAMONDS, rank: CardRank::NINE },
+                    Card { suit: CardSuit::SPADES, rank: CardRank::EIGHT },
+                ]),
+                Foundation::new(vec![Card { suit: CardSuit::CLUBS, rank: CardRank::KING }]),
+                Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::SEVEN }]),
+            ],
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
+        };
+
+        let moves = klondike.moves_available();
+        let expected = vec![
+            // The ace of clubs only to the first empty pile
+            (CardHolder::DECK, CardHolder::PILE(1), 1),
+            (CardHolder::DECK, CardHolder::FOUNDATION(0), 1),
+            (CardHolder::FOUNDATION(0), CardHolder::PILE(0), 1),
+            (CardHolder::FOUNDATION(3), CardHolder::FOUNDATION(1), 1),
+        ];
+
+        assert_eq!(moves.len(), expected.len());
+        for expected_move in expected {
+            assert!(moves.contains(&expected_move), "{:?} not found", expected_move);
+        }
+    }
+
+    #[test]
+    fn klondike_moves_available_cache() {
+        let mut klondike = Klondike::new_with_seed(3);
+        assert!(klondike.moves_cache.0.lock().unwrap().is_none());
+        let dealt = klondike.moves_available();
+        assert!(!klondike.moves_cache.0.lock().unwrap().is_none());
+        assert_eq!(klondike.moves_available(), dealt);
+
+        // Found again after every change, undos included
+        let (origin, destination, number) = dealt[0];
+        assert!(klondike.move_cards(origin, destination, number));
+        let moved = klondike.moves_available();
+        assert_eq!(moved, klondike.find_moves().available);
+        assert_ne!(moved, dealt);
+
+        klondike.undo();
+        assert_eq!(klondike.moves_available(), dealt);
+        klondike.take();
+        assert_eq!(klondike.moves_available(), klondike.find_moves().available);
+        assert_eq!(klondike.legal_destinations(CardHolder::DECK), klondike.card_holders().into_iter()
+            .filter(|x| klondike.can_move(CardHolder::DECK, *x, 1))
+            .collect::<Vec<_>>());
+
+        // Clones find their own moves
+        assert!(klondike.clone().moves_cache.0.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn klondike_moves_available_multiple_cards() {
+        let mut foundation = Foundation::new(vec![Card { suit: CardSuit::SPADES, rank: CardRank::TEN }]);
+        foundation.poke(&vec![Card { suit: CardSuit::HEARTS, rank: CardRank::NINE }]);
+
+        let klondike = KlondikeMockable {
+            deck: Box::new(Deck::new(&Vec::new())),
+            piles: vec![Pile::new()],
+            foundations: vec![
+                Foundation::new(vec![
+                    Card { suit: CardSuit::CLUBS, rank: CardRank::TWO },
+                    Card { suit: CardSuit::HEARTS, rank: CardRank::JACK },
+                ]),
+                foundation,
+            ],
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
+        };
+
+        assert_eq!(
+            klondike.moves_available(),
+            vec![(CardHolder::FOUNDATION(1), CardHolder::FOUNDATION(0), 2)]
+        );
+    }
+
+    #[test]
+    fn klondike_redeal() {
+        let config = KlondikeConfig { piles: 3, foundations: 5, ..KlondikeConfig::default() };
+        let mut klondike = Klondike::new_with_config(config).unwrap();
+        klondike.take();
+
+        klondike.redeal();
+        let first = klondike.get_status();
+        assert!(klondike.history.is_empty());
+        assert_eq!(first.piles.len(), 3);
+        assert_eq!(first.foundations.len(), 5);
+        assert_eq!(first.deck.cards_on_waste, 1);
+
+        // Two random deals could be the same, but it's really unlikely to
+        // happen several times in a row
+        let mut different = false;
+        for _i in 0..5 {
+            klondike.redeal();
+            if klondike.get_status() != first {
+                different = true;
+                break;
+            }
+        }
+        assert!(different);
+    }
+
+    #[test]
+    fn klondike_flips() {
+        let mut pile = Pile::new();
+        pile.poke(&vec![Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }]);
+
+        let mut klondike = KlondikeMockable {
+            deck: Box::new(Deck::new(&Vec::new())),
+            piles: vec![pile],
+            foundations: vec![
+                Foundation::new(vec![
+                    Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN },
+                    Card { suit: CardSuit::HEARTS, rank: CardRank::TWO },
+                ]),
+                Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]),
+            ],
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
+        };
+        let initial = klondike.get_status();
+        assert_eq!(initial.flips, 0);
+
+        // Moving the two of hearts shows the hidden queen of spades
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::PILE(0), 1));
+        assert_eq!(klondike.get_status().flips, 1);
+        assert_eq!(klondike.get_status().foundations[0].num_hidden, 0);
+
+        // The queen doesn't leave anything to flip behind
+        assert!(klondike.move_cards(CardHolder::FOUNDATION(0), CardHolder::FOUNDATION(1), 1));
+        assert_eq!(klondike.get_status().flips, 1);
+
+        klondike.undo();
+        assert_eq!(klondike.get_status().flips, 1);
+
+        klondike.undo();
+        assert_eq!(klondike.get_status(), initial);
+    }
+
+    #[test]
+    fn klondike_redo() {
+        let mut klondike = Klondike::new_with_seed(1);
+        assert!(!klondike.redo());
+
+        klondike.take();
+        let (origin, destination, number) = klondike.moves_available()[0];
+        assert!(klondike.move_cards(origin, destination, number));
+        let status = klondike.get_status();
+        klondike.undo();
+        klondike.undo();
+        assert!(klondike.redo_available());
+
+        assert!(klondike.redo());
+        assert!(klondike.redo());
+        assert!(!klondike.redo());
+        assert_eq!(klondike.get_status(), status);
+        assert_eq!(klondike.replay(), vec![KlondikeAction::TAKE, KlondikeAction::MOVE(origin, destination, number)]);
+
+        // The actions undone are kept by to_bytes
+        klondike.undo();
+        let mut restored = Klondike::from_bytes(&klondike.to_bytes()).unwrap();
+        assert_eq!(restored, klondike);
+        assert!(restored.redo());
+        assert_eq!(restored.get_status(), status);
+
+        // Until another action is done
+        klondike.take();
+        assert!(!klondike.redo_available());
+        assert!(!klondike.redo());
+    }
+
+    #[test]
+    fn klondike_replay() {
+        let mut klondike = Klondike::new_with_seed(1234);
+        for _i in 0..40 {
+            for j in 0..klondike.foundations.len() {
+                klondike.to_pile(CardHolder::FOUNDATION(j as u32));
+            }
+            klondike.to_pile(CardHolder::DECK);
+            klondike.take();
         }
-        print!(" Found:");
-        for i in &status.foundations {
-            print!(" (hid: {}, vis: {})", i.num_hidden, i.visible.len());
+        klondike.undo();
+
+        let replay = klondike.replay();
+        assert_eq!(replay.len(), klondike.history.len());
+
+        let json = serde_json::to_string(&replay).unwrap();
+        let replay: Vec<KlondikeAction> = serde_json::from_str(&json).unwrap();
+
+        let mut replayed = Klondike::new_with_seed(1234);
+        replayed.apply_replay(&replay);
+        assert_eq!(replayed.get_status(), klondike.get_status());
+        // Only the start times of the scores, the versions and the actions
+        // to redo, as the undone take isn't replayed, differ
+        assert_eq!(replayed.get_score(), klondike.get_score());
+        assert_eq!(replayed.get_version() + 2, klondike.get_version());
+        assert_eq!(replayed, Klondike {
+            score: replayed.score.clone(),
+            version: replayed.version,
+            redo: Vec::new(),
+            ..klondike
+        });
+    }
+
+    #[test]
+    fn klondike_status_display_widths() {
+        let ten = Card { suit: CardSuit::HEARTS, rank: CardRank::TEN };
+        let ace = Card { suit: CardSuit::CLUBS, rank: CardRank::ACE };
+        let mut foundations = vec![Foundation::new(vec![ten]), Foundation::new(vec![ace])];
+        foundations[0].poke(&vec![Card { suit: CardSuit::SPADES, rank: CardRank::NINE }]);
+        let klondike = klondike_with_foundations(foundations);
+        let board = klondike.get_status().to_string();
+        let rows: Vec<&str> = board.lines().skip_while(|x| !x.starts_with("  F1")).skip(1).collect();
+
+        // The ten and the ace start and end on the same columns
+        assert_eq!(rows[0], "[10♥] [ A♣]");
+        assert_eq!(rows[0].chars().count(), fmt_card_cell(None).chars().count() * 2 + 1);
+        assert_eq!(rows[1], "[ 9♤]");
+        for card in [ten, ace] {
+            assert_eq!(fmt_card_cell(Some(card)).chars().count(), CARD_WIDTH + 2);
         }
-        println!("");
+    }
+
+    #[test]
+    fn klondike_status_display() {
+        let mut pile = Pile::new();
+        pile.poke(&vec![Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }]);
+
+        let klondike = KlondikeMockable {
+            deck: Box::new(Deck::new(&vec![
+                Card { suit: CardSuit::DIAMONDS, rank: CardRank::SEVEN },
+                Card { suit: CardSuit::CLUBS, rank: CardRank::ACE },
+            ])),
+            piles: vec![pile, Pile::new()],
+            foundations: vec![
+                Foundation::new(vec![
+                    Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN },
+                    Card { suit: CardSuit::HEARTS, rank: CardRank::TEN },
+                ]),
+                Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]),
+            ],
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
+        };
+
+        assert_eq!(
+            format!("{}", klondike.get_status()),
+            [
+                "  P1    P2          D",
+                "[ A♥] [   ]       [ A♣] [ 1 ]",
+                "",
+                "  F1    F2",
+                "[ X ] [ K♥]",
+                "[10♥]",
+            ].join("\n")
+        );
+    }
+
+    #[test]
+    fn klondike_send_home() {
+        let mut pile = Pile::new();
+        pile.poke(&vec![Card { suit: CardSuit::HEARTS, rank: CardRank::ACE }]);
+
+        let mut klondike = KlondikeMockable {
+            deck: Box::new(Deck::new(&vec![
+                Card { suit: CardSuit::DIAMONDS, rank: CardRank::SEVEN },
+                Card { suit: CardSuit::CLUBS, rank: CardRank::ACE },
+            ])),
+            piles: vec![pile, Pile::new(), Pile::new()],
+            foundations: vec![
+                Foundation::new(vec![
+                    Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN },
+                    Card { suit: CardSuit::HEARTS, rank: CardRank::TWO },
+                ]),
+                Foundation::new(vec![Card { suit: CardSuit::HEARTS, rank: CardRank::KING }]),
+            ],
+            mover: SimpleCardMover {},
+            history: VecDeque::new(),
+            redo: Vec::new(),
+            max_history: None,
+            last_seq: None,
+            log: ActionLog::default(),
+            faceup: 1,
+            version: 0,
+            last_modified: LastModified(0),
+            moves_cache: MovesCache::default(),
+            score: Score::new(ScoringMode::Standard, 0),
+        };
+
+        // The ace of clubs goes to the first empty pile
+        assert_eq!(klondike.send_home(CardHolder::DECK), Some(1));
+        // The two of hearts goes over the ace of hearts
+        assert_eq!(klondike.send_home(CardHolder::FOUNDATION(0)), Some(0));
+        // The king has no place in the piles
+        assert_eq!(klondike.send_home(CardHolder::FOUNDATION(1)), None);
+
+        assert_eq!(klondike.history, vec![
+            KlondikeAction::MOVE(CardHolder::DECK, CardHolder::PILE(1), 1),
+            KlondikeAction::MOVE(CardHolder::FOUNDATION(0), CardHolder::PILE(0), 1),
+        ]);
+        assert_eq!(klondike.get_status().piles[0].num_cards, 2);
+        assert_eq!(klondike.get_status().piles[1].num_cards, 1);
+    }
+
+    /// Game with the ace of spades on the first pile, the given card on the
+    /// waste, and the king of hearts on the only foundation
+    fn play_waste_game(waste: Card) -> Klondike {
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let layout = layout_with(
+            vec![vec![ace], Vec::new()],
+            vec![FoundationLayout { hidden: Vec::new(), visible: vec![king] }],
+            vec![waste],
+        );
+        Klondike::from_layout(&layout, 1).unwrap()
+    }
+
+    #[test]
+    fn play_waste_home() {
+        let mut klondike = play_waste_game(Card { suit: CardSuit::SPADES, rank: CardRank::TWO });
+
+        assert!(klondike.play_waste());
+        assert_eq!(klondike.replay(), vec![KlondikeAction::MOVE(CardHolder::DECK, CardHolder::PILE(0), 1)]);
+    }
+
+    #[test]
+    fn play_waste_foundation() {
+        let mut klondike = play_waste_game(Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN });
+
+        assert!(klondike.play_waste());
+        assert_eq!(klondike.replay(), vec![KlondikeAction::MOVE(CardHolder::DECK, CardHolder::FOUNDATION(0), 1)]);
+        assert_eq!(klondike.get_status().foundations[0].visible.len(), 2);
+    }
+
+    #[test]
+    fn play_waste_nowhere() {
+        let mut klondike = play_waste_game(Card { suit: CardSuit::HEARTS, rank: CardRank::FIVE });
+        let status = klondike.get_status();
+
+        assert!(!klondike.play_waste());
+        assert_eq!(klondike.get_status(), status);
+        assert!(klondike.replay().is_empty());
+    }
+
+    /// Game with hearts up to the three, spades up to the ace and clubs up
+    /// to the two on the piles, the two of diamonds on the waste, and the
+    /// four of hearts (over the five), two of spades, ace of diamonds and
+    /// three of clubs on the foundations
+    fn auto_moves_game() -> Klondike {
+        let card = |suit, rank| Card { suit, rank };
+        let piles = vec![
+            vec![card(CardSuit::HEARTS, CardRank::ACE), card(CardSuit::HEARTS, CardRank::TWO),
+                card(CardSuit::HEARTS, CardRank::THREE)],
+            vec![card(CardSuit::SPADES, CardRank::ACE)],
+            vec![card(CardSuit::CLUBS, CardRank::ACE), card(CardSuit::CLUBS, CardRank::TWO)],
+            Vec::new(),
+        ];
+        let waste = vec![card(CardSuit::DIAMONDS, CardRank::TWO)];
+        let foundations = vec![
+            FoundationLayout {
+                hidden: vec![card(CardSuit::HEARTS, CardRank::FIVE)],
+                visible: vec![card(CardSuit::HEARTS, CardRank::FOUR)],
+            },
+            FoundationLayout { hidden: Vec::new(), visible: vec![card(CardSuit::SPADES, CardRank::TWO)] },
+            FoundationLayout { hidden: Vec::new(), visible: vec![card(CardSuit::DIAMONDS, CardRank::ACE)] },
+            FoundationLayout { hidden: Vec::new(), visible: vec![card(CardSuit::CLUBS, CardRank::THREE)] },
+        ];
+        Klondike::from_layout(&layout_with(piles, foundations, waste), 1).unwrap()
+    }
+
+    #[test]
+    fn klondike_take_and_autoplay() {
+        let card = |suit, rank| Card { suit, rank };
+        let ace = card(CardSuit::HEARTS, CardRank::ACE);
+        let two = card(CardSuit::HEARTS, CardRank::TWO);
+        let three = card(CardSuit::HEARTS, CardRank::THREE);
+        let mut layout = layout_with(
+            vec![vec![ace], Vec::new(), Vec::new(), Vec::new()],
+            vec![FoundationLayout { hidden: Vec::new(), visible: vec![three] }],
+            Vec::new(),
+        );
+        // The two is the next card to take
+        layout.stock.retain(|x| *x != two);
+        layout.stock.push(two);
+        let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+        let dealt = klondike.get_status();
+
+        assert_eq!(klondike.take_and_autoplay(AutoPolicy::Aggressive), 2);
+        assert_eq!(klondike.get_status().piles[0].top_card, Some(three));
+        assert_eq!(klondike.replay(), vec![
+            KlondikeAction::TAKE,
+            KlondikeAction::MOVE(CardHolder::DECK, CardHolder::PILE(0), 1),
+            KlondikeAction::MOVE(CardHolder::FOUNDATION(0), CardHolder::PILE(0), 1),
+        ]);
+
+        klondike.undo();
+        assert_eq!(klondike.get_status().piles[0].top_card, Some(two));
+        klondike.undo();
+        assert_eq!(klondike.get_status().deck.top_card_on_waste, Some(two));
+        klondike.undo();
+        assert_eq!(klondike.get_status(), dealt);
+
+        // Nothing else to send home after the take
+        assert_eq!(klondike.take_and_autoplay(AutoPolicy::AcesAndTwosOnly), 1);
+        assert_eq!(klondike.take_and_autoplay(AutoPolicy::AcesAndTwosOnly), 0);
+    }
+
+    #[test]
+    fn auto_moves_aces_and_twos() {
+        let mut klondike = auto_moves_game();
+
+        assert_eq!(klondike.auto_moves(AutoPolicy::AcesAndTwosOnly), 3);
+        assert_eq!(klondike.replay(), vec![
+            KlondikeAction::MOVE(CardHolder::FOUNDATION(1), CardHolder::PILE(1), 1),
+            KlondikeAction::MOVE(CardHolder::FOUNDATION(2), CardHolder::PILE(3), 1),
+            KlondikeAction::MOVE(CardHolder::DECK, CardHolder::PILE(3), 1),
+        ]);
+        assert_eq!(klondike.auto_moves(AutoPolicy::AcesAndTwosOnly), 0);
+    }
+
+    #[test]
+    fn auto_moves_safe_homes() {
+        let mut klondike = auto_moves_game();
+
+        // The three of clubs waits for the two of diamonds, and the four of
+        // hearts stays as the spades are still on the two
+        assert_eq!(klondike.auto_moves(AutoPolicy::SafeHomes), 4);
+        let homes = klondike.get_status().homes_by_suit;
+        assert_eq!(homes[&CardSuit::HEARTS], Some(CardRank::THREE));
+        assert_eq!(homes[&CardSuit::SPADES], Some(CardRank::TWO));
+        assert_eq!(homes[&CardSuit::CLUBS], Some(CardRank::THREE));
+        assert_eq!(homes[&CardSuit::DIAMONDS], Some(CardRank::TWO));
+        assert_eq!(klondike.replay().len(), 4);
+    }
+
+    #[test]
+    fn klondike_is_safe_autoplay() {
+        let card = |suit, rank| Card { suit, rank };
+        let with_homes = |homes: Vec<Vec<Card>>|
+            Klondike::from_layout(&layout_with(homes, Vec::new(), Vec::new()), 1).unwrap();
+        let home = |suit, rank| CardRank::iter().take(rank).map(|x| card(suit, x)).collect();
+        let clubs = |rank| home(CardSuit::CLUBS, rank);
+        let spades = |rank| home(CardSuit::SPADES, rank);
+        let five = card(CardSuit::HEARTS, CardRank::FIVE);
+
+        // Aces and twos, even with every home empty
+        let klondike = with_homes(vec![Vec::new(); 4]);
+        assert!(klondike.is_safe_autoplay(card(CardSuit::DIAMONDS, CardRank::ACE)));
+        assert!(klondike.is_safe_autoplay(card(CardSuit::SPADES, CardRank::TWO)));
+        assert!(!klondike.is_safe_autoplay(card(CardSuit::SPADES, CardRank::THREE)));
+
+        // A red five, when both black homes are at least on the four
+        let klondike = with_homes(vec![clubs(4), spades(3), Vec::new(), Vec::new()]);
+        assert!(!klondike.is_safe_autoplay(five));
+        let klondike = with_homes(vec![clubs(4), spades(4), Vec::new(), Vec::new()]);
+        assert!(klondike.is_safe_autoplay(five));
+        let klondike = with_homes(vec![clubs(6), spades(5), Vec::new(), Vec::new()]);
+        assert!(klondike.is_safe_autoplay(five));
+
+        // The homes of its own color don't matter
+        assert!(!klondike.is_safe_autoplay(card(CardSuit::CLUBS, CardRank::FOUR)));
+    }
+
+    #[test]
+    fn auto_moves_aggressive() {
+        let mut klondike = auto_moves_game();
+
+        assert_eq!(klondike.auto_moves(AutoPolicy::Aggressive), 6);
+        assert_eq!(klondike.get_status().homes_by_suit[&CardSuit::HEARTS], Some(CardRank::FIVE));
+        assert_eq!(klondike.replay().len(), 6);
+
+        // Every move is undone on its own
+        klondike.undo();
+        assert_eq!(klondike.get_status().homes_by_suit[&CardSuit::HEARTS], Some(CardRank::FOUR));
+    }
+
+    #[test]
+    fn auto_moves_flips() {
+        let mut klondike = auto_moves_game();
+        let four = Card { suit: CardSuit::HEARTS, rank: CardRank::FOUR };
+        let five = Card { suit: CardSuit::HEARTS, rank: CardRank::FIVE };
+        klondike.foundations[0] = Foundation::dealt(vec![five, four], 1,
+            BuildRule::AlternatingColors, EmptyColumnRule::KingsOnly, false);
+
+        assert_eq!(klondike.auto_moves(AutoPolicy::Aggressive), 7);
+        assert!(klondike.replay().contains(&KlondikeAction::FLIP(0)));
+        assert_eq!(klondike.get_status().homes_by_suit[&CardSuit::HEARTS], Some(CardRank::FIVE));
+    }
+
+    fn log_status(status: &KlondikeStatus) {
+        println!("{}", status.summary());
     }
 }
\ No newline at end of file
@@ -1,26 +1,74 @@
 use crate::card_game::american_cards::*;
 use crate::card_game::card_containers::*;
+use super::compact::*;
 use serde::Serialize;
 
 /// Each of the piles of the game. Acts as a Card Origin an Card Destination.
-/// Only one card can be peek or poke at a time.
+/// Only one card can be peek or poke at a time, unless the pile allows
+/// peeking several ones.
 /// When poking a card, it has to be the same suit and the next rank of the 
 /// card at top of the pile. If the pile is empty an ACE of any suit is allowed.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Pile {
     cards: Vec<Card>,
+    /// Whether several cards can be peeked at once, for variants letting
+    /// runs go back to the foundations
+    multi_peek: bool,
 }
 
 /// Value object used by UI for representing the status of a Pile
 #[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub struct PileStatus {
     pub top_card: Option<Card>,
-    pub num_cards: u32
+    pub num_cards: u32,
+    /// Suit of the ace at the bottom, which every card on the pile
+    /// follows. None while the pile is empty.
+    pub suit: Option<CardSuit>,
 }
 
 impl Pile {
     pub fn new() -> Pile {
-        Pile { cards: vec![] }
+        Pile { cards: vec![], multi_peek: false }
+    }
+
+    /// Creates an empty pile where the top cards can be peeked together.
+    /// They are returned from the highest rank down, so they can be
+    /// poked on a foundation building by suit.
+    pub fn new_with_multi_peek() -> Pile {
+        Pile { cards: vec![], multi_peek: true }
+    }
+
+    /// Creates a pile with the given cards, from the bottom to the top one.
+    /// None if they couldn't have been poked in that order.
+    pub fn from_cards(cards: &[Card]) -> Option<Pile> {
+        let mut pile = Pile::new();
+        for card in cards {
+            let card = vec![*card];
+            if !pile.try_poke(&card) {
+                return None;
+            }
+            pile.poke(&card);
+        }
+        Some(pile)
+    }
+
+    /// Checks whether the top number cards can be peeked: a single one, or
+    /// a run of the same suit in ascending rank if the pile allows it
+    fn can_peek(&self, number: usize) -> bool {
+        if number == 0 || number > self.cards.len() {
+            return false;
+        }
+        if number == 1 {
+            return true;
+        }
+
+        self.multi_peek && self.cards[self.cards.len() - number..].windows(2)
+            .all(|x| x[0].suit == x[1].suit && x[0].rank as u8 + 1 == x[1].rank as u8)
+    }
+
+    /// The cards on the pile, from the bottom to the top one
+    pub(crate) fn cards(&self) -> &[Card] {
+        &self.cards
     }
 
     pub fn get_status(&self) -> PileStatus {
@@ -30,35 +78,50 @@ impl Pile {
         }
 
         PileStatus {
-            top_card, num_cards: self.cards.len() as u32
+            top_card, num_cards: self.cards.len() as u32,
+            suit: self.cards.first().map(|x| x.suit),
         }
     }
+
+    /// Appends the compact representation of the pile
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_cards(out, &self.cards);
+        out.push(self.multi_peek as u8);
+    }
+
+    /// Reads a pile written by write_bytes
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Result<Pile, InvalidBytes> {
+        Ok(Pile {
+            cards: reader.read_cards()?,
+            multi_peek: match reader.read_u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(InvalidBytes),
+            },
+        })
+    }
 }
 
 impl CardOrigin for Pile {
     fn try_peek(&self, number: usize) -> Option<Vec<Card>> {
-        if number == 1 && !self.cards.is_empty() {
-            return Some(self.cards[self.cards.len() - 1..].to_vec());
+        if self.can_peek(number) {
+            return Some(self.cards[self.cards.len() - number..].iter().rev().copied().collect());
         }
         return None;
     }
 
     fn peek(&mut self, number: usize) -> Vec<Card> {
-        if number == 1 {
-            match self.cards.pop() {
-                Some(card) => {
-                    let r = vec![card];
-                    return r;
-                }
-                None => {}
-            }
+        if self.can_peek(number) {
+            let mut cards: Vec<Card> = self.cards.drain(self.cards.len() - number..).collect();
+            cards.reverse();
+            return cards;
         }
         return Vec::new();
     }
 
     fn undo_peek(&mut self, cards: &Vec<Card>) {
-        if cards.len() == 1 {
-            self.cards.push(cards[0]);
+        if cards.len() == 1 || self.multi_peek {
+            self.cards.extend(cards.iter().rev());
         }
     }
 }
@@ -105,6 +168,20 @@ mod tests {
     use crate::card_game::card_containers::test_common::*;
 
 
+    #[test]
+    fn pile_from_cards() {
+        let cards = vec![
+            Card { suit: CardSuit::HEARTS, rank: CardRank::ACE },
+            Card { suit: CardSuit::HEARTS, rank: CardRank::TWO },
+        ];
+        let pile = Pile::from_cards(&cards).unwrap();
+        assert_eq!(pile.cards, cards);
+
+        assert_eq!(Pile::from_cards(&[]), Some(Pile::new()));
+        assert_eq!(Pile::from_cards(&cards[1..]), None);
+        assert_eq!(Pile::from_cards(&[cards[0], Card { suit: CardSuit::SPADES, rank: CardRank::TWO }]), None);
+    }
+
     #[test]
     fn pile_new() {
         let pile = Pile::new();
@@ -158,12 +235,28 @@ mod tests {
                     rank: CardRank::THREE,
                 },
             ],
+            multi_peek: false,
         }
     }
 
+    #[test]
+    fn pile_status_suit() {
+        let mut pile = Pile::new();
+        assert_eq!(pile.get_status().suit, None);
+
+        pile.poke(&vec![Card { suit: CardSuit::SPADES, rank: CardRank::ACE }]);
+        assert_eq!(pile.get_status().suit, Some(CardSuit::SPADES));
+        pile.poke(&vec![Card { suit: CardSuit::SPADES, rank: CardRank::TWO }]);
+        assert_eq!(pile.get_status().suit, Some(CardSuit::SPADES));
+
+        pile.peek(1);
+        pile.peek(1);
+        assert_eq!(pile.get_status().suit, None);
+    }
+
     #[test]
     fn pile_peek_one_empty() {
-        let mut pile = Pile { cards: vec![] };
+        let mut pile = Pile { cards: vec![], multi_peek: false };
 
         assert_eq!(pile.try_peek(1), None);
 
@@ -180,6 +273,59 @@ mod tests {
         assert_eq!(pile.try_peek(0), None);
         assert_eq!(pile.peek(0).len(), 0);
     }
+
+    #[test]
+    fn pile_multi_peek() {
+        let mut pile = Pile { multi_peek: true, ..create_test_pile() };
+        let cards = pile.cards.clone();
+
+        assert_eq!(pile.try_peek(2), Some(vec![cards[2], cards[1]]));
+        assert_eq!(pile.try_peek(3), Some(vec![cards[2], cards[1], cards[0]]));
+        assert_eq!(pile.try_peek(4), None);
+
+        let peeked = pile.peek(2);
+        assert_eq!(peeked, vec![cards[2], cards[1]]);
+        assert_eq!(pile.cards, vec![cards[0]]);
+
+        pile.undo_peek(&peeked);
+        assert_eq!(pile.cards, cards);
+
+        assert_eq!(pile.peek(3), vec![cards[2], cards[1], cards[0]]);
+        assert_eq!(pile.get_status().num_cards, 0);
+    }
+
+    #[test]
+    fn pile_multi_peek_broken_run() {
+        let card = |suit, rank| Card { suit, rank };
+        let mut pile = Pile {
+            cards: vec![
+                card(CardSuit::HEARTS, CardRank::ACE),
+                card(CardSuit::HEARTS, CardRank::THREE),
+                card(CardSuit::CLUBS, CardRank::FOUR),
+            ],
+            multi_peek: true,
+        };
+
+        assert_eq!(pile.try_peek(1), Some(vec![card(CardSuit::CLUBS, CardRank::FOUR)]));
+        assert_eq!(pile.try_peek(2), None);
+        assert_eq!(pile.peek(3), Vec::new());
+        assert_eq!(pile.cards.len(), 3);
+
+        pile.cards.remove(2);
+        assert_eq!(pile.try_peek(2), None);
+    }
+
+    #[test]
+    fn pile_multi_peek_bytes() {
+        let pile = Pile { multi_peek: true, ..create_test_pile() };
+        let mut out = Vec::new();
+        pile.write_bytes(&mut out);
+
+        let mut reader = ByteReader::new(&out);
+        assert_eq!(Pile::read_bytes(&mut reader), Ok(pile));
+        assert!(reader.is_finished());
+    }
+
     #[test]
     fn pile_empty_poke_one_ace() {
         pile_empty_poke_one_ace_of_suit(CardSuit::CLUBS);
@@ -189,14 +335,14 @@ mod tests {
     }
 
     fn pile_empty_poke_one_ace_of_suit(suit: CardSuit) {
-        let mut pile = Pile { cards: vec![] };
+        let mut pile = Pile { cards: vec![], multi_peek: false };
 
         pile_poke_card_expect_size(&mut pile, suit, CardRank::ACE, 1, true);
     }
 
     #[test]
     fn pile_empty_poke_one_not_ace() {
-        let mut pile = Pile { cards: vec![] };
+        let mut pile = Pile { cards: vec![], multi_peek: false };
 
         pile_poke_card_expect_size(&mut pile, CardSuit::DIAMONDS, CardRank::FOUR, 0, false);
     }
@@ -0,0 +1,137 @@
+use super::compact::*;
+
+/// How the score of a game is computed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ScoringMode {
+    /// Points for the moves making progress and for each card turned
+    /// visible, never going below zero
+    Standard,
+    /// Like Standard, but losing some points every few seconds of play
+    Timed,
+    /// Starts at -52 and adds 5 points for each card on the piles
+    Vegas,
+}
+
+pub const TIMED_PENALTY: i32 = 2;
+pub const TIMED_PENALTY_PERIOD_MILLIS: u64 = 10_000;
+pub const VEGAS_INITIAL_SCORE: i32 = -52;
+
+impl ScoringMode {
+    fn to_u8(self) -> u8 {
+        match self {
+            ScoringMode::Standard => 0,
+            ScoringMode::Timed => 1,
+            ScoringMode::Vegas => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<ScoringMode, InvalidBytes> {
+        match value {
+            0 => Ok(ScoringMode::Standard),
+            1 => Ok(ScoringMode::Timed),
+            2 => Ok(ScoringMode::Vegas),
+            _ => Err(InvalidBytes),
+        }
+    }
+}
+
+/// Score of a game. The game gives the points earned so far, which
+/// don't depend on the time, and this turns them into the score.
+#[derive(Debug, Clone)]
+pub(crate) struct Score {
+    mode: ScoringMode,
+    /// Points already earned on the last reset
+    points_on_reset: i32,
+    /// Time of the last reset, in milliseconds since the epoch
+    reset_at: u64,
+}
+
+/// The time of the last reset is left out, so games dealt the same way
+/// are equal even if created at different times
+impl PartialEq for Score {
+    fn eq(&self, other: &Score) -> bool {
+        self.mode == other.mode && self.points_on_reset == other.points_on_reset
+    }
+}
+
+impl Score {
+    pub(crate) fn new(mode: ScoringMode, now: u64) -> Score {
+        Score { mode, points_on_reset: 0, reset_at: now }
+    }
+
+    pub(crate) fn mode(&self) -> ScoringMode {
+        self.mode
+    }
+
+    /// Makes the score start again from the given points and time
+    pub(crate) fn reset(&mut self, points: i32, now: u64) {
+        self.points_on_reset = points;
+        self.reset_at = now;
+    }
+
+    /// Keeps counting the given points, which the game no longer counts
+    /// among the ones earned
+    pub(crate) fn keep_points(&mut self, points: i32) {
+        self.points_on_reset -= points;
+    }
+
+    pub(crate) fn get(&self, points: i32, now: u64) -> i32 {
+        let earned = points - self.points_on_reset;
+        match self.mode {
+            ScoringMode::Standard => earned.max(0),
+            ScoringMode::Timed => {
+                let periods = now.saturating_sub(self.reset_at) / TIMED_PENALTY_PERIOD_MILLIS;
+                (earned - periods as i32 * TIMED_PENALTY).max(0)
+            }
+            ScoringMode::Vegas => VEGAS_INITIAL_SCORE + earned,
+        }
+    }
+
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.push(self.mode.to_u8());
+        write_u32(out, self.points_on_reset as u32);
+        write_u64(out, self.reset_at);
+    }
+
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Result<Score, InvalidBytes> {
+        Ok(Score {
+            mode: ScoringMode::from_u8(reader.read_u8()?)?,
+            points_on_reset: reader.read_u32()? as i32,
+            reset_at: reader.read_u64()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_penalty() {
+        let mut score = Score::new(ScoringMode::Timed, 1_000);
+        assert_eq!(score.get(20, 1_000), 20);
+        assert_eq!(score.get(20, 10_999), 20);
+        assert_eq!(score.get(20, 11_000), 18);
+        assert_eq!(score.get(20, 101_000), 0);
+
+        score.reset(20, 101_000);
+        assert_eq!(score.get(25, 101_000), 5);
+
+        score.keep_points(10);
+        assert_eq!(score.get(15, 101_000), 5);
+    }
+
+    #[test]
+    fn score_bytes_round_trip() {
+        let mut score = Score::new(ScoringMode::Vegas, 1234);
+        score.reset(-15, 5678);
+        let mut out = Vec::new();
+        score.write_bytes(&mut out);
+
+        let mut reader = ByteReader::new(&out);
+        let read = Score::read_bytes(&mut reader).unwrap();
+        assert_eq!(read, score);
+        assert_eq!(read.reset_at, score.reset_at);
+        assert!(reader.is_finished());
+    }
+}
@@ -0,0 +1,141 @@
+use super::*;
+use std::collections::HashSet;
+
+/// Maximum number of positions visited before giving up
+pub const MAX_POSITIONS: usize = 100_000;
+
+/// Looks for the actions winning the game from its current position, in
+/// the form taken by apply_replay. None if the game can't be won, or the
+/// way to win it wasn't found after visiting MAX_POSITIONS positions.
+pub fn solve(klondike: &Klondike) -> Option<Vec<KlondikeAction>> {
+    if klondike.is_won() {
+        return Some(Vec::new());
+    }
+
+    let mut visited = HashSet::new();
+    visited.insert(klondike.position_key());
+
+    // Depth first search. Each level keeps the game and the actions not
+    // tried yet, the most promising one at the end.
+    let mut stack = vec![(klondike.clone(), candidates(klondike))];
+    let mut path: Vec<KlondikeAction> = Vec::new();
+
+    while let Some((game, actions)) = stack.last_mut() {
+        let action = match actions.pop() {
+            Some(action) => action,
+            None => {
+                stack.pop();
+                path.pop();
+                continue;
+            }
+        };
+
+        let mut next = game.clone();
+        next.apply_replay(std::slice::from_ref(&action));
+        if !visited.insert(next.position_key()) {
+            continue;
+        }
+        if visited.len() > MAX_POSITIONS {
+            return None;
+        }
+
+        path.push(action);
+        if next.is_won() {
+            return Some(path);
+        }
+
+        let next_actions = candidates(&next);
+        stack.push((next, next_actions));
+    }
+
+    None
+}
+
+/// Actions worth trying from the given position, the most promising at
+/// the end: flipping hidden cards, sending cards home, then uncovering
+/// hidden cards, then the rest of the moves and finally taking from the
+/// stock.
+fn candidates(klondike: &Klondike) -> Vec<KlondikeAction> {
+    let mut moves = klondike.moves_available();
+    moves.sort_by_key(|(origin, destination, number)| match (origin, destination) {
+        (_, CardHolder::PILE(_)) => 3,
+        (CardHolder::FOUNDATION(idx), _) if uncovers(klondike, *idx, *number) => 2,
+        (CardHolder::DECK, _) => 1,
+        _ => 0,
+    });
+
+    let mut result = Vec::new();
+    if klondike.count_cards(CardHolder::DECK) > 0 {
+        result.push(KlondikeAction::TAKE);
+    }
+    result.extend(moves.into_iter().map(|(origin, destination, number)|
+        KlondikeAction::MOVE(origin, destination, number)));
+    // Without auto flip, flipping never hurts
+    result.extend(klondike.foundations.iter().enumerate()
+        .filter(|(_i, x)| x.hidden_count() > 0 && x.get_status().visible.is_empty())
+        .map(|(i, _x)| KlondikeAction::FLIP(i as u32)));
+    result
+}
+
+/// Checks whether moving number cards from the foundation leaves a hidden
+/// card on top of it
+fn uncovers(klondike: &Klondike, foundation: u32, number: u32) -> bool {
+    klondike.foundations.get(foundation as usize)
+        .map(|x| x.get_status())
+        .is_some_and(|x| x.num_hidden > 0 && x.visible.len() as u32 == number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_common::*;
+
+    /// Layout with the cards of each suit up to the given rank on the
+    /// piles, the given foundations, and the rest on the stock
+    fn layout_homed_up_to(rank: CardRank, foundations: Vec<FoundationLayout>) -> KlondikeLayout {
+        let mut piles = vec![Vec::new(); 4];
+        for card in Card::full_deck().into_iter().filter(|x| x.rank.to_u8() <= rank.to_u8()) {
+            piles[card.suit.to_u8() as usize].push(card);
+        }
+        layout_with(piles, foundations, Vec::new())
+    }
+
+    #[test]
+    fn solve_nearly_won() {
+        let jack = Card { suit: CardSuit::HEARTS, rank: CardRank::JACK };
+        let queen = Card { suit: CardSuit::SPADES, rank: CardRank::QUEEN };
+        let layout = layout_homed_up_to(CardRank::TEN, vec![
+            FoundationLayout { hidden: vec![jack], visible: vec![queen] },
+            FoundationLayout { hidden: Vec::new(), visible: Vec::new() },
+        ]);
+        let klondike = Klondike::from_layout(&layout, 1).unwrap();
+
+        let actions = solve(&klondike).unwrap();
+
+        let mut solved = klondike.clone();
+        solved.apply_replay(&actions);
+        assert!(solved.is_won());
+        assert_eq!(solved.replay(), actions);
+        // The original game is left untouched
+        assert!(!klondike.is_won());
+    }
+
+    #[test]
+    fn solve_unwinnable() {
+        let ten = Card { suit: CardSuit::HEARTS, rank: CardRank::TEN };
+        let jack = Card { suit: CardSuit::HEARTS, rank: CardRank::JACK };
+        let mut piles = layout_homed_up_to(CardRank::KING, Vec::new()).piles;
+        piles[CardSuit::HEARTS.to_u8() as usize].truncate(9);
+        // The jack can't go anywhere, so the ten below it never comes out
+        let layout = layout_with(piles, vec![FoundationLayout { hidden: vec![ten], visible: vec![jack] }], Vec::new());
+        let klondike = Klondike::from_layout(&layout, 1).unwrap();
+
+        assert_eq!(solve(&klondike), None);
+    }
+
+    #[test]
+    fn solve_won() {
+        let klondike = Klondike::from_layout(&layout_homed_up_to(CardRank::KING, Vec::new()), 1).unwrap();
+        assert_eq!(solve(&klondike), Some(Vec::new()));
+    }
+}
@@ -0,0 +1,212 @@
+use super::super::Klondike;
+use super::klondike_repository::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Wrapper in order to limit the number of stored games.
+/// When full, saving a new game deletes the least recently accessed one.
+pub struct BoundedRepository<T: KlondikeRepository> {
+    delegate: T,
+    capacity: usize,
+    access: Mutex<AccessOrder>,
+}
+
+/// Keeps track of the relative order in which the ids were last accessed.
+struct AccessOrder {
+    last_access: HashMap<String, u64>,
+    counter: u64,
+}
+
+impl AccessOrder {
+    fn touch(&mut self, id: &str) {
+        self.counter += 1;
+        self.last_access.insert(id.to_string(), self.counter);
+    }
+
+    fn least_recently_accessed(&self) -> Option<String> {
+        self.last_access.iter()
+            .min_by_key(|(_id, order)| **order)
+            .map(|(id, _order)| id.clone())
+    }
+}
+
+impl<T: KlondikeRepository> BoundedRepository<T> {
+    pub fn new(delegate: T, capacity: usize) -> BoundedRepository<T> {
+        BoundedRepository {
+            delegate,
+            capacity,
+            access: Mutex::new(AccessOrder { last_access: HashMap::new(), counter: 0 }),
+        }
+    }
+
+    /// Deletes the least recently accessed games until there is room for a new one
+    fn make_room(&mut self) {
+        loop {
+            let to_remove = {
+                let access = self.access.lock().unwrap();
+                if access.last_access.len() < self.capacity {
+                    return;
+                }
+                access.least_recently_accessed()
+            };
+
+            match to_remove {
+                Some(id) => { self.delete(&id); }
+                None => return,
+            }
+        }
+    }
+}
+
+impl<T: KlondikeRepository> KlondikeRepository for BoundedRepository<T> {
+
+    fn save(&mut self, klondike: Klondike) -> String {
+        self.make_room();
+
+        let result = self.delegate.save(klondike);
+
+        self.access.lock().unwrap().touch(&result);
+
+        result
+    }
+
+    fn update(&mut self, id: String, klondike: Klondike) {
+        if !self.access.lock().unwrap().last_access.contains_key(&id) {
+            self.make_room();
+        }
+
+        self.delegate.update(id.clone(), klondike);
+
+        self.access.lock().unwrap().touch(&id);
+    }
+
+    fn get(&self, id: &String) -> Option<Klondike> {
+        let result = self.delegate.get(id);
+
+        if result.is_some() {
+            self.access.lock().unwrap().touch(id);
+        }
+
+        result
+    }
+
+    fn delete(&mut self, id: &String) -> Option<Klondike> {
+        let result = self.delegate.delete(id);
+
+        self.access.lock().unwrap().last_access.remove(id);
+
+        result
+    }
+
+    fn count(&self) -> usize {
+        self.delegate.count()
+    }
+
+    fn list_ids(&self) -> Vec<String> {
+        self.delegate.list_ids()
+    }
+
+    fn with_mut<'a>(&mut self, id: &String, f: GameTask<'a>) -> bool {
+        let result = self.delegate.with_mut(id, f);
+
+        if result {
+            self.access.lock().unwrap().touch(id);
+        }
+
+        result
+    }
+
+    fn view<'a>(&self, id: &String, f: GameView<'a>) -> bool {
+        let result = self.delegate.view(id, f);
+
+        if result {
+            self.access.lock().unwrap().touch(id);
+        }
+
+        result
+    }
+
+    fn touch(&mut self, id: &String) -> bool {
+        let result = self.delegate.touch(id);
+
+        if result {
+            self.access.lock().unwrap().touch(id);
+        }
+
+        result
+    }
+
+    fn shared_game(&self, id: &String) -> Option<SharedGame> {
+        self.delegate.shared_game(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::hashmap_repository::KlondikeHashMapRepository;
+    use super::super::klondike_repository::test::*;
+
+    #[test]
+    fn save_update_get_bounded() {
+        save_update_get(&mut BoundedRepository::new(KlondikeHashMapRepository::new(), 10));
+    }
+
+    #[test]
+    fn delete_bounded() {
+        delete(&mut BoundedRepository::new(KlondikeHashMapRepository::new(), 10));
+    }
+
+    #[test]
+    fn count_bounded() {
+        count(&mut BoundedRepository::new(KlondikeHashMapRepository::new(), 10));
+    }
+
+    #[test]
+    fn with_mut_view_bounded() {
+        with_mut_view(&mut BoundedRepository::new(KlondikeHashMapRepository::new(), 10));
+    }
+
+    #[test]
+    fn list_ids_bounded() {
+        list_ids(&mut BoundedRepository::new(KlondikeHashMapRepository::new(), 10));
+    }
+
+    #[test]
+    fn touch_bounded() {
+        touch(&mut BoundedRepository::new(KlondikeHashMapRepository::new(), 10));
+    }
+
+    #[test]
+    fn evicts_least_recently_accessed() {
+        let mut repo = BoundedRepository::new(KlondikeHashMapRepository::new(), 3);
+
+        let id1 = repo.save(Klondike::new());
+        let id2 = repo.save(Klondike::new());
+        let id3 = repo.save(Klondike::new());
+
+        // Accessing the first one makes the second the least recently accessed
+        assert!(repo.get(&id1).is_some());
+
+        let id4 = repo.save(Klondike::new());
+
+        assert!(repo.get(&id2).is_none());
+        assert!(repo.get(&id1).is_some());
+        assert!(repo.get(&id3).is_some());
+        assert!(repo.get(&id4).is_some());
+    }
+
+    #[test]
+    fn update_unknown_id_respects_capacity() {
+        let mut repo = BoundedRepository::new(KlondikeHashMapRepository::new(), 2);
+
+        let id1 = repo.save(Klondike::new());
+        let id2 = repo.save(Klondike::new());
+
+        repo.update(String::from("new id"), Klondike::new());
+
+        assert!(repo.get(&id1).is_none());
+        assert!(repo.get(&id2).is_some());
+        assert!(repo.get(&String::from("new id")).is_some());
+    }
+}
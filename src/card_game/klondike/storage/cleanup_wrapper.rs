@@ -1,46 +1,93 @@
 use super::super::Klondike;
 use super::klondike_repository::*;
+use super::shared_repository::GameLocks;
+use super::periodic_task::PeriodicTask;
 use std::collections::HashMap;
 use std::sync::{Mutex, Arc};
-use clokwerk::{Scheduler, TimeUnits, ScheduleHandle};
 use std::time::{Duration, Instant};
 use std::marker::Send;
 
 /// Wrapper in order to add cleanup to the repository.
 /// When used it will delete the stored games after a given period of inactivity
+/// The thread looking for expired games is stopped when the repository is dropped.
+/// It deletes each game holding its lock, see locks.
 pub struct KlondikeCleanUpRepository<T: KlondikeRepository + Send + 'static, U: TimeoutRepository + Send + 'static> {
     delegate: Arc<Mutex<T>>,
     repo: Arc<Mutex<U>>,
-    thread_handle: ScheduleHandle,
+    locks: GameLocks,
+    scan_interval: Duration,
+    _scan: PeriodicTask,
 }
 
+/// Shorter scan intervals, like the tenth of a timeout of a few
+/// nanoseconds, would keep the scanning thread busy
+const MIN_SCAN_INTERVAL: Duration = Duration::from_millis(1);
+
 impl<T: KlondikeRepository + Send + 'static, U: TimeoutRepository + Send + 'static> KlondikeCleanUpRepository<T, U> {
+    /// Creates the repository looking for expired games every tenth of the timeout
     pub fn new (delegate: T, timeout: Duration, repo: U) -> KlondikeCleanUpRepository<T, U> {
+        KlondikeCleanUpRepository::new_with_scan_interval(delegate, timeout, timeout / 10, repo)
+    }
+
+    /// Creates the repository looking for expired games every scan_interval.
+    /// The scheduler works with whole seconds, so shorter intervals scan
+    /// every second. Intervals under a millisecond are taken as one.
+    pub fn new_with_scan_interval (delegate: T, timeout: Duration, scan_interval: Duration, repo: U)
+            -> KlondikeCleanUpRepository<T, U> {
+
+        let scan_interval = scan_interval.max(MIN_SCAN_INTERVAL);
 
         let delegate = Arc::new(Mutex::new(delegate));
         let repo = Arc::new(Mutex::new(repo));
 
+        let locks = GameLocks::new();
+
         let sch_delegate = Arc::clone(&delegate);
         let sch_repo = Arc::clone(&repo);
+        let sch_locks = locks.clone();
 
-        let mut scheduler = Scheduler::new();
-        scheduler.every(10.seconds()).run (move || {
+        let scan = PeriodicTask::start(scan_interval, move || {
             let to_remove = { sch_repo.lock().unwrap().get_expired(&timeout) };
             for id in to_remove {
-                sch_delegate.lock().unwrap().delete(&id);
+                sch_locks.locked(&id, || sch_delegate.lock().unwrap().delete(&id));
             }
         });
 
-        let thread_handle = scheduler.watch_thread(timeout / 10);
-
         let result = KlondikeCleanUpRepository {
             delegate, 
             repo,
-            thread_handle,
+            locks,
+            scan_interval,
+            _scan: scan,
         };
 
         result
     }
+
+    /// Locks of the games, held while deleting the expired ones. Given to
+    /// a SharedRepository, the games in use there aren't deleted until
+    /// their tasks end.
+    pub fn locks(&self) -> GameLocks {
+        self.locks.clone()
+    }
+
+    /// Time between the checks for expired games
+    pub fn scan_interval(&self) -> Duration {
+        self.scan_interval
+    }
+
+    /// Stops looking for expired games, waiting for the running scan to end
+    pub fn shutdown(self) {}
+
+    /// Saves the game like save does, but it will be deleted after the given
+    /// period of inactivity instead of the one given on creation.
+    pub fn save_with_timeout(&mut self, klondike: Klondike, timeout: Duration) -> String {
+        let result = self.save(klondike);
+
+        self.repo.lock().unwrap().save_timeout(&result, timeout);
+
+        result
+    }
 }
 
 impl<T: KlondikeRepository + Send + 'static, U: TimeoutRepository + Send + 'static> KlondikeRepository 
@@ -79,6 +126,48 @@ impl<T: KlondikeRepository + Send + 'static, U: TimeoutRepository + Send + 'stat
 
         result
     }
+
+    fn count(&self) -> usize {
+        self.delegate.lock().unwrap().count()
+    }
+
+    fn list_ids(&self) -> Vec<String> {
+        self.delegate.lock().unwrap().list_ids()
+    }
+
+    fn with_mut<'a>(&mut self, id: &String, f: GameTask<'a>) -> bool {
+        let result = { self.delegate.lock().unwrap().with_mut(id, f) };
+
+        if result {
+            self.repo.lock().unwrap().save_last_access(id);
+        }
+
+        result
+    }
+
+    fn view<'a>(&self, id: &String, f: GameView<'a>) -> bool {
+        let result = { self.delegate.lock().unwrap().view(id, f) };
+
+        if result {
+            self.repo.lock().unwrap().save_last_access(id);
+        }
+
+        result
+    }
+
+    fn touch(&mut self, id: &String) -> bool {
+        let result = { self.delegate.lock().unwrap().touch(id) };
+
+        if result {
+            self.repo.lock().unwrap().save_last_access(id);
+        }
+
+        result
+    }
+
+    fn shared_game(&self, id: &String) -> Option<SharedGame> {
+        self.delegate.lock().unwrap().shared_game(id)
+    }
 }
 
 /// Storage system for access timestamps.
@@ -87,9 +176,13 @@ pub trait TimeoutRepository {
     /// Set the last access time for id to now
     fn save_last_access(&mut self, id: &String);
 
+    /// Sets a timeout for id, to be used instead of the default one
+    fn save_timeout(&mut self, id: &String, timeout: Duration);
+
     /// Returns a vector containing all expired ids
     /// Expired meaning: (now - last_access_time) > timeout
-    fn get_expired(&mut self, timeout: &Duration) -> Vec<String>;
+    /// where timeout is the one saved for the id, or the given default one.
+    fn get_expired(&mut self, default_timeout: &Duration) -> Vec<String>;
 
     fn remove(&mut self, id: &String);
 
@@ -97,11 +190,12 @@ pub trait TimeoutRepository {
 
 pub struct HashMapTimeoutRepository {
     times: HashMap<String, Instant>,
+    timeouts: HashMap<String, Duration>,
 }
 
 impl HashMapTimeoutRepository {
     pub fn new() -> HashMapTimeoutRepository {
-        HashMapTimeoutRepository {times: HashMap::new()}
+        HashMapTimeoutRepository {times: HashMap::new(), timeouts: HashMap::new()}
     }
 }
 
@@ -110,17 +204,22 @@ impl TimeoutRepository for HashMapTimeoutRepository {
         self.times.insert(id.clone(), Instant::now());
     }
 
-    fn get_expired(&mut self, timeout: &Duration) -> Vec<String> {
+    fn save_timeout(&mut self, id: &String, timeout: Duration) {
+        self.timeouts.insert(id.clone(), timeout);
+    }
+
+    fn get_expired(&mut self, default_timeout: &Duration) -> Vec<String> {
         let mut result = Vec::new();
 
         for (id, instant) in self.times.iter() {
+            let timeout = self.timeouts.get(id).unwrap_or(default_timeout);
             if instant.elapsed() > *timeout {
                 result.push(id.to_string());
             }
         }
         
         for id in &result {
-            self.times.remove(id);
+            self.remove(id);
         }
     
         result
@@ -128,6 +227,7 @@ impl TimeoutRepository for HashMapTimeoutRepository {
 
     fn remove(&mut self, id: &String) {
         self.times.remove(id);
+        self.timeouts.remove(id);
     }
 
 }
@@ -137,6 +237,8 @@ mod tests {
     use super::*;
     use mockall::predicate::{eq, always};
     use std::thread;
+    use super::super::hashmap_repository::KlondikeHashMapRepository;
+    use super::super::shared_repository::SharedRepository;
 
     #[test]
     fn save () {
@@ -220,11 +322,52 @@ mod tests {
         assert_eq! (repo.delete(&id), None);
     }
 
+    #[test]
+    fn count() {
+        let mut delegate = MockKlondikeRepository::new();
+        delegate.expect_count().returning(|| 3);
+
+        let repo = KlondikeCleanUpRepository::new(delegate, Duration::from_secs(1),
+                        HashMapTimeoutRepository::new());
+
+        assert_eq! (repo.count(), 3);
+    }
+
+    #[test]
+    fn list_ids() {
+        let mut delegate = MockKlondikeRepository::new();
+        delegate.expect_list_ids().returning(|| vec![String::from("xxxx")]);
+
+        let repo = KlondikeCleanUpRepository::new(delegate, Duration::from_secs(1),
+                        HashMapTimeoutRepository::new());
+
+        assert_eq! (repo.list_ids(), vec![String::from("xxxx")]);
+    }
+
+    #[test]
+    fn view_existing() {
+        let id = String::from("testId");
+        let mut delegate = MockKlondikeRepository::new();
+        delegate.expect_get().times(0);
+        delegate.expect_view().with(eq(id.clone()), always())
+                .times(1)
+                .returning(|_x, f| { f(&Klondike::new_with_seed(1)); true });
+
+        let repo = KlondikeCleanUpRepository::new(delegate, Duration::from_secs(1),
+                        HashMapTimeoutRepository::new());
+
+        let mut seen = None;
+        assert!(repo.view(&id, &mut |x| seen = Some(x.get_status())));
+        assert_eq!(seen, Some(Klondike::new_with_seed(1).get_status()));
+        assert!(repo.repo.lock().unwrap().times.contains_key(&id));
+    }
+
     #[test]
     fn timeout() {
         let mut delegate = MockKlondikeRepository::new();
-        let klondike = Klondike::new();
-        let klondike2 = Klondike::new();
+        // Different deals, so each save gets its own id
+        let klondike = Klondike::new_with_seed(1);
+        let klondike2 = Klondike::new_with_seed(2);
         delegate.expect_save().with(eq(klondike.clone()))
                 .returning(|_x| String::from("xxxx"));
         delegate.expect_save().with(eq(klondike2.clone()))
@@ -233,21 +376,142 @@ mod tests {
                 .times(1)
                 .return_once(|_x| None); //Don't care
         delegate.expect_get().with(always())
-                .returning(|_x| None); //Don't care
+                .returning(|_x| Some(Klondike::new())); //Don't care
 
-        let mut repo = KlondikeCleanUpRepository::new(delegate, Duration::from_millis(100), 
+        let mut repo = KlondikeCleanUpRepository::new_with_scan_interval(delegate,
+                        Duration::from_millis(300), Duration::from_secs(1),
                         HashMapTimeoutRepository::new());
 
         repo.save(klondike);
         repo.save(klondike2);
 
-        let ten_millis = Duration::from_millis(10);
+        // Accessing xxxx keeps it alive while yyyy expires
+        let fifty_millis = Duration::from_millis(50);
         let id = String::from("xxxx");
-        for _i in 0..12 {
-            thread::sleep(ten_millis);
+        for _i in 0..50 {
+            thread::sleep(fifty_millis);
             repo.get(&id);
         }
 
     }
-}
 
+    #[test]
+    fn expired_games_are_deleted_on_scan() {
+        let mut repo = KlondikeCleanUpRepository::new_with_scan_interval(
+                        KlondikeHashMapRepository::new(), Duration::from_millis(500),
+                        Duration::from_secs(1), HashMapTimeoutRepository::new());
+
+        repo.save(Klondike::new());
+        assert_eq!(repo.count(), 1);
+
+        // Expired after half a second, deleted on the next scan
+        thread::sleep(Duration::from_millis(2500));
+        assert_eq!(repo.count(), 0);
+    }
+
+    #[test]
+    fn touched_games_are_kept() {
+        let mut repo = KlondikeCleanUpRepository::new_with_scan_interval(
+                        KlondikeHashMapRepository::new(), Duration::from_millis(500),
+                        Duration::from_secs(1), HashMapTimeoutRepository::new());
+
+        let touched = repo.save(Klondike::new_with_seed(1));
+        let untouched = repo.save(Klondike::new_with_seed(2));
+
+        for _i in 0..25 {
+            thread::sleep(Duration::from_millis(100));
+            assert!(repo.touch(&touched));
+        }
+        assert_eq!(repo.list_ids(), vec![touched]);
+        assert!(!repo.touch(&untouched));
+    }
+
+    #[test]
+    fn games_in_use_are_deleted_afterwards() {
+        let repo = KlondikeCleanUpRepository::new_with_scan_interval(
+                        KlondikeHashMapRepository::new(), Duration::from_millis(100),
+                        Duration::from_secs(1), HashMapTimeoutRepository::new());
+        let locks = repo.locks();
+        let repo = SharedRepository::new_with_locks(repo, locks.clone());
+        let id = repo.save(Klondike::new_with_seed(1));
+
+        // It expires while in use
+        locks.locked(&id, || {
+            thread::sleep(Duration::from_millis(2500));
+            assert_eq!(repo.count(), 1);
+        });
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(repo.count(), 0);
+    }
+
+    #[test]
+    fn zero_scan_interval() {
+        let repo = KlondikeCleanUpRepository::new_with_scan_interval(
+                        KlondikeHashMapRepository::new(), Duration::from_secs(60),
+                        Duration::ZERO, HashMapTimeoutRepository::new());
+        assert_eq!(repo.scan_interval(), MIN_SCAN_INTERVAL);
+
+        // Nor the tenth of a tiny timeout
+        let repo = KlondikeCleanUpRepository::new(KlondikeHashMapRepository::new(),
+                        Duration::from_nanos(5), HashMapTimeoutRepository::new());
+        assert_eq!(repo.scan_interval(), MIN_SCAN_INTERVAL);
+
+        let repo = KlondikeCleanUpRepository::new(KlondikeHashMapRepository::new(),
+                        Duration::from_secs(60), HashMapTimeoutRepository::new());
+        assert_eq!(repo.scan_interval(), Duration::from_secs(6));
+    }
+
+    #[test]
+    fn drop_stops_the_thread() {
+        for _i in 0..50 {
+            let repo = KlondikeCleanUpRepository::new_with_scan_interval(
+                            KlondikeHashMapRepository::new(), Duration::from_secs(60),
+                            Duration::from_secs(60), HashMapTimeoutRepository::new());
+            let delegate = Arc::downgrade(&repo.delegate);
+            let timeouts = Arc::downgrade(&repo.repo);
+
+            let start = Instant::now();
+            repo.shutdown();
+
+            // The thread doesn't wait for the next scan to end
+            assert!(start.elapsed() < Duration::from_secs(1));
+            // Nor keeps any reference to the repositories
+            assert!(delegate.upgrade().is_none());
+            assert!(timeouts.upgrade().is_none());
+        }
+    }
+
+    #[test]
+    fn save_with_timeout() {
+        let mut delegate = MockKlondikeRepository::new();
+        let klondike = Klondike::new();
+        delegate.expect_save().with(eq(klondike.clone()))
+                .returning(|_x| String::from("xxxx"));
+
+        let mut repo = KlondikeCleanUpRepository::new(delegate, Duration::from_secs(1),
+                        HashMapTimeoutRepository::new());
+        assert_eq! (repo.save_with_timeout(klondike, Duration::from_secs(60)), String::from("xxxx"));
+    }
+
+    #[test]
+    fn timeout_per_id() {
+        let mut repo = HashMapTimeoutRepository::new();
+        let short = String::from("short");
+        let long = String::from("long");
+
+        repo.save_last_access(&short);
+        repo.save_last_access(&long);
+        repo.save_timeout(&long, Duration::from_secs(60));
+
+        thread::sleep(Duration::from_millis(30));
+
+        assert_eq!(repo.get_expired(&Duration::from_millis(10)), vec![short.clone()]);
+        assert!(repo.get_expired(&Duration::from_millis(10)).is_empty());
+
+        // Removing an id also forgets its timeout
+        repo.remove(&long);
+        repo.save_last_access(&long);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(repo.get_expired(&Duration::from_millis(10)), vec![long]);
+    }
+}
@@ -1,41 +1,99 @@
 use super::super::Klondike;
-use super::klondike_repository::KlondikeRepository;
+use super::klondike_repository::{KlondikeRepository, GameTask, GameView, SharedGame};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
 use uuid::Uuid;
 
 
+/// Kind of ids given to the saved games
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IdKind {
+    /// Random UUIDs, hard to guess
+    Uuid,
+    /// Numbers starting on 1, easy to type and share
+    Sequential,
+}
+
+/// Keeps each game behind its own lock, see shared_game
 pub struct KlondikeHashMapRepository {
-    games: HashMap<String, Klondike>,
+    games: HashMap<String, SharedGame>,
+    ids: IdKind,
+    /// Last number used as id when they are sequential
+    last_id: u64,
 }
 
 /// Simple implementation of KlondikeRepository using Hashmap.
 impl KlondikeRepository for KlondikeHashMapRepository {
 
     fn save(&mut self, klondike: Klondike) -> String {
-        let my_uuid = Uuid::new_v4();
-        let uuid = format!("{}", my_uuid);
+        let id = match self.ids {
+            IdKind::Uuid => format!("{}", Uuid::new_v4()),
+            IdKind::Sequential => {
+                self.last_id += 1;
+                self.last_id.to_string()
+            }
+        };
 
-        self.update(uuid.clone(), klondike);
+        self.update(id.clone(), klondike);
 
-        uuid
+        id
     }
 
     fn update(&mut self, id: String, klondike: Klondike) {
-        self.games.insert(id, klondike);
+        // Games added with their ids, like the ones loaded from a file,
+        // must not get them reused
+        if let (IdKind::Sequential, Ok(number)) = (self.ids, id.parse::<u64>()) {
+            self.last_id = self.last_id.max(number);
+        }
+        self.games.insert(id, Arc::new(Mutex::new(klondike)));
     }
 
     fn get(&self, id: &String) -> Option<Klondike> {
-        self.games.get(id).map(|x| (*x).clone())
+        self.games.get(id).map(|x| lock(x).clone())
     }
 
     fn delete(&mut self, id: &String) -> Option<Klondike> {
-        self.games.remove(id)
+        self.games.remove(id).map(|game| match Arc::try_unwrap(game) {
+            Ok(game) => game.into_inner().unwrap_or_else(PoisonError::into_inner),
+            // Still used by someone else
+            Err(game) => lock(&game).clone(),
+        })
+    }
+
+    fn count(&self) -> usize {
+        self.games.len()
+    }
+
+    fn list_ids(&self) -> Vec<String> {
+        self.games.keys().cloned().collect()
+    }
+
+    fn with_mut<'a>(&mut self, id: &String, f: GameTask<'a>) -> bool {
+        self.games.get(id).map(|x| f(&mut lock(x))).is_some()
+    }
+
+    fn view<'a>(&self, id: &String, f: GameView<'a>) -> bool {
+        self.games.get(id).map(|x| f(&lock(x))).is_some()
+    }
+
+    fn shared_game(&self, id: &String) -> Option<SharedGame> {
+        self.games.get(id).cloned()
     }
 }
 
+/// A task failing over a game doesn't keep it from being used again
+fn lock(game: &SharedGame) -> std::sync::MutexGuard<'_, Klondike> {
+    game.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 impl KlondikeHashMapRepository {
     pub fn new() -> Self {
-        KlondikeHashMapRepository { games: HashMap::new() }
+        KlondikeHashMapRepository::new_with_ids(IdKind::Uuid)
+    }
+
+    /// Creates a repository giving the saved games the given kind of ids
+    pub fn new_with_ids(ids: IdKind) -> Self {
+        KlondikeHashMapRepository { games: HashMap::new(), ids, last_id: 0 }
     }
 }
 
@@ -53,4 +111,70 @@ mod tests {
     fn delete_hashmap() {
         delete(&mut KlondikeHashMapRepository::new());
     }
+
+    #[test]
+    fn count_hashmap() {
+        count(&mut KlondikeHashMapRepository::new());
+    }
+
+    #[test]
+    fn with_mut_view_hashmap() {
+        with_mut_view(&mut KlondikeHashMapRepository::new());
+    }
+
+    #[test]
+    fn list_ids_hashmap() {
+        list_ids(&mut KlondikeHashMapRepository::new());
+    }
+
+    #[test]
+    fn touch_hashmap() {
+        touch(&mut KlondikeHashMapRepository::new());
+    }
+
+    #[test]
+    fn shared_game_hashmap() {
+        let mut repo = KlondikeHashMapRepository::new();
+        let id = repo.save(Klondike::new_with_seed(1));
+
+        // Changing it changes the stored game
+        let game = repo.shared_game(&id).unwrap();
+        game.lock().unwrap().take();
+        assert_eq!(repo.get(&id).map(|x| x.replay().len()), Some(1));
+        assert!(repo.shared_game(&String::from("invalid id")).is_none());
+
+        // Deleting it still gives the game
+        assert_eq!(repo.delete(&id).map(|x| x.replay().len()), Some(1));
+        assert_eq!(game.lock().unwrap().replay().len(), 1);
+    }
+
+    #[test]
+    fn save_update_get_sequential() {
+        save_update_get(&mut KlondikeHashMapRepository::new_with_ids(IdKind::Sequential));
+    }
+
+    #[test]
+    fn delete_sequential() {
+        delete(&mut KlondikeHashMapRepository::new_with_ids(IdKind::Sequential));
+    }
+
+    #[test]
+    fn sequential_ids() {
+        let mut repo = KlondikeHashMapRepository::new_with_ids(IdKind::Sequential);
+        let id1 = repo.save(Klondike::new());
+        let id2 = repo.save(Klondike::new());
+        assert_eq!((id1.as_str(), id2.as_str()), ("1", "2"));
+
+        // Ids of deleted games aren't given again
+        repo.delete(&id2);
+        assert_eq!(repo.save(Klondike::new()), "3");
+        assert!(repo.get(&id1).is_some());
+
+        assert_eq!(repo.save(Klondike::new()).len(), 1);
+
+        // Nor the ones of games added with their ids
+        repo.update(String::from("10"), Klondike::new());
+        assert_eq!(repo.save(Klondike::new()), "11");
+        assert_eq!(KlondikeHashMapRepository::new().save(Klondike::new()).len(), 36);
+    }
 }
\ No newline at end of file
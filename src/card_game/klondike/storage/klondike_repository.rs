@@ -1,5 +1,16 @@
 use mockall::automock;
 use super::super::Klondike;
+use std::sync::{Arc, Mutex};
+
+/// Task run over a saved game in place. Aliased as automock can't
+/// mock methods taking Fn objects directly.
+pub type GameTask<'a> = &'a mut dyn FnMut(&mut Klondike);
+
+/// Task run over a saved game without changing it
+pub type GameView<'a> = &'a mut dyn FnMut(&Klondike);
+
+/// Saved game behind its own lock
+pub type SharedGame = Arc<Mutex<Klondike>>;
 
 /// Implementations of storage systems for Klondike games 
 /// should implement this trait.
@@ -19,6 +30,50 @@ pub trait KlondikeRepository {
     /// Returns the removed element
     fn delete(&mut self, id: &String) -> Option<Klondike>;
 
+    /// Number of games currently stored
+    fn count(&self) -> usize;
+
+    /// Ids of all the games currently stored
+    fn list_ids(&self) -> Vec<String>;
+
+    /// Runs f over the saved game in place, keeping the changes.
+    /// Returns false if there is no game with that id.
+    /// By default the game is got, changed and updated.
+    fn with_mut<'a>(&mut self, id: &String, f: GameTask<'a>) -> bool {
+        match self.get(id) {
+            Some(mut klondike) => {
+                f(&mut klondike);
+                self.update(id.clone(), klondike);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs f over the saved game without changing it.
+    /// Returns false if there is no game with that id.
+    fn view<'a>(&self, id: &String, f: GameView<'a>) -> bool {
+        match self.get(id) {
+            Some(klondike) => { f(&klondike); true }
+            None => false,
+        }
+    }
+
+    /// Counts as an access to the game, keeping it alive on repositories
+    /// deleting the games not accessed for a while, without reading it.
+    /// Returns false if there is no game with that id.
+    fn touch(&mut self, id: &String) -> bool {
+        self.view(id, &mut |_x| ())
+    }
+
+    /// The saved game itself, on repositories keeping each game behind
+    /// its own lock, so it can be changed in place without holding the
+    /// repository. It doesn't count as an access. None on the rest of
+    /// repositories, or if there is no game with that id.
+    fn shared_game(&self, _id: &String) -> Option<SharedGame> {
+        None
+    }
+
 }
 
 /// Test that should be passed by any implementation of KlondikeRepository
@@ -71,4 +126,60 @@ pub mod test {
         assert_eq!(get2.map(|x| x.get_status()), Some(status2));        
     }
 
+    pub fn count(repo: &mut dyn KlondikeRepository) {
+        assert_eq!(repo.count(), 0);
+
+        let id1 = repo.save(Klondike::new());
+        repo.save(Klondike::new());
+        assert_eq!(repo.count(), 2);
+
+        repo.update(id1.clone(), Klondike::new());
+        assert_eq!(repo.count(), 2);
+
+        repo.delete(&id1);
+        assert_eq!(repo.count(), 1);
+    }
+
+    pub fn with_mut_view(repo: &mut dyn KlondikeRepository) {
+        let klondike = Klondike::new();
+        let id = repo.save(klondike.clone());
+
+        assert!(repo.with_mut(&id, &mut |x| { x.take(); }));
+        let mut replay = Vec::new();
+        assert!(repo.view(&id, &mut |x| replay = x.replay()));
+        assert_eq!(replay.len(), 1);
+        assert_eq!(repo.get(&id).map(|x| x.replay().len()), Some(1));
+
+        let invalid = String::from("invalid id");
+        assert!(!repo.with_mut(&invalid, &mut |_x| panic!("No game to change")));
+        assert!(!repo.view(&invalid, &mut |_x| panic!("No game to view")));
+        assert!(repo.get(&invalid).is_none());
+    }
+
+    pub fn list_ids(repo: &mut dyn KlondikeRepository) {
+        assert!(repo.list_ids().is_empty());
+
+        let id1 = repo.save(Klondike::new());
+        let id2 = repo.save(Klondike::new());
+        let ids = repo.list_ids();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&id1));
+        assert!(ids.contains(&id2));
+
+        repo.delete(&id1);
+        assert_eq!(repo.list_ids(), vec![id2]);
+    }
+
+    pub fn touch(repo: &mut dyn KlondikeRepository) {
+        let klondike = Klondike::new();
+        let id = repo.save(klondike.clone());
+
+        assert!(repo.touch(&id));
+        assert_eq!(repo.get(&id), Some(klondike));
+        assert!(!repo.touch(&String::from("invalid id")));
+
+        repo.delete(&id);
+        assert!(!repo.touch(&id));
+    }
+
 }
\ No newline at end of file
@@ -1,3 +1,7 @@
 pub mod klondike_repository;
 pub mod hashmap_repository;
 pub mod cleanup_wrapper;
+pub mod bounded_wrapper;
+pub mod snapshot_wrapper;
+pub mod shared_repository;
+pub mod periodic_task;
@@ -0,0 +1,68 @@
+use std::sync::mpsc::{self, Sender, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use clokwerk::{Scheduler, TimeUnits};
+use std::time::Duration;
+use std::marker::Send;
+
+/// Thread running a task every interval, used by the wrappers doing work
+/// in the background. The thread is stopped when dropped, waiting for the
+/// running task to end.
+pub struct PeriodicTask {
+    stop: Option<Sender<()>>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl PeriodicTask {
+    /// Starts running the task every interval. The scheduler works with
+    /// whole seconds, so shorter intervals run it every second.
+    pub fn start<F: FnMut() + Send + 'static>(interval: Duration, task: F) -> PeriodicTask {
+        let mut scheduler = Scheduler::new();
+        let seconds = interval.as_secs().max(1) as u32;
+        scheduler.every(seconds.seconds()).run(task);
+
+        // Waiting on the channel instead of sleeping, so the thread
+        // ends as soon as the sender is dropped
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread_handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                scheduler.run_pending();
+            }
+        });
+
+        PeriodicTask {
+            stop: Some(stop),
+            thread_handle: Some(thread_handle),
+        }
+    }
+}
+
+impl Drop for PeriodicTask {
+
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.thread_handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn runs_until_dropped() {
+        let runs = Arc::new(Mutex::new(0));
+        let task_runs = Arc::clone(&runs);
+        let task = PeriodicTask::start(Duration::from_millis(100), move || *task_runs.lock().unwrap() += 1);
+
+        thread::sleep(Duration::from_millis(2500));
+        drop(task);
+        let done = *runs.lock().unwrap();
+        assert!(done >= 1);
+
+        thread::sleep(Duration::from_millis(1500));
+        assert_eq!(*runs.lock().unwrap(), done);
+    }
+}
@@ -0,0 +1,324 @@
+use super::super::Klondike;
+use super::klondike_repository::{KlondikeRepository, SharedGame};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+
+/// Repository shared between threads where each game has its own lock,
+/// so operations on different games run in parallel while the ones
+/// on the same game are done one after the other.
+pub struct SharedRepository {
+    repo: RwLock<Box<dyn KlondikeRepository + Send + Sync>>,
+    locks: GameLocks,
+}
+
+/// Lock of each game in use, shared with whatever else changes or
+/// deletes the games of the repository, like its cleanup thread
+#[derive(Clone, Default)]
+pub struct GameLocks(Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>);
+
+impl GameLocks {
+    pub fn new() -> GameLocks {
+        GameLocks::default()
+    }
+
+    /// Runs f holding the lock of the given id. The lock is dropped
+    /// once nobody else is waiting for it. A panic in f doesn't keep the
+    /// lock from being taken again, as it guards no data.
+    pub fn locked<R, F: FnOnce() -> R>(&self, id: &str, f: F) -> R {
+        let lock = {
+            let mut locks = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            Arc::clone(locks.entry(id.to_string()).or_insert_with(|| Arc::new(Mutex::new(()))))
+        };
+
+        let result = {
+            let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+            f()
+        };
+
+        let mut locks = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        // Only the map and this function hold it
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(id);
+        }
+
+        result
+    }
+}
+
+impl SharedRepository {
+    pub fn new<T: KlondikeRepository + Send + Sync + 'static>(repo: T) -> SharedRepository {
+        SharedRepository::new_with_locks(repo, GameLocks::new())
+    }
+
+    /// Creates the repository taking the locks of the games from the
+    /// given ones, so others using them wait for the games in use
+    pub fn new_with_locks<T: KlondikeRepository + Send + Sync + 'static>(repo: T, locks: GameLocks)
+            -> SharedRepository {
+        SharedRepository {
+            repo: RwLock::new(Box::new(repo)),
+            locks,
+        }
+    }
+
+    pub fn save(&self, klondike: Klondike) -> String {
+        self.repo.write().unwrap().save(klondike)
+    }
+
+    /// Runs the task over the game with the given id, changing it in
+    /// place. None if there is no such game, or it was deleted by its
+    /// repository while the task ran.
+    /// When the repository keeps each game behind its own lock, only the
+    /// locks of the game are held while the task runs, so the tasks over
+    /// other games don't wait for it. Otherwise the whole repository is.
+    /// A failed task leaves the game as it was when it failed.
+    pub fn with_game<R, F: FnOnce(&mut Klondike) -> R>(&self, id: &str, task: F) -> Option<R> {
+        self.locks.locked(id, || match self.shared_game(id) {
+            Some(game) => {
+                let result = task(&mut lock(&game));
+                // Counts as an access, as with_mut does
+                if self.repo.write().unwrap().touch(&id.to_string()) { Some(result) } else { None }
+            }
+            None => {
+                let mut task = Some(task);
+                let mut result = None;
+                self.repo.write().unwrap().with_mut(&id.to_string(), &mut |x| {
+                    result = task.take().map(|task| task(x));
+                });
+                result
+            }
+        })
+    }
+
+    /// Runs the task over the game with the given id without changing
+    /// nor copying it. None if there is no such game.
+    /// It waits for any task running over the game, but not the rest
+    /// of the repository when it keeps each game behind its own lock.
+    pub fn view<R, F: FnOnce(&Klondike) -> R>(&self, id: &str, task: F) -> Option<R> {
+        if let Some(game) = self.shared_game(id) {
+            let result = task(&lock(&game));
+            // Counts as an access, as view does
+            self.repo.read().unwrap().view(&id.to_string(), &mut |_x| ());
+            return Some(result);
+        }
+
+        let mut task = Some(task);
+        let mut result = None;
+        self.repo.read().unwrap().view(&id.to_string(), &mut |x| {
+            result = task.take().map(|task| task(x));
+        });
+        result
+    }
+
+    /// Gets a copy of the game with the given id
+    pub fn get(&self, id: &str) -> Option<Klondike> {
+        self.view(id, Klondike::clone)
+    }
+
+    /// Removes the game, waiting for any task running over it
+    pub fn delete(&self, id: &str) -> Option<Klondike> {
+        self.locks.locked(id, || self.repo.write().unwrap().delete(&id.to_string()))
+    }
+
+    /// Counts as an access to the game without reading it, waiting for
+    /// any task running over it. Returns false if there is no such game.
+    pub fn touch(&self, id: &str) -> bool {
+        self.locks.locked(id, || self.repo.write().unwrap().touch(&id.to_string()))
+    }
+
+    pub fn count(&self) -> usize {
+        self.repo.read().unwrap().count()
+    }
+
+    pub fn list_ids(&self) -> Vec<String> {
+        self.repo.read().unwrap().list_ids()
+    }
+
+    /// The game behind its own lock, releasing the repository
+    fn shared_game(&self, id: &str) -> Option<SharedGame> {
+        self.repo.read().unwrap().shared_game(&id.to_string())
+    }
+}
+
+/// A failed task doesn't keep the game from being used again
+fn lock(game: &SharedGame) -> MutexGuard<'_, Klondike> {
+    game.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::hashmap_repository::KlondikeHashMapRepository;
+    use super::super::klondike_repository::MockKlondikeRepository;
+    use crate::card_game::klondike::{CardHolder, KlondikeAction};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn with_game_updates() {
+        let repo = SharedRepository::new(KlondikeHashMapRepository::new());
+        let id = repo.save(Klondike::new_with_seed(1));
+
+        assert_eq!(repo.with_game(&id, |x| { x.take(); x.replay().len() }), Some(1));
+        assert_eq!(repo.get(&id).map(|x| x.replay().len()), Some(1));
+        assert_eq!(repo.with_game("invalid id", |x| x.take()), None);
+
+        assert!(repo.delete(&id).is_some());
+        assert_eq!(repo.count(), 0);
+        assert!(repo.locks.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn view_and_with_game_use_the_stored_game() {
+        let mut delegate = MockKlondikeRepository::new();
+        delegate.expect_get().times(0);
+        delegate.expect_update().times(0);
+        delegate.expect_shared_game().returning(|_id| None);
+        delegate.expect_view()
+                .returning(|id, f| { if id == "xxxx" { f(&Klondike::new_with_seed(1)) }; id == "xxxx" });
+        delegate.expect_with_mut()
+                .returning(|id, f| { if id == "xxxx" { f(&mut Klondike::new_with_seed(1)) }; id == "xxxx" });
+        let repo = SharedRepository::new(delegate);
+
+        assert_eq!(repo.view("xxxx", |x| x.get_status()), Some(Klondike::new_with_seed(1).get_status()));
+        assert_eq!(repo.view("yyyy", |x| x.get_status()), None);
+        assert_eq!(repo.with_game("xxxx", |x| { x.take(); x.replay().len() }), Some(1));
+        assert_eq!(repo.with_game("yyyy", |x| x.take()), None);
+    }
+
+    #[test]
+    fn tasks_dont_block_other_games() {
+        let repo = Arc::new(SharedRepository::new(KlondikeHashMapRepository::new()));
+        let busy = repo.save(Klondike::new_with_seed(1));
+        let other = repo.save(Klondike::new_with_seed(2));
+
+        let (started, wait_started) = mpsc::channel();
+        let (finish, wait_finish) = mpsc::channel::<()>();
+        let task_repo = Arc::clone(&repo);
+        let task_id = busy.clone();
+        let handle = thread::spawn(move || {
+            task_repo.with_game(&task_id, |x| {
+                x.take();
+                started.send(()).unwrap();
+                wait_finish.recv().unwrap();
+            })
+        });
+        wait_started.recv().unwrap();
+
+        // The rest of games can be used
+        assert!(repo.list_ids().contains(&busy));
+        assert_eq!(repo.with_game(&other, |x| { x.take(); x.replay().len() }), Some(1));
+        let new = repo.save(Klondike::new_with_seed(3));
+        assert!(repo.delete(&new).is_some());
+        assert_eq!(repo.count(), 2);
+
+        finish.send(()).unwrap();
+        assert_eq!(handle.join().unwrap(), Some(()));
+        assert_eq!(repo.view(&busy, |x| x.replay().len()), Some(1));
+    }
+
+    #[test]
+    fn touch_waits_for_the_task() {
+        let repo = Arc::new(SharedRepository::new(KlondikeHashMapRepository::new()));
+        let id = repo.save(Klondike::new_with_seed(1));
+
+        let (started, wait_started) = mpsc::channel();
+        let (finish, wait_finish) = mpsc::channel::<()>();
+        let task_repo = Arc::clone(&repo);
+        let task_id = id.clone();
+        let handle = thread::spawn(move || {
+            task_repo.with_game(&task_id, |x| {
+                x.take();
+                started.send(()).unwrap();
+                wait_finish.recv().unwrap();
+            })
+        });
+        wait_started.recv().unwrap();
+
+        let (touched, wait_touched) = mpsc::channel();
+        let touch_repo = Arc::clone(&repo);
+        let touch_id = id.clone();
+        let touching = thread::spawn(move || touched.send(touch_repo.touch(&touch_id)).unwrap());
+        assert!(wait_touched.recv_timeout(Duration::from_millis(200)).is_err());
+
+        finish.send(()).unwrap();
+        assert!(wait_touched.recv().unwrap());
+        handle.join().unwrap();
+        touching.join().unwrap();
+        assert!(!repo.touch("invalid id"));
+    }
+
+    #[test]
+    fn with_game_after_a_panic() {
+        let repo = Arc::new(SharedRepository::new(KlondikeHashMapRepository::new()));
+        let id = repo.save(Klondike::new_with_seed(1));
+
+        let task_repo = Arc::clone(&repo);
+        let task_id = id.clone();
+        let panicked = thread::spawn(move || {
+            task_repo.with_game(&task_id, |x| { x.take(); panic!("task failed") })
+        }).join();
+        assert!(panicked.is_err());
+
+        // The game is kept as the failed task left it
+        assert_eq!(repo.with_game(&id, |x| { x.take(); x.replay().len() }), Some(2));
+    }
+
+    #[test]
+    fn lock_taken_after_a_panic() {
+        let locks = GameLocks::new();
+        let panicking = locks.clone();
+        assert!(thread::spawn(move || panicking.locked("xxxx", || panic!("task failed"))).join().is_err());
+
+        assert_eq!(locks.locked("xxxx", || 1), 1);
+        assert!(locks.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn concurrent_games() {
+        const THREADS: usize = 8;
+        const TAKES: usize = 50;
+
+        let repo = Arc::new(SharedRepository::new(KlondikeHashMapRepository::new()));
+        let ids: Vec<String> = (0..THREADS).map(|_i| repo.save(Klondike::new())).collect();
+
+        let handles: Vec<thread::JoinHandle<()>> = ids.iter().cloned()
+            .map(|id| {
+                let repo = Arc::clone(&repo);
+                thread::spawn(move || {
+                    for _i in 0..TAKES {
+                        repo.with_game(&id, |x| {
+                            x.take();
+                            x.send_home(CardHolder::DECK);
+                        }).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        // The same game can also be used from several threads
+        let shared_id = ids[0].clone();
+        let others: Vec<thread::JoinHandle<()>> = (0..THREADS)
+            .map(|_i| {
+                let repo = Arc::clone(&repo);
+                let id = shared_id.clone();
+                thread::spawn(move || {
+                    repo.with_game(&id, |x| x.take_n(TAKES as u32)).unwrap();
+                })
+            })
+            .collect();
+
+        for handle in handles.into_iter().chain(others) {
+            handle.join().unwrap();
+        }
+
+        for (i, id) in ids.iter().enumerate() {
+            let takes = repo.get(id).unwrap().replay().iter()
+                .filter(|x| **x == KlondikeAction::TAKE)
+                .count();
+            let expected = if i == 0 { TAKES * (THREADS + 1) } else { TAKES };
+            assert_eq!(takes, expected);
+        }
+        assert!(repo.locks.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use super::super::Klondike;
+use super::klondike_repository::*;
+use super::periodic_task::PeriodicTask;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Arc};
+use std::time::Duration;
+use std::marker::Send;
+
+/// Wrapper in order to keep the games of an in-memory repository on disk.
+/// Every game is written to a single JSON file periodically, and when the
+/// repository is dropped. The games found on that file are loaded on creation.
+pub struct SnapshottingRepository<T: KlondikeRepository + Send + 'static> {
+    delegate: Arc<Mutex<T>>,
+    path: PathBuf,
+    writer: Option<PeriodicTask>,
+}
+
+impl<T: KlondikeRepository + Send + 'static> SnapshottingRepository<T> {
+    /// Creates the repository, adding to the delegate the games on the
+    /// given file, if it exists, and writing them back every interval.
+    /// The scheduler works with whole seconds, so shorter intervals write
+    /// every second. A zero interval is rejected as invalid input.
+    pub fn new(mut delegate: T, path: impl Into<PathBuf>, interval: Duration)
+            -> io::Result<SnapshottingRepository<T>> {
+
+        if interval.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "The snapshot interval can't be zero"));
+        }
+
+        let path = path.into();
+        for (id, klondike) in read_snapshot(&path)? {
+            delegate.update(id, klondike);
+        }
+
+        let delegate = Arc::new(Mutex::new(delegate));
+        let sch_delegate = Arc::clone(&delegate);
+        let sch_path = path.clone();
+
+        let writer = PeriodicTask::start(interval, move || {
+            // A failed write is retried on the next one
+            write_snapshot(&*sch_delegate.lock().unwrap(), &sch_path).ok();
+        });
+
+        Ok(SnapshottingRepository {
+            delegate,
+            path,
+            writer: Some(writer),
+        })
+    }
+
+    /// Writes every game to the file now, without waiting for the timer
+    pub fn flush(&self) -> io::Result<()> {
+        write_snapshot(&*self.delegate.lock().unwrap(), &self.path)
+    }
+}
+
+/// Games on the snapshot file with their ids. Empty if there is no file yet.
+fn read_snapshot(path: &Path) -> io::Result<Vec<(String, Klondike)>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let games: HashMap<String, Vec<u8>> = serde_json::from_slice(&contents)?;
+    games.into_iter()
+        .map(|(id, bytes)| match Klondike::from_bytes(&bytes) {
+            Ok(klondike) => Ok((id, klondike)),
+            Err(_e) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Invalid game {} on {}", id, path.display()))),
+        })
+        .collect()
+}
+
+/// Writes the games of the repository in their compact form, replacing
+/// the file only once all of them are written
+fn write_snapshot<T: KlondikeRepository + ?Sized>(repo: &T, path: &Path) -> io::Result<()> {
+    let mut games = HashMap::new();
+    for id in repo.list_ids() {
+        repo.view(&id, &mut |x| { games.insert(id.clone(), x.to_bytes()); });
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, serde_json::to_vec(&games)?)?;
+    fs::rename(&tmp, path)
+}
+
+impl<T: KlondikeRepository + Send + 'static> KlondikeRepository for SnapshottingRepository<T> {
+
+    fn save(&mut self, klondike: Klondike) -> String {
+        self.delegate.lock().unwrap().save(klondike)
+    }
+
+    fn update(&mut self, id: String, klondike: Klondike) {
+        self.delegate.lock().unwrap().update(id, klondike)
+    }
+
+    fn get(&self, id: &String) -> Option<Klondike> {
+        self.delegate.lock().unwrap().get(id)
+    }
+
+    fn delete(&mut self, id: &String) -> Option<Klondike> {
+        self.delegate.lock().unwrap().delete(id)
+    }
+
+    fn count(&self) -> usize {
+        self.delegate.lock().unwrap().count()
+    }
+
+    fn list_ids(&self) -> Vec<String> {
+        self.delegate.lock().unwrap().list_ids()
+    }
+
+    fn with_mut<'a>(&mut self, id: &String, f: GameTask<'a>) -> bool {
+        self.delegate.lock().unwrap().with_mut(id, f)
+    }
+
+    fn view<'a>(&self, id: &String, f: GameView<'a>) -> bool {
+        self.delegate.lock().unwrap().view(id, f)
+    }
+
+    fn touch(&mut self, id: &String) -> bool {
+        self.delegate.lock().unwrap().touch(id)
+    }
+
+    fn shared_game(&self, id: &String) -> Option<SharedGame> {
+        self.delegate.lock().unwrap().shared_game(id)
+    }
+}
+
+impl<T: KlondikeRepository + Send + 'static> Drop for SnapshottingRepository<T> {
+
+    fn drop(&mut self) {
+        // Stopped first, so the last write is this one
+        self.writer.take();
+        self.flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::hashmap_repository::KlondikeHashMapRepository;
+    use super::super::klondike_repository::test::*;
+    use std::{env, process, thread};
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("klondike-{}-{}.json", name, process::id()))
+    }
+
+    fn snapshotting(path: &Path, interval: Duration) -> SnapshottingRepository<KlondikeHashMapRepository> {
+        SnapshottingRepository::new(KlondikeHashMapRepository::new(), path, interval).unwrap()
+    }
+
+    /// Runs a conformance test over a repository whose file is removed after it
+    fn run_on_snapshotting(name: &str, test: fn(&mut dyn KlondikeRepository)) {
+        let path = snapshot_path(name);
+        test(&mut snapshotting(&path, Duration::from_secs(3600)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_update_get_snapshotting() {
+        run_on_snapshotting("save_update_get", save_update_get);
+    }
+
+    #[test]
+    fn delete_snapshotting() {
+        run_on_snapshotting("delete", delete);
+    }
+
+    #[test]
+    fn count_snapshotting() {
+        run_on_snapshotting("count", count);
+    }
+
+    #[test]
+    fn with_mut_view_snapshotting() {
+        run_on_snapshotting("with_mut_view", with_mut_view);
+    }
+
+    #[test]
+    fn list_ids_snapshotting() {
+        run_on_snapshotting("list_ids", list_ids);
+    }
+
+    #[test]
+    fn touch_snapshotting() {
+        run_on_snapshotting("touch", touch);
+    }
+
+    #[test]
+    fn flush_and_reload() {
+        let path = snapshot_path("reload");
+        let mut repo = snapshotting(&path, Duration::from_secs(3600));
+        let mut klondike = Klondike::new_with_seed(4);
+        klondike.take_n(2);
+        let id1 = repo.save(klondike.clone());
+        let id2 = repo.save(Klondike::new_with_seed(5));
+        repo.flush().unwrap();
+
+        // The file is read by a new repository, even with the old one alive
+        let reloaded = snapshotting(&path, Duration::from_secs(3600));
+        assert_eq!(reloaded.count(), 2);
+        let loaded = reloaded.get(&id1).unwrap();
+        assert_eq!(loaded.get_status(), klondike.get_status());
+        assert_eq!(loaded.replay(), klondike.replay());
+        assert_eq!(reloaded.get(&id2).map(|x| x.get_status()),
+            Some(Klondike::new_with_seed(5).get_status()));
+
+        drop(reloaded);
+        drop(repo);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn written_on_timer_and_drop() {
+        let path = snapshot_path("timer");
+        let mut repo = snapshotting(&path, Duration::from_secs(1));
+        let id = repo.save(Klondike::new_with_seed(6));
+
+        thread::sleep(Duration::from_millis(2500));
+        let games = read_snapshot(&path).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].0, id);
+
+        // Changes after the last write are kept when dropped
+        repo.with_mut(&id, &mut |x| x.take());
+        drop(repo);
+        let games = read_snapshot(&path).unwrap();
+        assert_eq!(games[0].1.replay().len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_snapshot() {
+        let path = snapshot_path("invalid");
+        fs::write(&path, r#"{"xxxx":[1,2,3]}"#).unwrap();
+        let result = SnapshottingRepository::new(KlondikeHashMapRepository::new(), &path,
+            Duration::from_secs(3600));
+        assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn zero_interval() {
+        let path = snapshot_path("zero");
+        let result = SnapshottingRepository::new(KlondikeHashMapRepository::new(), &path, Duration::ZERO);
+        assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+        assert!(!path.exists());
+    }
+}
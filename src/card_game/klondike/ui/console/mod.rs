@@ -1,19 +1,49 @@
-use crate::card_game::american_cards::*;
 use crate::card_game::klondike::*;
 use std::io::{self, BufRead};
 use ansi_term::Style;
 use crate::card_game::klondike::ui::get_card_holder;
+use crate::card_game::klondike::solver;
+use std::thread;
+use std::time::Duration;
 
-pub fn game() {
-    let mut klondike = Klondike::new();
+/// Time between the actions shown by the solve command
+const AUTOPLAY_DELAY: Duration = Duration::from_millis(500);
+
+/// Plays a game on the console, created with the given configuration.
+pub fn game(config: KlondikeConfig) {
+    let mut klondike = match Klondike::new_with_config(config) {
+        Ok(klondike) => klondike,
+        Err(e) => {
+            println!("Invalid game configuration: {:?}", e);
+            return;
+        }
+    };
 
     let stdin = io::stdin();
-    let mut iterator = stdin.lock().lines();
+    play(&mut klondike, stdin.lock());
+}
+
+/// Runs the commands read from input until the exit command or the end
+/// of the input.
+fn play<R: BufRead>(klondike: &mut Klondike, input: R) {
+    let mut iterator = input.lines();
     
     loop {
-        print_status(&klondike);
+        print_status(klondike);
+        if klondike.is_trivially_winnable() {
+            println!("Only sending the cards home is left. {}: Finish the game",
+                Style::new().bold().paint("F"));
+            println!();
+        }
 
-        let line = iterator.next().unwrap().unwrap();
+        let line = match iterator.next() {
+            None => break,
+            Some(Err(e)) => {
+                println!("Error reading command: {}", e);
+                break;
+            }
+            Some(Ok(line)) => line,
+        };
         let mut part = line.split_whitespace();
 
         if let Some(cmd) = part.next() {
@@ -25,8 +55,8 @@ pub fn game() {
                     klondike.take(); 
                 }
                 "m" | "M" | /* these for the un*x gurus ;) */ "mv" | "MV" => {
-                    if let Some(origin) = get_card_holder(part.next()) {
-                        if let Some(destination) = get_card_holder(part.next()) {
+                    if let Some(origin) = get_holder_or_waste(part.next()) {
+                        if let Some(destination) = get_holder_or_waste(part.next()) {
                             if let Ok(number) = part.next().unwrap_or("1").parse::<u32>() {
                                 
                                 klondike.move_cards(origin, destination, number);
@@ -35,74 +65,218 @@ pub fn game() {
                     }
                 }
                 "p" | "P" => {
-                    if let Some(origin) = get_card_holder(part.next()) {
+                    if let Some(origin) = get_holder_or_waste(part.next()) {
                         klondike.to_pile(origin);
                     }
                 }
                 "u" | "U" => klondike.undo(),
-                _ =>{}
+                "n" | "N" => klondike.redeal(),
+                "sv" | "SV" | "solve" | "SOLVE" => autoplay(klondike, AUTOPLAY_DELAY),
+                "f" | "F" => {
+                    if klondike.is_trivially_winnable() {
+                        klondike.auto_moves(AutoPolicy::Aggressive);
+                    }
+                }
+                _ => {
+                    if let Some((origin, destination)) = parse_compact_move(cmd) {
+                        if let Ok(number) = part.next().unwrap_or("1").parse::<u32>() {
+                            klondike.move_cards(origin, destination, number);
+                        }
+                    }
+                }
             } 
         }
     }
 }
 
-fn print_status (klondike: &Klondike) {
-    let status = klondike.get_status();
-
-    println!("  P1    P2    P3    P4          D"); 
-    println!("[{}] [{}] [{}] [{}]       [{}] [{:^3}]", 
-        fmt_pile_card(status.piles[0].top_card),
-        fmt_pile_card(status.piles[1].top_card),
-        fmt_pile_card(status.piles[2].top_card),
-        fmt_pile_card(status.piles[3].top_card),
-        fmt_pile_card(status.deck.top_card_on_waste),
-        status.deck.cards_on_stock
-        );
-    
-    println!("");
-    println!("  F1    F2    F3    F4    F5    F6    F7"); 
- 
-    let max_num_cards = status.foundations.iter()
-        .map(|x| -> u32 {x.num_hidden + x.visible.len() as u32})
-        .max().unwrap();
-
-    for i in 0..max_num_cards {
-        for j in 0..7 {
-            print!("{} ", fmt_found_card(&status.foundations[j], i));
+/// Like get_card_holder, also accepting "w" for the waste of the deck
+fn get_holder_or_waste(str: Option<&str>) -> Option<CardHolder> {
+    match str {
+        Some("w") | Some("W") => Some(CardHolder::DECK),
+        _ => get_card_holder(str),
+    }
+}
+
+/// Splits moves written without spaces, like "wf2" or "f3f5", into
+/// their origin and destination
+fn parse_compact_move(cmd: &str) -> Option<(CardHolder, CardHolder)> {
+    // The destination starts on the first letter after the origin's one
+    let split = cmd.char_indices().skip(1).find(|(_i, c)| c.is_ascii_alphabetic())?.0;
+    let (origin, destination) = cmd.split_at(split);
+
+    Some((get_holder_or_waste(Some(origin))?, get_holder_or_waste(Some(destination))?))
+}
+
+/// Plays the rest of the game if it can be won, showing the board after
+/// each action. They can be undone afterwards like any other.
+fn autoplay(klondike: &mut Klondike, delay: Duration) {
+    let actions = match solver::solve(klondike) {
+        Some(actions) => actions,
+        None => {
+            println!("No way to win this game was found");
+            return;
         }
-        println!("");
+    };
+
+    for action in actions {
+        klondike.apply_replay(&[action]);
+        println!("{}", klondike.get_status());
+        thread::sleep(delay);
     }
+}
 
+fn print_status (klondike: &Klondike) {
+    println!("{}", klondike.get_status());
     println!("");
 
     let style = Style::new().bold();
     println!(
-        "Commands: {}: Exit {}: Take from stock {}: move cards {}: move cards to pile {}: Undo",
+        "Commands: {}: Exit {}: Take from stock {}: move cards ({} for short, {} being the waste) {}: move cards to pile {}: Undo {}: New game {}: Solve",
         style.paint("X"),
         style.paint("T"),
         style.paint("M <origin> <destination> [number of cards]"),
+        style.paint("<origin><destination> [number of cards]"),
+        style.paint("W"),
         style.paint("P <origin>"),
         style.paint("U"),
+        style.paint("N"),
+        style.paint("SV (or SOLVE)"),
         ); 
     println!("");
 
 }
 
-fn fmt_pile_card (card: Option<Card>) -> String {
-    format!("{}", match card {
-        None => "   ".to_string(),
-        Some(card) => format!("{}", card) 
-    })
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_game::klondike::test_common::*;
+
+    #[test]
+    fn play_until_end_of_input() {
+        let mut klondike = Klondike::new();
+        let status = klondike.get_status();
+
+        play(&mut klondike, "t\nt\nu\n\n".as_bytes());
+
+        assert_eq!(klondike.get_status().deck.cards_on_waste, status.deck.cards_on_waste + 1);
+    }
+
+    #[test]
+    fn play_until_exit() {
+        let mut klondike = Klondike::new();
+        let status = klondike.get_status();
+
+        play(&mut klondike, "x\nt\n".as_bytes());
+
+        assert_eq!(klondike.get_status(), status);
+    }
+
+    #[test]
+    fn compact_moves() {
+        assert_eq!(parse_compact_move("wf2"), Some((CardHolder::DECK, CardHolder::FOUNDATION(1))));
+        assert_eq!(parse_compact_move("f3f5"), Some((CardHolder::FOUNDATION(2), CardHolder::FOUNDATION(4))));
+        assert_eq!(parse_compact_move("F10p1"), Some((CardHolder::FOUNDATION(9), CardHolder::PILE(0))));
+        assert_eq!(parse_compact_move("dP4"), Some((CardHolder::DECK, CardHolder::PILE(3))));
+        assert_eq!(parse_compact_move("f1w"), Some((CardHolder::FOUNDATION(0), CardHolder::DECK)));
+
+        assert_eq!(parse_compact_move("w"), None);
+        assert_eq!(parse_compact_move("f3"), None);
+        assert_eq!(parse_compact_move("wf"), None);
+        assert_eq!(parse_compact_move("w1f2"), None);
+        assert_eq!(parse_compact_move("f3f5p1"), None);
+        assert_eq!(parse_compact_move("sv"), None);
+    }
+
+    #[test]
+    fn compact_and_spaced_moves_agree() {
+        let ace = Card { suit: CardSuit::CLUBS, rank: CardRank::ACE };
+        let two = Card { suit: CardSuit::CLUBS, rank: CardRank::TWO };
+        let layout = layout_with(vec![Vec::new(); 4], Vec::new(), vec![two, ace]);
+        let compact = Klondike::from_layout(&layout, 1).unwrap();
+        let mut spaced = compact.clone();
+        let mut compact = compact;
+
+        play(&mut compact, "wp1\nWP1\n".as_bytes());
+        play(&mut spaced, "m d p1
+m w P1
+".as_bytes());
+
+        assert_eq!(compact.get_status(), spaced.get_status());
+        assert_eq!(compact.count_cards(CardHolder::PILE(0)), 2);
+    }
+
+    #[test]
+    fn finish_trivially_winnable() {
+        // Each foundation has a run from a king down, the rest of the
+        // cards are home
+        let mut piles = vec![Vec::new(); 4];
+        let mut foundations = Vec::new();
+        for suit in CardSuit::iter() {
+            let cards: Vec<Card> = Card::full_deck().into_iter().filter(|x| x.suit == suit).collect();
+            piles[suit.to_u8() as usize] = cards[..9].to_vec();
+            foundations.push(FoundationLayout { hidden: Vec::new(), visible: Vec::new() });
+        }
+        let layout = |foundations: Vec<FoundationLayout>| layout_with(piles.clone(), foundations, Vec::new());
+        let run = |suits: [CardSuit; 4]| -> Vec<Card> {
+            [CardRank::KING, CardRank::QUEEN, CardRank::JACK, CardRank::TEN].iter().zip(suits.iter())
+                .map(|(rank, suit)| Card { suit: *suit, rank: *rank })
+                .collect()
+        };
+        use CardSuit::*;
+        foundations[0].visible = run([SPADES, HEARTS, CLUBS, DIAMONDS]);
+        foundations[1].visible = run([HEARTS, SPADES, DIAMONDS, CLUBS]);
+        foundations[2].visible = run([CLUBS, DIAMONDS, SPADES, HEARTS]);
+        foundations[3].visible = run([DIAMONDS, CLUBS, HEARTS, SPADES]);
+        let mut klondike = Klondike::from_layout(&layout(foundations.clone()), 1).unwrap();
+        assert!(klondike.is_trivially_winnable());
+
+        play(&mut klondike, "f\n".as_bytes());
+        assert!(klondike.is_won());
+        assert!(!klondike.is_trivially_winnable());
+
+        // A hidden card, or one left on the deck, may need other moves
+        let mut hidden = foundations.clone();
+        hidden[0].hidden = vec![hidden[0].visible.remove(0)];
+        assert!(!Klondike::from_layout(&layout(hidden), 1).unwrap().is_trivially_winnable());
+        let mut on_stock = foundations;
+        on_stock[0].visible.pop();
+        let mut klondike = Klondike::from_layout(&layout(on_stock), 1).unwrap();
+        assert!(!klondike.is_trivially_winnable());
+        let status = klondike.get_status();
+        play(&mut klondike, "f\n".as_bytes());
+        assert_eq!(klondike.get_status(), status);
+    }
+
+    #[test]
+    fn solve_command() {
+        let king = Card { suit: CardSuit::SPADES, rank: CardRank::KING };
+        let mut piles = vec![Vec::new(); 4];
+        for card in Card::full_deck().into_iter().filter(|x| *x != king) {
+            piles[card.suit.to_u8() as usize].push(card);
+        }
+        let layout = layout_with(piles, Vec::new(), Vec::new());
+
+        for command in ["sv", "SOLVE", "solve"] {
+            let mut klondike = Klondike::from_layout(&layout, 1).unwrap();
+            play(&mut klondike, format!("{}\n", command).as_bytes());
+            assert!(klondike.is_won(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn solve_and_undo() {
+        let mut piles = vec![Vec::new(); 4];
+        for card in Card::full_deck().into_iter().filter(|x| x.rank != CardRank::KING) {
+            piles[card.suit.to_u8() as usize].push(card);
+        }
+        let mut klondike = Klondike::from_layout(&layout_with(piles, Vec::new(), Vec::new()), 1).unwrap();
 
-fn fmt_found_card (foundation: &crate::card_game::klondike::foundation::FoundationStatus, index: u32) -> String {
+        autoplay(&mut klondike, Duration::from_millis(0));
+        assert!(klondike.is_won());
 
-    if index < foundation.num_hidden {
-        "[ X ]".to_string()
-    } else {
-        format!("{}", match foundation.visible.get((index - foundation.num_hidden) as usize) {
-            None => "     ".to_string(),
-            Some(card) => format!("[{}]", card) 
-        })
+        // The last king goes back to the waste
+        play(&mut klondike, "u\n".as_bytes());
+        assert!(!klondike.is_won());
+        assert_eq!(klondike.get_status().deck.cards_on_waste, 1);
     }
 }
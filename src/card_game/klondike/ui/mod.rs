@@ -4,22 +4,91 @@ pub mod web;
 use crate::card_game::klondike::*;
 
 fn get_card_holder (str: Option<&str>) -> Option<CardHolder> {
-    match str {
-        Some("d") | Some("D") => Some(CardHolder::DECK),
-
-        Some("p1") | Some("P1") => Some(CardHolder::PILE(0)),
-        Some("p2") | Some("P2") => Some(CardHolder::PILE(1)),
-        Some("p3") | Some("P3") => Some(CardHolder::PILE(2)),
-        Some("p4") | Some("P4") => Some(CardHolder::PILE(3)),
-
-        Some("f1") | Some("F1")  => Some(CardHolder::FOUNDATION(0)),
-        Some("f2") | Some("F2")  => Some(CardHolder::FOUNDATION(1)),
-        Some("f3") | Some("F3")  => Some(CardHolder::FOUNDATION(2)),
-        Some("f4") | Some("F4")  => Some(CardHolder::FOUNDATION(3)),
-        Some("f5") | Some("F5")  => Some(CardHolder::FOUNDATION(4)),
-        Some("f6") | Some("F6")  => Some(CardHolder::FOUNDATION(5)),
-        Some("f7") | Some("F7")  => Some(CardHolder::FOUNDATION(6)),
+    let str = str?;
+    let mut chars = str.chars();
+    let kind = chars.next()?.to_ascii_lowercase();
+    let suffix = chars.as_str();
 
+    if kind == 'd' {
+        return if suffix.is_empty() { Some(CardHolder::DECK) } else { None };
+    }
+
+    // Only plain digits are accepted, so "+1" or "p-1" are rejected
+    if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let number = suffix.parse::<u32>().ok()?;
+    if number == 0 {
+        return None;
+    }
+
+    match kind {
+        'p' => Some(CardHolder::PILE(number - 1)),
+        'f' => Some(CardHolder::FOUNDATION(number - 1)),
         _ => None
     }
 }
+
+/// Inverse of get_card_holder: returns the lowercase token that identifies
+/// the given card holder.
+pub fn card_holder_to_str (holder: CardHolder) -> String {
+    match holder {
+        CardHolder::DECK => "d".to_string(),
+        CardHolder::PILE(idx) => format!("p{}", idx + 1),
+        CardHolder::FOUNDATION(idx) => format!("f{}", idx + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_holder_to_str_round_trip() {
+        let mut holders = vec![CardHolder::DECK];
+        for i in 0..4 {
+            holders.push(CardHolder::PILE(i));
+        }
+        for i in 0..7 {
+            holders.push(CardHolder::FOUNDATION(i));
+        }
+
+        for holder in holders {
+            assert_eq!(get_card_holder(Some(&card_holder_to_str(holder))), Some(holder));
+        }
+    }
+
+    #[test]
+    fn get_card_holder_any_index() {
+        assert_eq!(get_card_holder(Some("d")), Some(CardHolder::DECK));
+        assert_eq!(get_card_holder(Some("D")), Some(CardHolder::DECK));
+        assert_eq!(get_card_holder(Some("P1")), Some(CardHolder::PILE(0)));
+        assert_eq!(get_card_holder(Some("p5")), Some(CardHolder::PILE(4)));
+        assert_eq!(get_card_holder(Some("p10")), Some(CardHolder::PILE(9)));
+        assert_eq!(get_card_holder(Some("F7")), Some(CardHolder::FOUNDATION(6)));
+        assert_eq!(get_card_holder(Some("f12")), Some(CardHolder::FOUNDATION(11)));
+    }
+
+    #[test]
+    fn get_card_holder_invalid() {
+        assert_eq!(get_card_holder(None), None);
+        assert_eq!(get_card_holder(Some("")), None);
+        assert_eq!(get_card_holder(Some("f0")), None);
+        assert_eq!(get_card_holder(Some("p0")), None);
+        assert_eq!(get_card_holder(Some("pp")), None);
+        assert_eq!(get_card_holder(Some("f")), None);
+        assert_eq!(get_card_holder(Some("p")), None);
+        assert_eq!(get_card_holder(Some("p+1")), None);
+        assert_eq!(get_card_holder(Some("d1")), None);
+        assert_eq!(get_card_holder(Some("x1")), None);
+        assert_eq!(get_card_holder(Some("p99999999999")), None);
+    }
+
+    #[test]
+    fn card_holder_to_str_tokens() {
+        assert_eq!(card_holder_to_str(CardHolder::DECK), "d");
+        assert_eq!(card_holder_to_str(CardHolder::PILE(2)), "p3");
+        assert_eq!(card_holder_to_str(CardHolder::FOUNDATION(0)), "f1");
+    }
+}
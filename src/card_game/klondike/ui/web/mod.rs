@@ -1,28 +1,174 @@
 use crate::card_game::klondike::*;
-use rocket::{State, Error, response};
+use rocket::{State, Error, Rocket, Build, response};
 use rocket::response::{Responder, Response};
-use rocket::request::Request;
+use rocket::request::{self, FromRequest, Outcome, Request};
 use rocket::http::{ContentType, Header, Status};
 use rocket::fairing::{Fairing, Info, Kind};
-use std::sync::{Mutex, Arc};
-use rocket::serde::json::Json;
-use crate::card_game::klondike::ui::get_card_holder;
+use rocket::serde::json::{self, Json};
+use crate::card_game::klondike::ui::{get_card_holder, card_holder_to_str};
 use serde::{Serialize, Deserialize};
 use crate::card_game::klondike::storage::cleanup_wrapper::{HashMapTimeoutRepository, KlondikeCleanUpRepository};
 use crate::card_game::klondike::storage::hashmap_repository::KlondikeHashMapRepository;
-use crate::card_game::klondike::storage::klondike_repository::KlondikeRepository;
+use crate::card_game::klondike::storage::shared_repository::SharedRepository;
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
+use std::env;
+use std::fmt;
+use chrono::{NaiveDate, Utc};
+
+mod openapi;
+
+/// Statuses kept for each game to compute diffs from
+const RECENT_STATUSES: usize = 8;
+/// Games with recent statuses kept, forgetting the least recently used
+/// one when exceeded
+const MAX_RECENT_GAMES: usize = 10_000;
+/// Most cards a move action can take, as there are no more in the game
+const MAX_MOVE_NUMBER: u32 = 52;
 
 struct KlondikeGames {
-    repo: Arc<Mutex<dyn KlondikeRepository + Send + 'static>>,
+    repo: SharedRepository,
+    /// Configuration used for every new game
+    config: KlondikeConfig,
+    /// Token required for administrative operations, taken from the 
+    /// ADMIN_TOKEN environment variable. They are disabled if not present.
+    admin_token: Option<String>,
+    recent: Mutex<RecentStatuses>,
+}
+
+impl KlondikeGames {
+    fn remember(&self, id: &str, status: &KlondikeStatus) {
+        self.recent.lock().unwrap().remember(id, status);
+    }
+
+    fn recent_status(&self, id: &str, version: u64) -> Option<KlondikeStatus> {
+        self.recent.lock().unwrap().get(id, version)
+    }
+
+    fn forget(&self, id: &str) {
+        self.recent.lock().unwrap().games.remove(id);
+    }
+}
+
+/// Last statuses sent for each game, the newest at the end, along with
+/// the order in which the games were last used. When there are too many
+/// games, the least recently used one is forgotten, like the ones that
+/// expired and are no longer asked for.
+struct RecentStatuses {
+    games: HashMap<String, (u64, Vec<KlondikeStatus>)>,
+    capacity: usize,
+    counter: u64,
+}
+
+impl RecentStatuses {
+    fn new(capacity: usize) -> RecentStatuses {
+        RecentStatuses { games: HashMap::new(), capacity, counter: 0 }
+    }
+
+    fn remember(&mut self, id: &str, status: &KlondikeStatus) {
+        if self.games.len() >= self.capacity && !self.games.contains_key(id) {
+            let oldest = self.games.iter()
+                .min_by_key(|(_id, (used, _statuses))| *used)
+                .map(|(id, _x)| id.clone());
+            if let Some(oldest) = oldest {
+                self.games.remove(&oldest);
+            }
+        }
+
+        self.counter += 1;
+        let (used, statuses) = self.games.entry(id.to_string()).or_default();
+        *used = self.counter;
+        if statuses.last().map(|x| x.version) != Some(status.version) {
+            statuses.push(status.clone());
+        }
+        if statuses.len() > RECENT_STATUSES {
+            statuses.remove(0);
+        }
+    }
+
+    fn get(&self, id: &str, version: u64) -> Option<KlondikeStatus> {
+        self.games.get(id)?.1.iter().find(|x| x.version == version).cloned()
+    }
+}
+
+/// Value of the X-Admin-Token header, if present
+struct AdminToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(AdminToken(req.headers().get_one("X-Admin-Token").map(|x| x.to_string())))
+    }
+}
+
+/// Value of the If-Match header, if present
+struct IfMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(IfMatch(req.headers().get_one("If-Match").map(|x| x.to_string())))
+    }
+}
+
+impl IfMatch {
+    /// Checks the header, if present, names the given version
+    fn matches(&self, version: u64) -> bool {
+        match &self.0 {
+            None => true,
+            Some(value) => value == "*" || *value == etag(version),
+        }
+    }
+}
+
+fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
 }
 
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct Action {
-    action: String,
+    action: ActionKind,
+    /// Origin, destination and number of cards of a move, ignored by
+    /// the other actions
     from: Option<String>,
     to: Option<String>,
-    number: Option<u32>
+    number: Option<u32>,
+    /// Sequence number set by the client; an action repeating the last
+    /// one received is not applied again
+    seq: Option<u64>
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ActionKind {
+    Take,
+    Undo,
+    Redo,
+    Move,
+    /// Sends home the cards that are safe to send
+    Auto,
+    /// Takes, and then sends home like auto
+    #[serde(rename = "take_auto")]
+    TakeAuto,
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ActionKind::Take => "take",
+            ActionKind::Undo => "undo",
+            ActionKind::Redo => "redo",
+            ActionKind::Move => "move",
+            ActionKind::Auto => "auto",
+            ActionKind::TakeAuto => "take_auto",
+        })
+    }
 }
 
 #[derive(Responder)]
@@ -41,65 +187,331 @@ impl Created<()> {
             inner: ()
         }
     }
+
+    /// Sends the given body along with the headers
+    fn with<T>(self, inner: T) -> Created<T> {
+        Created { inner, location: self.location, expose_location: self.expose_location }
+    }
 }
 
 #[post("/game")]
-fn new_game(shared: &State<KlondikeGames>) -> Created<()> {
-    let mut state = shared.repo.lock().unwrap();
-    let id = state.save(Klondike::new());
+fn new_game(shared: &State<KlondikeGames>) -> Result<Created<()>, Status> {
+    let klondike = Klondike::new_with_config(shared.config)
+        .map_err(|_e| Status::InternalServerError)?;
+    let id = shared.repo.save(klondike);
+
+    return Ok(Created::new(format!("/klondike/game/{}", id)));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Daily {
+    /// Day of the deal, as yyyy-mm-dd
+    date: String,
+}
+
+/// Creates the deal of the given day, today (UTC) if not given, so
+/// everyone playing that day gets the same game
+#[post("/game/daily?<date>")]
+fn daily_game(date: Option<String>, shared: &State<KlondikeGames>) -> Result<Created<Json<Daily>>, Status> {
+    let date = match date {
+        Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_e| Status::BadRequest)?,
+        None => Utc::now().date_naive(),
+    };
+    let config = KlondikeConfig { seed: Some(daily_seed(date)), ..shared.config };
+    let klondike = Klondike::new_with_config(config)
+        .map_err(|_e| Status::InternalServerError)?;
+    let id = shared.repo.save(klondike);
 
-    return Created::new(format!("/klondike/game/{}", id));
+    let daily = Daily { date: date.format("%Y-%m-%d").to_string() };
+    Ok(Created::new(format!("/klondike/game/{}", id)).with(Json(daily)))
 }
 
-#[get("/game/<uuid>")]
-fn get_status(uuid: String, shared: &State<KlondikeGames>) 
-            -> ApiResponse<Option<KlondikeStatus>> {
+#[post("/game/import", data="<layout>")]
+fn import_game(layout: Result<Json<KlondikeLayout>, json::Error>, shared: &State<KlondikeGames>)
+            -> Result<Created<()>, (Status, String)> {
+    let layout = layout.map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let klondike = Klondike::from_layout(&layout, shared.config.draw_count)
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let id = shared.repo.save(klondike);
 
-    execute(uuid, shared, |_x: &mut Klondike| -> Status { Status::Ok })
+    Ok(Created::new(format!("/klondike/game/{}", id)))
+}
+
+#[get("/game/<uuid>", rank = 2)]
+fn get_status(uuid: String, shared: &State<KlondikeGames>) -> StatusResponse {
 
+    StatusResponse(match shared.repo.view(&uuid, |x| x.get_status()) {
+        Some(status) => {
+            shared.remember(&uuid, &status);
+            let etag = Some(etag(status.version));
+            ApiResponse { status: Status::Ok, json: Json(Some(status)), etag }
+        }
+        None => {
+            // The game may have expired
+            shared.forget(&uuid);
+            ApiResponse { status: Status::NotFound, json: Json(None), etag: None }
+        }
+    })
 }
 
+/// Only the parts of the status changed since the given version. All of
+/// them if that version wasn't sent recently.
+#[get("/game/<uuid>?<since_version>", rank = 1)]
+fn get_status_since(uuid: String, since_version: u64, shared: &State<KlondikeGames>)
+            -> ApiResponse<Option<KlondikeStatusDiff>> {
+
+    let status = match shared.repo.view(&uuid, |x| x.get_status()) {
+        Some(status) => status,
+        None => {
+            shared.forget(&uuid);
+            return ApiResponse { status: Status::NotFound, json: Json(None), etag: None };
+        }
+    };
+
+    let diff = match shared.recent_status(&uuid, since_version) {
+        Some(prev) => status.diff(&prev),
+        None => status.as_diff(),
+    };
+    shared.remember(&uuid, &status);
+
+    ApiResponse { status: Status::Ok, etag: Some(etag(status.version)), json: Json(Some(diff)) }
+}
+
+/// Applies the action, unless the If-Match header is given and doesn't
+/// match the version of the game
 #[put("/game/<uuid>", data="<action>")]
-fn execute_action(uuid: String, action: Json<Action>, shared: &State<KlondikeGames>) 
-            ->  ApiResponse<Option<KlondikeStatus>> {
-
-    execute(uuid, shared, |x: &mut Klondike| -> Status {
-        match action.action.as_str() {
-            "take" => { x.take(); return Status::Ok },
-            "undo" => { x.undo(); return Status::Ok },
-            "move" => {
-                let from_o_ch = get_card_holder(action.from.as_ref().map(|x| x.as_str()));
-                let to_o_ch = get_card_holder(action.to.as_ref().map(|x| x.as_str()));
-                if let (Some(from_ch), Some(to_ch)) = (from_o_ch, to_o_ch) {
-                    if x.move_cards(from_ch, to_ch, action.number.unwrap_or(1)) {
-                        return Status::Ok;
-                    } else {
-                        return Status::Forbidden;
-                    }
-                } else {
-                    return Status::BadRequest;
-                }
-            },
-            _ => Status::BadRequest
+fn execute_action(uuid: String, action: Result<Json<Action>, json::Error>, if_match: IfMatch,
+            shared: &State<KlondikeGames>) -> Result<ApiResponse<Option<KlondikeStatus>>, (Status, String)> {
+
+    let action = action.map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let response = execute(uuid.clone(), shared, |x: &mut Klondike| -> Status {
+        if !if_match.matches(x.get_version()) {
+            return Status::PreconditionFailed;
         }
+        apply_action(x, &action)
+    });
+
+    log_action(&uuid, &action, response.status);
+    Ok(response)
+}
+
+/// Undoes the last action, like the undo action of PUT
+#[post("/game/<uuid>/undo")]
+fn undo(uuid: String, shared: &State<KlondikeGames>) -> ApiResponse<Option<KlondikeStatus>> {
+    let action = Action { action: ActionKind::Undo, from: None, to: None, number: None, seq: None };
+    let response = execute(uuid.clone(), shared, |x: &mut Klondike| -> Status {
+        x.undo();
+        Status::Ok
+    });
+
+    log_action(&uuid, &action, response.status);
+    response
+}
+
+/// Does again the last action undone, if no other action was done since
+#[post("/game/<uuid>/redo")]
+fn redo(uuid: String, shared: &State<KlondikeGames>) -> ApiResponse<Option<KlondikeStatus>> {
+    let action = Action { action: ActionKind::Redo, from: None, to: None, number: None, seq: None };
+    let response = execute(uuid.clone(), shared, |x: &mut Klondike| -> Status {
+        x.redo();
+        Status::Ok
+    });
+
+    log_action(&uuid, &action, response.status);
+    response
+}
+
+/// Keeps the game alive, as if it was accessed, without sending it
+#[post("/game/<uuid>/heartbeat")]
+fn heartbeat(uuid: String, shared: &State<KlondikeGames>) -> Status {
+    if shared.repo.touch(&uuid) { Status::NoContent } else { Status::NotFound }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Validation {
+    legal: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Checks whether the move given like in a move action is allowed,
+/// without doing it
+#[post("/game/<uuid>/validate", data="<action>")]
+fn validate_move(uuid: String, action: Result<Json<Action>, json::Error>, shared: &State<KlondikeGames>)
+            -> Result<Json<Validation>, Status> {
+
+    let action = action.map_err(|_e| Status::BadRequest)?;
+    let from = get_card_holder(action.from.as_deref());
+    let to = get_card_holder(action.to.as_deref());
+    let result = shared.repo.view(&uuid, |klondike| match (from, to) {
+        (Some(from), Some(to)) => klondike.check_move(from, to, action.number.unwrap_or(1))
+            .map_err(|e| e.to_string()),
+        (None, _) => Err("Invalid origin".to_string()),
+        (_, None) => Err("Invalid destination".to_string()),
+    }).ok_or(Status::NotFound)?;
+
+    Ok(Json(Validation { legal: result.is_ok(), reason: result.err() }))
+}
+
+/// Actions done so far, without the undone ones, as returned by replay
+#[get("/game/<uuid>/history")]
+fn get_history(uuid: String, shared: &State<KlondikeGames>) -> Option<Json<Vec<KlondikeAction>>> {
+    shared.repo.view(&uuid, |x| Json(x.replay()))
+}
+
+/// Holders where the top cards of the origin can be moved, as the
+/// tokens used by the move action
+#[get("/game/<uuid>/moves/<origin>")]
+fn legal_destinations(uuid: String, origin: String, shared: &State<KlondikeGames>)
+            -> Result<Json<Vec<String>>, Status> {
+
+    let origin = get_card_holder(Some(&origin)).ok_or(Status::BadRequest)?;
+    let destinations = shared.repo.view(&uuid, |x| x.legal_destinations(origin))
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(destinations.into_iter().map(card_holder_to_str).collect()))
+}
+
+#[derive(Deserialize)]
+struct Batch {
+    actions: Vec<Action>,
+}
+
+#[derive(Serialize)]
+struct BatchResult {
+    status: KlondikeStatus,
+    /// Whether each action of the batch was applied, in the same order
+    results: Vec<bool>,
+}
+
+/// Applies all the actions in order without letting other requests
+/// change the game in between. An action failing doesn't stop the rest.
+#[put("/game/<uuid>/batch", data="<batch>")]
+fn execute_batch(uuid: String, batch: Result<Json<Batch>, json::Error>, shared: &State<KlondikeGames>)
+            -> Result<ApiResponse<Option<BatchResult>>, (Status, String)> {
+
+    let batch = batch.map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let result = shared.repo.with_game(&uuid, |x| {
+        let results = batch.actions.iter()
+            .map(|action| {
+                let status = apply_action(x, action);
+                log_action(&uuid, action, status);
+                status == Status::Ok
+            })
+            .collect();
+        BatchResult { status: x.get_status(), results }
+    });
+
+    Ok(match result {
+        Some(result) => {
+            shared.remember(&uuid, &result.status);
+            ApiResponse {
+                etag: Some(etag(result.status.version)),
+                status: Status::Ok,
+                json: Json(Some(result)),
+            }
+        },
+        None => ApiResponse { status: Status::NotFound, json: Json(None), etag: None },
     })
 }
 
-#[delete("/game/<id>")]
-fn delete(id: String, shared: &State<KlondikeGames>) -> Status {
-    let mut repo = shared.repo.lock().unwrap();
+/// Records the action requested on the game and its result. Nothing is
+/// formatted unless a logger accepting it is installed.
+fn log_action(id: &str, action: &Action, status: Status) {
+    log::info!(target: "klondike::web", "game={} action={} from={:?} to={:?} number={:?} status={}",
+        id, action.action, action.from, action.to, action.number, status.code);
+}
+
+fn apply_action(x: &mut Klondike, action: &Action) -> Status {
+    if action.seq.is_some() && action.seq == x.get_last_seq() {
+        return Status::Ok;
+    }
+
+    let status = match action.action {
+        ActionKind::Take => { x.take(); Status::Ok },
+        ActionKind::Undo => { x.undo(); Status::Ok },
+        ActionKind::Redo => { x.redo(); Status::Ok },
+        ActionKind::Auto => { x.auto_moves(AutoPolicy::SafeHomes); Status::Ok },
+        ActionKind::TakeAuto => { x.take_and_autoplay(AutoPolicy::SafeHomes); Status::Ok },
+        ActionKind::Move => {
+            let from_o_ch = get_card_holder(action.from.as_ref().map(|x| x.as_str()));
+            let to_o_ch = get_card_holder(action.to.as_ref().map(|x| x.as_str()));
+            let number = action.number.unwrap_or(1);
+            if number == 0 || number > MAX_MOVE_NUMBER {
+                Status::BadRequest
+            } else if let (Some(from_ch), Some(to_ch)) = (from_o_ch, to_o_ch) {
+                if x.move_cards(from_ch, to_ch, number) {
+                    Status::Ok
+                } else {
+                    Status::Forbidden
+                }
+            } else {
+                Status::BadRequest
+            }
+        },
+    };
+
+    if status == Status::Ok && action.seq.is_some() {
+        x.set_last_seq(action.seq);
+    }
 
-    match repo.delete(&id) {
+    status
+}
+
+#[derive(Serialize, Deserialize)]
+struct Health {
+    status: String,
+    games: usize,
+}
+
+#[get("/health")]
+fn health(shared: &State<KlondikeGames>) -> Json<Health> {
+    let games = shared.repo.count();
+
+    Json(Health { status: "ok".to_string(), games })
+}
+
+/// Description of the routes and their bodies, following OpenAPI
+#[get("/openapi.json")]
+fn api_description() -> Json<serde_json::Value> {
+    Json(openapi::description())
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeletedGames {
+    deleted: usize,
+}
+
+#[delete("/games")]
+fn delete_all(token: AdminToken, shared: &State<KlondikeGames>) -> Result<Json<DeletedGames>, Status> {
+    match (&shared.admin_token, token.0) {
+        (Some(expected), Some(given)) if *expected == given => {},
+        _ => return Err(Status::Unauthorized),
+    }
+
+    let mut deleted = 0;
+    for id in shared.repo.list_ids() {
+        if shared.repo.delete(&id).is_some() {
+            deleted += 1;
+        }
+        shared.forget(&id);
+    }
+
+    Ok(Json(DeletedGames { deleted }))
+}
+
+#[delete("/game/<uuid>")]
+fn delete(uuid: String, shared: &State<KlondikeGames>) -> Status {
+    shared.forget(&uuid);
+    match shared.repo.delete(&uuid) {
         Some(_x) => Status::Ok,
         None => Status::NotFound
     }
 }
 
-#[options("/game/<id>")]
-fn options(id: String, shared: &State<KlondikeGames>) -> Status {
-    let repo = shared.repo.lock().unwrap();
-
-    match repo.get(&id) {
+#[options("/game/<uuid>")]
+fn options(uuid: String, shared: &State<KlondikeGames>) -> Status {
+    match shared.repo.view(&uuid, |_x| ()) {
         Some(_x) => Status::Ok,
         None => Status::NotFound
     }
@@ -110,15 +522,16 @@ fn execute<F: Fn(&mut Klondike) -> Status>(
             shared: &State<KlondikeGames>, 
             task: F) -> ApiResponse<Option<KlondikeStatus>> {
 
-    let mut repo = shared.repo.lock().unwrap();
+    let result = shared.repo.with_game(&id, |x| (task(x), x.get_status()));
 
-    if let Some(x) = repo.get(&id).as_mut() {
-        let task_result = task(x);
-        repo.update(id, x.clone());
-        return ApiResponse { status: task_result, json: Json(Option::Some(x.get_status()))};
-    }     
-    
-    ApiResponse { status: Status::NotFound, json: Json(Option::None)}
+    if let Some((task_result, status)) = result {
+        shared.remember(&id, &status);
+        let etag = Some(etag(status.version));
+        return ApiResponse { status: task_result, json: Json(Option::Some(status)), etag };
+    }
+
+    shared.forget(&id);
+    ApiResponse { status: Status::NotFound, json: Json(Option::None), etag: None }
 }
 
 
@@ -127,14 +540,43 @@ fn execute<F: Fn(&mut Klondike) -> Status>(
 struct ApiResponse<T: Serialize> {
     json: Json<T>,
     status: Status,
+    /// Version of the game, sent as the ETag header
+    etag: Option<String>,
 }
 
 impl<'r, 'o: 'r, T: Serialize> Responder<'r, 'o> for ApiResponse<T> {
     fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
-        Response::build_from(self.json.respond_to(&req).unwrap())
-            .status(self.status)
-            .header(ContentType::JSON)
-            .ok()
+        let mut response = Response::build_from(self.json.respond_to(&req).unwrap());
+        response.status(self.status).header(ContentType::JSON);
+        add_etag(&mut response, self.etag);
+        response.ok()
+    }
+}
+
+fn add_etag(response: &mut response::Builder, etag: Option<String>) {
+    if let Some(etag) = etag {
+        response.header(Header::new("ETag", etag))
+            .header(Header::new("Access-Control-Expose-Headers", "ETag"));
+    }
+}
+
+/// Status of a game, sent as the text board instead of JSON when the
+/// client prefers plain text on its Accept header
+#[derive(Debug)]
+struct StatusResponse(ApiResponse<Option<KlondikeStatus>>);
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for StatusResponse {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let wants_text = req.accept().is_some_and(|x| x.preferred().is_plain());
+        match self.0 {
+            ApiResponse { json: Json(Some(status)), status: code, etag } if wants_text => {
+                let mut response = Response::build_from(status.to_string().respond_to(req)?);
+                response.status(code).header(ContentType::Plain);
+                add_etag(&mut response, etag);
+                response.ok()
+            }
+            json => json.respond_to(req),
+        }
     }
 }
 
@@ -157,8 +599,12 @@ impl Fairing for CORS {
     }
 }
 
-#[rocket::main]
-pub async fn main_rocket() -> Result<(), Error> {
+pub fn main_rocket(config: KlondikeConfig) -> Result<(), Error> {
+    // This is what #[rocket::main] does, but it doesn't allow arguments
+    rocket::async_main(launch(config))
+}
+
+async fn launch(config: KlondikeConfig) -> Result<(), Error> {
     //TODO: Make repository choices configurable
     let repo = KlondikeHashMapRepository::new();
     let repo = KlondikeCleanUpRepository::new(
@@ -166,11 +612,626 @@ pub async fn main_rocket() -> Result<(), Error> {
         Duration::from_secs(15 * 60), // 15 Minutes
         HashMapTimeoutRepository::new()
     );
+    // The expired games in use are deleted once their actions end
+    let locks = repo.locks();
+
+    build_rocket(config, SharedRepository::new_with_locks(repo, locks)).launch().await.map(|_rocket| ())
+}
 
-    let state = KlondikeGames { repo: Arc::new(Mutex::new(repo))};
+fn build_rocket(config: KlondikeConfig, repo: SharedRepository) -> Rocket<Build> {
+    let state = KlondikeGames { 
+        repo, 
+        config,
+        admin_token: env::var("ADMIN_TOKEN").ok(),
+        recent: Mutex::new(RecentStatuses::new(MAX_RECENT_GAMES)),
+    };
 
     rocket::build()
         .attach(CORS)
-        .mount("/klondike", routes![new_game, get_status, execute_action, delete, options])
-        .manage(state).launch().await
+        .mount("/klondike", routes![new_game, daily_game, import_game, get_status, get_status_since, execute_action, execute_batch, undo, redo, heartbeat, validate_move, legal_destinations, get_history, delete, options, health, delete_all, api_description])
+        .manage(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::Accept;
+    use crate::card_game::klondike::test_common::*;
+
+    const ADMIN_TOKEN: &str = "test-admin-token";
+
+    /// Keeps the messages logged by the web handlers, so tests can check them
+    struct CapturingLogger;
+
+    static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static LOGGER: CapturingLogger = CapturingLogger;
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "klondike::web"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                LOGGED.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn client() -> Client {
+        // Installed before Rocket tries to install its own logger
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(log::LevelFilter::Info);
+        }
+        env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+        Client::tracked(build_rocket(KlondikeConfig::default(), SharedRepository::new(KlondikeHashMapRepository::new()))).unwrap()
+    }
+
+    #[test]
+    fn health() {
+        let client = client();
+        client.post("/klondike/game").dispatch();
+
+        let response = client.get("/klondike/health").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let health: Health = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(health.status, "ok");
+        assert_eq!(health.games, 1);
+    }
+
+    #[test]
+    fn api_description() {
+        let client = client();
+        let response = client.get("/klondike/openapi.json").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+        let description: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        let paths = description["paths"].as_object().unwrap();
+        for path in ["/klondike/game", "/klondike/game/daily", "/klondike/game/import", "/klondike/game/{uuid}",
+                "/klondike/game/{uuid}/undo", "/klondike/game/{uuid}/redo", "/klondike/game/{uuid}/heartbeat", "/klondike/game/{uuid}/validate", "/klondike/game/{uuid}/history",
+                "/klondike/game/{uuid}/moves/{origin}", "/klondike/game/{uuid}/batch", "/klondike/games",
+                "/klondike/health", "/klondike/openapi.json"] {
+            assert!(paths.contains_key(path), "{}", path);
+        }
+        assert_eq!(paths.len(), 14);
+
+        // Every route mounted is described
+        for route in client.rocket().routes() {
+            let path = route.uri.path().replace('<', "{").replace('>', "}");
+            let method = route.method.as_str().to_lowercase();
+            if method != "options" {
+                assert!(paths.get(&path).and_then(|x| x.get(&method)).is_some(), "{} {}", method, path);
+            }
+        }
+        assert!(description["components"]["schemas"]["Action"].is_object());
+        assert!(description["components"]["schemas"]["KlondikeStatus"].is_object());
+    }
+
+    #[test]
+    fn heartbeat() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.post(format!("{}/heartbeat", url)).dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let response = client.post("/klondike/game/xxxx/heartbeat").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    fn count_games(client: &Client) -> usize {
+        let response = client.get("/klondike/health").dispatch();
+        let health: Health = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        health.games
+    }
+
+    #[test]
+    fn delete_all_authorized() {
+        let client = client();
+        client.post("/klondike/game").dispatch();
+        client.post("/klondike/game").dispatch();
+
+        let response = client.delete("/klondike/games")
+            .header(Header::new("X-Admin-Token", ADMIN_TOKEN))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let deleted: DeletedGames = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(deleted.deleted, 2);
+        assert_eq!(count_games(&client), 0);
+    }
+
+    #[test]
+    fn delete_all_unauthorized() {
+        let client = client();
+        client.post("/klondike/game").dispatch();
+
+        let response = client.delete("/klondike/games").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client.delete("/klondike/games")
+            .header(Header::new("X-Admin-Token", "wrong"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        assert_eq!(count_games(&client), 1);
+    }
+
+    fn cards_on_stock(response: rocket::local::blocking::LocalResponse) -> u64 {
+        let status: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        status["deck"]["cards_on_stock"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn repeated_seq_is_applied_once() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+        let path = url.trim_start_matches("/klondike").to_string();
+        let initial = cards_on_stock(client.get(format!("/klondike{}", path)).dispatch());
+
+        for _i in 0..2 {
+            let response = client.put(format!("/klondike{}", path))
+                .body(r#"{"action":"take","seq":1}"#)
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(cards_on_stock(response), initial - 1);
+        }
+
+        let response = client.put(format!("/klondike{}", path))
+            .body(r#"{"action":"take","seq":2}"#)
+            .dispatch();
+        assert_eq!(cards_on_stock(response), initial - 2);
+    }
+
+    #[test]
+    fn move_number_out_of_range() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        for number in [0, 53, 1_000_000] {
+            let response = client.put(&url)
+                .body(format!(r#"{{"action":"move","from":"f7","to":"f1","number":{}}}"#, number))
+                .dispatch();
+            assert_eq!(response.status(), Status::BadRequest);
+        }
+
+        // Within range, but more than the column has
+        let response = client.put(&url)
+            .body(r#"{"action":"move","from":"f7","to":"f1","number":52}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let status: serde_json::Value = serde_json::from_str(&client.get(&url).dispatch().into_string().unwrap()).unwrap();
+        assert_eq!(status["version"], 0);
+    }
+
+    #[test]
+    fn well_formed_move() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.put(&url)
+            .body(r#"{"action":"move","from":"f7","to":"f1","number":1,"seq":1}"#)
+            .dispatch();
+        assert_ne!(response.status(), Status::BadRequest);
+        assert_eq!(client.put(&url).body(r#"{"action":"auto"}"#).dispatch().status(), Status::Ok);
+
+        let taken = cards_on_stock(client.get(&url).dispatch());
+        assert_eq!(client.put(&url).body(r#"{"action":"take_auto"}"#).dispatch().status(), Status::Ok);
+        assert_eq!(cards_on_stock(client.get(&url).dispatch()), taken - 1);
+
+        assert_eq!(client.put(&url).body(r#"{"action":"undo"}"#).dispatch().status(), Status::Ok);
+        assert_eq!(client.put(&url).body(r#"{"action":"redo"}"#).dispatch().status(), Status::Ok);
+        assert_eq!(cards_on_stock(client.get(&url).dispatch()), taken - 1);
+    }
+
+    #[test]
+    fn malformed_actions() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        for body in [r#"{"action":"shuffle"}"#, r#"{"action":"Take"}"#, r#"{"action":"take","cards":3}"#,
+                r#"{"from":"d","to":"p1"}"#] {
+            let response = client.put(&url).body(body).dispatch();
+            assert_eq!(response.status(), Status::BadRequest);
+            assert!(!response.into_string().unwrap().is_empty());
+        }
+
+        let response = client.put(format!("{}/batch", url))
+            .body(r#"{"actions":[{"action":"take"},{"action":"take","extra":true}]}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client.post(format!("{}/validate", url))
+            .body(r#"{"action":"move","from":"d","to":"p1","extra":1}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // Nothing was applied
+        let status: serde_json::Value = serde_json::from_str(&client.get(&url).dispatch().into_string().unwrap()).unwrap();
+        assert_eq!(status["version"], 0);
+    }
+
+    #[test]
+    fn daily_game() {
+        let client = client();
+        let mut statuses = Vec::new();
+        for _i in 0..2 {
+            let response = client.post("/klondike/game/daily?date=2024-02-29").dispatch();
+            assert_eq!(response.status(), Status::Created);
+            let url = response.headers().get_one("Location").unwrap().to_string();
+            let daily: Daily = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+            assert_eq!(daily.date, "2024-02-29");
+
+            let status: serde_json::Value = serde_json::from_str(&client.get(url).dispatch().into_string().unwrap()).unwrap();
+            statuses.push(status["foundations"].clone());
+        }
+        assert_eq!(statuses[0], statuses[1]);
+
+        let response = client.post("/klondike/game/daily").dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let daily: Daily = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(daily.date, Utc::now().date_naive().format("%Y-%m-%d").to_string());
+
+        let response = client.post("/klondike/game/daily?date=2024-02-30").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn import_game() {
+        let client = client();
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let two = Card { suit: CardSuit::SPADES, rank: CardRank::TWO };
+        let five = Card { suit: CardSuit::DIAMONDS, rank: CardRank::FIVE };
+        let king = Card { suit: CardSuit::HEARTS, rank: CardRank::KING };
+        let three = Card { suit: CardSuit::CLUBS, rank: CardRank::THREE };
+        let layout = layout_with(
+            vec![vec![ace, two], Vec::new()],
+            vec![FoundationLayout { hidden: vec![five], visible: vec![king] }],
+            vec![three],
+        );
+
+        let response = client.post("/klondike/game/import")
+            .body(serde_json::to_string(&layout).unwrap())
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.get(url).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let status: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(status["deck"]["cards_on_stock"], 47);
+        assert_eq!(status["deck"]["top_card_on_waste"]["rank"], "THREE");
+        assert_eq!(status["piles"][0]["num_cards"], 2);
+        assert_eq!(status["piles"].as_array().unwrap().len(), 2);
+        assert_eq!(status["foundations"][0]["num_hidden"], 1);
+        assert_eq!(status["foundations"][0]["visible"][0]["suit"], "HEARTS");
+    }
+
+    #[test]
+    fn import_invalid_game() {
+        let client = client();
+        let mut layout = layout_with(Vec::new(), Vec::new(), Vec::new());
+        layout.stock.pop();
+
+        let response = client.post("/klondike/game/import")
+            .body(serde_json::to_string(&layout).unwrap())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(response.into_string().unwrap(), "Expected 52 cards, found 51");
+
+        let response = client.post("/klondike/game/import").body("{\"stock\": []}").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        assert_eq!(count_games(&client), 0);
+    }
+
+    #[test]
+    fn execute_batch() {
+        let client = client();
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let mut layout = layout_with(vec![Vec::new(); 4], Vec::new(), Vec::new());
+        // The ace is the next card to take
+        layout.stock.retain(|x| *x != ace);
+        layout.stock.push(ace);
+        let response = client.post("/klondike/game/import")
+            .body(serde_json::to_string(&layout).unwrap())
+            .dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        // The second move fails, as the waste is empty by then
+        let response = client.put(format!("{}/batch", url))
+            .body(r#"{"actions":[
+                {"action":"take"},
+                {"action":"move","from":"d","to":"p1"},
+                {"action":"move","from":"d","to":"p2"},
+                {"action":"take"}
+            ]}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let result: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(result["results"], serde_json::json!([true, true, false, true]));
+        assert_eq!(result["status"]["piles"][0]["num_cards"], 1);
+        assert_eq!(result["status"]["deck"]["cards_on_stock"], 50);
+        assert_eq!(result["status"]["deck"]["top_card_on_waste"]["rank"], "KING");
+
+        let response = client.put("/klondike/game/invalid/batch")
+            .body(r#"{"actions":[{"action":"take"}]}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn get_status_as_text() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.get(&url).header(Accept::Plain).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::Plain));
+        assert_eq!(response.headers().get_one("ETag"), Some("\"0\""));
+        let board = response.into_string().unwrap();
+        let shared = client.rocket().state::<KlondikeGames>().unwrap();
+        let id = url.rsplit('/').next().unwrap().to_string();
+        assert_eq!(board, shared.repo.view(&id, |x| x.get_status().to_string()).unwrap());
+
+        // Unknown games are still answered with JSON
+        let response = client.get("/klondike/game/xxxx").header(Accept::Plain).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+    }
+
+    #[test]
+    fn get_status_as_json() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        for response in [client.get(&url).header(Accept::JSON).dispatch(), client.get(&url).dispatch()] {
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.content_type(), Some(ContentType::JSON));
+            let status: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+            assert_eq!(status["version"], 0);
+        }
+    }
+
+    #[test]
+    fn if_match_version() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.get(url.clone()).dispatch();
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+        assert_eq!(etag, "\"0\"");
+
+        let response = client.put(url.clone())
+            .header(Header::new("If-Match", etag.clone()))
+            .body(r#"{"action":"take"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some("\"1\""));
+
+        // Someone else already changed the game
+        let response = client.put(url.clone())
+            .header(Header::new("If-Match", etag))
+            .body(r#"{"action":"take"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::PreconditionFailed);
+        let status: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(status["version"], 1);
+    }
+
+    #[test]
+    fn get_status_since_version() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+        client.get(url.clone()).dispatch();
+        client.put(url.clone()).body(r#"{"action":"take"}"#).dispatch();
+
+        let response = client.get(format!("{}?since_version=0", url)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let diff: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(diff["since_version"], 0);
+        assert_eq!(diff["version"], 1);
+        assert!(diff["deck"].is_object());
+        assert!(diff.get("piles").is_none());
+        assert!(diff.get("foundations").is_none());
+
+        // Unknown versions get everything
+        let response = client.get(format!("{}?since_version=99", url)).dispatch();
+        let diff: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert!(diff["since_version"].is_null());
+        assert_eq!(diff["foundations"].as_object().unwrap().len(), 7);
+
+        let response = client.get("/klondike/game/invalid?since_version=0").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn recent_statuses_forget_the_least_recently_used() {
+        let status = Klondike::new_with_seed(1).get_status();
+        let mut recent = RecentStatuses::new(2);
+        recent.remember("a", &status);
+        recent.remember("b", &status);
+        recent.remember("a", &status);
+        recent.remember("c", &status);
+
+        assert!(recent.get("a", status.version).is_some());
+        assert!(recent.get("b", status.version).is_none());
+        assert!(recent.get("c", status.version).is_some());
+        assert_eq!(recent.games.len(), 2);
+    }
+
+    #[test]
+    fn missing_games_are_forgotten() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+        let id = url.rsplit('/').next().unwrap().to_string();
+        client.get(url.clone()).dispatch();
+
+        let shared = client.rocket().state::<KlondikeGames>().unwrap();
+        assert!(shared.recent.lock().unwrap().games.contains_key(&id));
+        // Deleted behind the web API, like the expired games
+        shared.repo.delete(&id);
+        let response = client.get(format!("{}?since_version=0", url)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert!(!shared.recent.lock().unwrap().games.contains_key(&id));
+    }
+
+    #[test]
+    fn validate_move() {
+        let client = client();
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let layout = layout_with(vec![Vec::new(); 4], Vec::new(), vec![ace]);
+        let response = client.post("/klondike/game/import")
+            .body(serde_json::to_string(&layout).unwrap())
+            .dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.post(format!("{}/validate", url))
+            .body(r#"{"action":"move","from":"d","to":"p1"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let validation: Validation = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert!(validation.legal);
+        assert_eq!(validation.reason, None);
+
+        let response = client.post(format!("{}/validate", url))
+            .body(r#"{"action":"move","from":"d","to":"p1","number":2}"#)
+            .dispatch();
+        let validation: Validation = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert!(!validation.legal);
+        assert_eq!(validation.reason.unwrap(), "There aren't 2 cards to take from the origin");
+
+        // Nothing was moved
+        let status: serde_json::Value = serde_json::from_str(&client.get(url).dispatch().into_string().unwrap()).unwrap();
+        assert_eq!(status["deck"]["cards_on_waste"], 1);
+        assert_eq!(status["version"], 0);
+    }
+
+    #[test]
+    fn legal_destinations() {
+        let client = client();
+        let ace = Card { suit: CardSuit::HEARTS, rank: CardRank::ACE };
+        let layout = layout_with(vec![Vec::new(); 2], Vec::new(), vec![ace]);
+        let response = client.post("/klondike/game/import")
+            .body(serde_json::to_string(&layout).unwrap())
+            .dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.get(format!("{}/moves/d", url)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let destinations: Vec<String> = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(destinations, vec!["p1", "p2"]);
+
+        assert_eq!(client.get(format!("{}/moves/x1", url)).dispatch().status(), Status::BadRequest);
+        assert_eq!(client.get("/klondike/game/invalid/moves/d").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn actions_are_logged() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+        let id = url.rsplit('/').next().unwrap().to_string();
+
+        client.put(&url).body(r#"{"action":"move","from":"p1","to":"p2"}"#).dispatch();
+        client.put("/klondike/game/invalid").body(r#"{"action":"take"}"#).dispatch();
+
+        let logged = LOGGED.lock().unwrap();
+        assert!(logged.contains(&format!(
+            "game={} action=move from=Some(\"p1\") to=Some(\"p2\") number=None status=403", id)));
+        assert!(logged.contains(&"game=invalid action=take from=None to=None number=None status=404".to_string()));
+    }
+
+    #[test]
+    fn get_history() {
+        let client = client();
+        let ace = Card { suit: CardSuit::SPADES, rank: CardRank::ACE };
+        let mut layout = layout_with(vec![Vec::new(); 4], Vec::new(), Vec::new());
+        layout.stock.retain(|x| *x != ace);
+        layout.stock.push(ace);
+        let response = client.post("/klondike/game/import")
+            .body(serde_json::to_string(&layout).unwrap())
+            .dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        for action in [r#"{"action":"take"}"#, r#"{"action":"move","from":"d","to":"p1"}"#, r#"{"action":"take"}"#,
+                r#"{"action":"take"}"#, r#"{"action":"undo"}"#].iter() {
+            client.put(&url).body(*action).dispatch();
+        }
+
+        let response = client.get(format!("{}/history", url)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let history: Vec<KlondikeAction> = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(history, vec![
+            KlondikeAction::TAKE,
+            KlondikeAction::MOVE(CardHolder::DECK, CardHolder::PILE(0), 1),
+            KlondikeAction::TAKE,
+        ]);
+
+        assert_eq!(client.get("/klondike/game/invalid/history").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn undo_endpoint() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+        let initial: serde_json::Value = serde_json::from_str(&client.get(&url).dispatch().into_string().unwrap()).unwrap();
+
+        let response = client.put(&url).body(r#"{"action":"take"}"#).dispatch();
+        let taken: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_ne!(taken["deck"], initial["deck"]);
+
+        let response = client.post(format!("{}/undo", url)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some("\"2\""));
+        let undone: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(undone["deck"], initial["deck"]);
+        assert_eq!(undone["foundations"], initial["foundations"]);
+        let fetched: serde_json::Value = serde_json::from_str(&client.get(&url).dispatch().into_string().unwrap()).unwrap();
+        assert_eq!(fetched, undone);
+
+        assert_eq!(client.post("/klondike/game/invalid/undo").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn redo_endpoint() {
+        let client = client();
+        let response = client.post("/klondike/game").dispatch();
+        let url = response.headers().get_one("Location").unwrap().to_string();
+
+        let response = client.put(&url).body(r#"{"action":"take"}"#).dispatch();
+        let taken: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        client.post(format!("{}/undo", url)).dispatch();
+
+        let response = client.post(format!("{}/redo", url)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some("\"3\""));
+        let redone: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(redone["deck"], taken["deck"]);
+        assert_eq!(redone["foundations"], taken["foundations"]);
+        let fetched: serde_json::Value = serde_json::from_str(&client.get(&url).dispatch().into_string().unwrap()).unwrap();
+        assert_eq!(fetched, redone);
+
+        // Nothing left to redo
+        let response = client.post(format!("{}/redo", url)).dispatch();
+        assert_eq!(response.headers().get_one("ETag"), Some("\"3\""));
+
+        assert_eq!(client.post("/klondike/game/invalid/redo").dispatch().status(), Status::NotFound);
+    }
 }
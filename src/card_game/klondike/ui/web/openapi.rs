@@ -0,0 +1,285 @@
+use serde_json::{json, Value};
+
+/// Hand written OpenAPI description of the routes mounted on /klondike,
+/// for clients generating their code from it
+pub(super) fn description() -> Value {
+    let uuid = json!({ "name": "uuid", "in": "path", "required": true, "schema": { "type": "string" } });
+    let status = json!({
+        "description": "Status of the game, with its version as the ETag header",
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/KlondikeStatus" } } }
+    });
+    let created = json!({ "description": "Game created, with its url as the Location header" });
+    let not_found = json!({ "description": "No game with that id" });
+    let bad_request = json!({ "description": "Malformed request" });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Klondike",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/klondike/game": {
+                "post": {
+                    "summary": "Deals a new game",
+                    "responses": { "201": created },
+                },
+            },
+            "/klondike/game/daily": {
+                "post": {
+                    "summary": "Deals the game of the given day, today if none",
+                    "parameters": [
+                        { "name": "date", "in": "query", "required": false,
+                          "schema": { "type": "string", "format": "date" } },
+                    ],
+                    "responses": { "201": created, "400": bad_request },
+                },
+            },
+            "/klondike/game/import": {
+                "post": {
+                    "summary": "Creates a game with its cards placed as given",
+                    "requestBody": { "content": { "application/json": {
+                        "schema": { "$ref": "#/components/schemas/KlondikeLayout" } } } },
+                    "responses": { "201": created, "400": bad_request },
+                },
+            },
+            "/klondike/game/{uuid}": {
+                "get": {
+                    "summary": "Status of the game, or the parts changed since since_version. \
+                        The board as text if plain text is accepted",
+                    "parameters": [
+                        uuid,
+                        { "name": "since_version", "in": "query", "required": false,
+                          "schema": { "type": "integer" } },
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "Status of the game",
+                            "content": {
+                                "application/json": { "schema": { "$ref": "#/components/schemas/KlondikeStatus" } },
+                                "text/plain": { "schema": { "type": "string" } },
+                            },
+                        },
+                        "404": not_found,
+                    },
+                },
+                "put": {
+                    "summary": "Applies an action, unless If-Match doesn't match the version",
+                    "parameters": [
+                        uuid,
+                        { "name": "If-Match", "in": "header", "required": false, "schema": { "type": "string" } },
+                    ],
+                    "requestBody": { "content": { "application/json": {
+                        "schema": { "$ref": "#/components/schemas/Action" } } } },
+                    "responses": {
+                        "200": status,
+                        "400": bad_request,
+                        "403": { "description": "The move is not allowed" },
+                        "404": not_found,
+                        "412": { "description": "The game changed since the given version" },
+                    },
+                },
+                "delete": {
+                    "summary": "Deletes the game",
+                    "parameters": [uuid],
+                    "responses": { "200": { "description": "Game deleted" }, "404": not_found },
+                },
+            },
+            "/klondike/game/{uuid}/undo": {
+                "post": {
+                    "summary": "Undoes the last action",
+                    "parameters": [uuid],
+                    "responses": { "200": status, "404": not_found },
+                },
+            },
+            "/klondike/game/{uuid}/redo": {
+                "post": {
+                    "summary": "Does again the last action undone",
+                    "parameters": [uuid],
+                    "responses": { "200": status, "404": not_found },
+                },
+            },
+            "/klondike/game/{uuid}/heartbeat": {
+                "post": {
+                    "summary": "Keeps the game alive without sending it",
+                    "parameters": [uuid],
+                    "responses": { "204": { "description": "Game kept" }, "404": not_found },
+                },
+            },
+            "/klondike/game/{uuid}/validate": {
+                "post": {
+                    "summary": "Checks whether a move is allowed, without doing it",
+                    "parameters": [uuid],
+                    "requestBody": { "content": { "application/json": {
+                        "schema": { "$ref": "#/components/schemas/Action" } } } },
+                    "responses": {
+                        "200": { "description": "Whether the move is legal, and why not",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "legal": { "type": "boolean" },
+                                    "reason": { "type": "string" },
+                                },
+                            } } } },
+                        "400": bad_request,
+                        "404": not_found,
+                    },
+                },
+            },
+            "/klondike/game/{uuid}/history": {
+                "get": {
+                    "summary": "Actions done so far, without the undone ones",
+                    "parameters": [uuid],
+                    "responses": { "200": { "description": "Actions in the order they were done" }, "404": not_found },
+                },
+            },
+            "/klondike/game/{uuid}/moves/{origin}": {
+                "get": {
+                    "summary": "Holders where the top cards of the origin can be moved",
+                    "parameters": [
+                        uuid,
+                        { "name": "origin", "in": "path", "required": true,
+                          "schema": { "$ref": "#/components/schemas/CardHolder" } },
+                    ],
+                    "responses": {
+                        "200": { "description": "Destinations",
+                            "content": { "application/json": { "schema": {
+                                "type": "array", "items": { "$ref": "#/components/schemas/CardHolder" },
+                            } } } },
+                        "400": bad_request,
+                        "404": not_found,
+                    },
+                },
+            },
+            "/klondike/game/{uuid}/batch": {
+                "put": {
+                    "summary": "Applies several actions in order. A failing one doesn't stop the rest",
+                    "parameters": [uuid],
+                    "requestBody": { "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": {
+                            "actions": { "type": "array", "items": { "$ref": "#/components/schemas/Action" } },
+                        },
+                    } } } },
+                    "responses": {
+                        "200": { "description": "Status of the game and whether each action was applied",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "status": { "$ref": "#/components/schemas/KlondikeStatus" },
+                                    "results": { "type": "array", "items": { "type": "boolean" } },
+                                },
+                            } } } },
+                        "400": bad_request,
+                        "404": not_found,
+                    },
+                },
+            },
+            "/klondike/games": {
+                "delete": {
+                    "summary": "Deletes every game",
+                    "parameters": [
+                        { "name": "X-Admin-Token", "in": "header", "required": true, "schema": { "type": "string" } },
+                    ],
+                    "responses": { "200": { "description": "Number of games deleted" },
+                        "401": { "description": "Missing or wrong admin token" } },
+                },
+            },
+            "/klondike/health": {
+                "get": {
+                    "summary": "Whether the server is up, and how many games it keeps",
+                    "responses": { "200": { "description": "Health of the server" } },
+                },
+            },
+            "/klondike/openapi.json": {
+                "get": {
+                    "summary": "This document",
+                    "responses": { "200": { "description": "OpenAPI description of the API" } },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "Action": {
+                    "type": "object",
+                    "required": ["action"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "action": { "type": "string", "enum": ["take", "undo", "redo", "move", "auto", "take_auto"] },
+                        "from": { "$ref": "#/components/schemas/CardHolder" },
+                        "to": { "$ref": "#/components/schemas/CardHolder" },
+                        "number": { "type": "integer", "minimum": 1, "maximum": 52 },
+                        "seq": { "type": "integer" },
+                    },
+                },
+                "CardHolder": {
+                    "type": "string",
+                    "description": "d for the deck, p1, p2... for the piles and f1, f2... for the foundations",
+                    "pattern": "^(d|[pf][0-9]+)$",
+                },
+                "Card": {
+                    "type": "object",
+                    "properties": {
+                        "suit": { "type": "string", "enum": ["SPADES", "HEARTS", "DIAMONDS", "CLUBS"] },
+                        "rank": { "type": "string", "enum": ["ACE", "TWO", "THREE", "FOUR", "FIVE", "SIX",
+                            "SEVEN", "EIGHT", "NINE", "TEN", "JACK", "QUEEN", "KING"] },
+                    },
+                },
+                "KlondikeLayout": {
+                    "type": "object",
+                    "properties": {
+                        "stock": { "type": "array", "items": { "$ref": "#/components/schemas/Card" } },
+                        "waste": { "type": "array", "items": { "$ref": "#/components/schemas/Card" } },
+                        "piles": { "type": "array", "items": {
+                            "type": "array", "items": { "$ref": "#/components/schemas/Card" } } },
+                        "foundations": { "type": "array", "items": {
+                            "type": "object",
+                            "properties": {
+                                "hidden": { "type": "array", "items": { "$ref": "#/components/schemas/Card" } },
+                                "visible": { "type": "array", "items": { "$ref": "#/components/schemas/Card" } },
+                            },
+                        } },
+                    },
+                },
+                "KlondikeStatus": {
+                    "type": "object",
+                    "properties": {
+                        "deck": {
+                            "type": "object",
+                            "properties": {
+                                "cards_on_waste": { "type": "integer" },
+                                "cards_on_stock": { "type": "integer" },
+                                "top_card_on_waste": { "$ref": "#/components/schemas/Card", "nullable": true },
+                                "cards_until_recycle": { "type": "integer" },
+                                "can_take": { "type": "boolean" },
+                            },
+                        },
+                        "piles": { "type": "array", "items": {
+                            "type": "object",
+                            "properties": {
+                                "top_card": { "$ref": "#/components/schemas/Card", "nullable": true },
+                                "num_cards": { "type": "integer" },
+                                "suit": { "type": "string", "nullable": true },
+                            },
+                        } },
+                        "foundations": { "type": "array", "items": {
+                            "type": "object",
+                            "properties": {
+                                "num_hidden": { "type": "integer" },
+                                "visible": { "type": "array", "items": { "$ref": "#/components/schemas/Card" } },
+                            },
+                        } },
+                        "flips": { "type": "integer" },
+                        "score": { "type": "integer" },
+                        "undo_available": { "type": "boolean" },
+                        "homes_by_suit": { "type": "object",
+                            "additionalProperties": { "type": "string", "nullable": true } },
+                        "difficulty": { "type": "string", "enum": ["Easy", "Medium", "Hard"], "nullable": true },
+                        "version": { "type": "integer" },
+                        "last_modified": { "type": "integer" },
+                    },
+                },
+            },
+        },
+    })
+}
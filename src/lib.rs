@@ -1 +1,3 @@
+#[macro_use] extern crate rocket;
 
+pub mod card_game;
@@ -1,19 +1,194 @@
-pub mod card_game;
-
-#[macro_use] extern crate rocket;
-
+use klondike::card_game;
 use card_game::klondike::ui::*;
+use card_game::klondike::{Klondike, KlondikeConfig};
 use std::env;
+use std::fs;
+use std::process;
 
 fn main() {
-    if env::args().any(|arg| -> bool {"-web".eq(&arg)}) {
-        if let Err(e) = web::main_rocket() {
+    let args: Vec<String> = env::args().collect();
+
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("Usage: klondike [-web] [--seed <number>] [--draw-three] [--replay <file>]");
+            process::exit(1);
+        }
+    };
+
+    if let Some(path) = &options.replay {
+        match load_game(path) {
+            Ok(klondike) => println!("{}", klondike.get_status()),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        }
+    } else if options.web {
+        if let Err(e) = web::main_rocket(options.config()) {
             println!("Whoops! Rocket didn't launch!");
             // We drop the error to get a Rocket-formatted panic.
             drop(e);
         }
     } else {
-        console::game();
+        console::game(options.config());
+    }
+}
+
+/// Options given in the command line
+#[derive(Debug, PartialEq)]
+struct Options {
+    web: bool,
+    seed: Option<u64>,
+    draw_three: bool,
+    /// File with a game saved with to_bytes, to print its board
+    replay: Option<String>,
+}
+
+impl Options {
+    fn config(&self) -> KlondikeConfig {
+        KlondikeConfig {
+            draw_count: if self.draw_three { 3 } else { 1 },
+            seed: self.seed,
+            ..KlondikeConfig::default()
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    Ok(Options {
+        web: args.iter().any(|arg| -> bool {"-web".eq(arg)}),
+        seed: parse_seed(args)?,
+        draw_three: args.iter().any(|arg| -> bool {"--draw-three".eq(arg)}),
+        replay: parse_replay(args)?,
+    })
+}
+
+/// Returns the file following "--replay", if that flag is present.
+fn parse_replay(args: &[String]) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == "--replay") {
+        None => Ok(None),
+        Some(idx) => args.get(idx + 1)
+            .map(|path| Some(path.clone()))
+            .ok_or_else(|| "Missing file after --replay".to_string()),
+    }
+}
+
+/// Restores the game saved on the given file with to_bytes
+fn load_game(path: &str) -> Result<Klondike, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    Klondike::from_bytes(&bytes).map_err(|_e| format!("Invalid game file: {}", path))
+}
+
+/// Returns the number following "--seed", if that flag is present.
+fn parse_seed(args: &[String]) -> Result<Option<u64>, String> {
+    match args.iter().position(|arg| arg == "--seed") {
+        None => Ok(None),
+        Some(idx) => match args.get(idx + 1) {
+            None => Err("Missing number after --seed".to_string()),
+            Some(value) => value.parse::<u64>()
+                .map(Some)
+                .map_err(|_e| format!("Invalid seed: {}", value)),
+        },
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn single_card_holder() {
+        // The ui takes the holders of the klondike module, the only ones
+        let holder = card_game::klondike::CardHolder::FOUNDATION(0);
+        assert_eq!(card_holder_to_str(holder), "f1");
+    }
+
+    #[test]
+    fn parse_seed_present() {
+        assert_eq!(parse_seed(&args(&["klondike", "--seed", "42"])), Ok(Some(42)));
+        assert_eq!(parse_seed(&args(&["klondike", "--seed", "7", "-web"])), Ok(Some(7)));
+    }
+
+    #[test]
+    fn parse_seed_absent() {
+        assert_eq!(parse_seed(&args(&["klondike"])), Ok(None));
+        assert_eq!(parse_seed(&args(&["klondike", "-web"])), Ok(None));
+    }
+
+    #[test]
+    fn parse_seed_invalid() {
+        assert!(parse_seed(&args(&["klondike", "--seed"])).is_err());
+        assert!(parse_seed(&args(&["klondike", "--seed", "abc"])).is_err());
+        assert!(parse_seed(&args(&["klondike", "--seed", "-1"])).is_err());
+    }
+
+    #[test]
+    fn parse_options_combinations() {
+        assert_eq!(
+            parse_options(&args(&["klondike"])),
+            Ok(Options { web: false, seed: None, draw_three: false, replay: None })
+        );
+        assert_eq!(
+            parse_options(&args(&["klondike", "-web", "--draw-three"])),
+            Ok(Options { web: true, seed: None, draw_three: true, replay: None })
+        );
+        assert_eq!(
+            parse_options(&args(&["klondike", "--draw-three", "--seed", "5"])),
+            Ok(Options { web: false, seed: Some(5), draw_three: true, replay: None })
+        );
+        assert_eq!(
+            parse_options(&args(&["klondike", "--seed", "5", "-web"])),
+            Ok(Options { web: true, seed: Some(5), draw_three: false, replay: None })
+        );
+        assert!(parse_options(&args(&["klondike", "-web", "--seed"])).is_err());
+    }
+
+    #[test]
+    fn parse_replay_file() {
+        assert_eq!(parse_replay(&args(&["klondike", "--replay", "game.bin"])), Ok(Some("game.bin".to_string())));
+        assert_eq!(parse_replay(&args(&["klondike", "-web"])), Ok(None));
+        assert!(parse_replay(&args(&["klondike", "--replay"])).is_err());
+        assert_eq!(
+            parse_options(&args(&["klondike", "--replay", "game.bin"])).map(|x| x.replay),
+            Ok(Some("game.bin".to_string()))
+        );
+    }
+
+    #[test]
+    fn load_game_file() {
+        let mut klondike = Klondike::new_with_seed(8);
+        klondike.take_n(3);
+        let path = env::temp_dir().join(format!("klondike-replay-{}.bin", process::id()));
+        fs::write(&path, klondike.to_bytes()).unwrap();
+
+        let loaded = load_game(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.get_status(), klondike.get_status());
+        assert_eq!(loaded.replay(), klondike.replay());
+
+        // Missing and corrupted files
+        assert!(load_game(path.to_str().unwrap()).is_err());
+        let path = env::temp_dir().join(format!("klondike-invalid-{}.bin", process::id()));
+        fs::write(&path, [1, 2, 3]).unwrap();
+        let loaded = load_game(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.err(), Some(format!("Invalid game file: {}", path.to_str().unwrap())));
+    }
+
+    #[test]
+    fn options_config() {
+        let config = Options { web: false, seed: Some(3), draw_three: true, replay: None }.config();
+        assert_eq!(config.draw_count, 3);
+        assert_eq!(config.seed, Some(3));
+
+        let config = Options { web: true, seed: None, draw_three: false, replay: None }.config();
+        assert_eq!(config, KlondikeConfig::default());
+    }
+}